@@ -0,0 +1,90 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_polyline::prelude::*;
+
+// This example demonstrates how to color a line from per-vertex scalars with a colormap.
+//
+// It should display on screen a helix colored from bottom to top with the viridis colormap.
+//
+// Press SPACE to switch between the viridis and turbo colormaps, and UP and DOWN to
+// narrow or widen the scalar range. Only the material uniform changes, the polyline
+// itself is never touched.
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(PolylinePlugin)
+        .add_systems(Startup, setup)
+        .add_systems(Update, update_colormap)
+        .run();
+}
+
+#[derive(Resource)]
+struct HelixMaterial(Handle<PolylineMaterial>);
+
+fn setup(
+    mut commands: Commands,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+    mut polylines: ResMut<Assets<Polyline>>,
+) {
+    let vertices: Vec<Vec3> = (0..=1000)
+        .map(|i| {
+            let t = i as f32 / 1000.0;
+            let angle = t * 8.0 * TAU;
+            Vec3::new(angle.cos(), t * 4.0 - 2.0, angle.sin())
+        })
+        .collect();
+    // Use the height of each vertex as its scalar
+    let scalars = vertices.iter().map(|v| v.y).collect();
+
+    let material = polyline_materials.add(PolylineMaterial {
+        width: 5.0,
+        colormap: Some(PolylineColormap::Viridis),
+        scalar_min: -2.0,
+        scalar_max: 2.0,
+        ..default()
+    });
+    commands.insert_resource(HelixMaterial(material.clone()));
+
+    commands.spawn(PolylineBundle {
+        polyline: PolylineHandle(polylines.add(Polyline {
+            vertices,
+            scalars,
+            ..default()
+        })),
+        material: PolylineMaterialHandle(material),
+        ..default()
+    });
+
+    // camera
+    commands.spawn((
+        Camera3d::default(),
+        Msaa::Sample4,
+        Transform::from_xyz(0.0, 1.0, 6.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+}
+
+fn update_colormap(
+    input: Res<ButtonInput<KeyCode>>,
+    helix_material: Res<HelixMaterial>,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+) {
+    if !input.any_pressed([KeyCode::Space, KeyCode::ArrowUp, KeyCode::ArrowDown]) {
+        return;
+    }
+    let Some(material) = polyline_materials.get_mut(&helix_material.0) else {
+        return;
+    };
+    if input.just_pressed(KeyCode::Space) {
+        material.colormap = match material.colormap {
+            Some(PolylineColormap::Viridis) => Some(PolylineColormap::Turbo),
+            _ => Some(PolylineColormap::Viridis),
+        };
+    }
+    if input.pressed(KeyCode::ArrowUp) {
+        material.scalar_max = (material.scalar_max - 0.02).max(material.scalar_min + 0.1);
+    }
+    if input.pressed(KeyCode::ArrowDown) {
+        material.scalar_max += 0.02;
+    }
+}
@@ -76,12 +76,14 @@ fn setup(
     commands.spawn(PolylineBundle {
         polyline: PolylineHandle(polylines.add(Polyline {
             vertices: vec![top, bottom],
+            ..Default::default()
         })),
         material: PolylineMaterialHandle(materials.add(PolylineMaterial {
             width: 5.0,
             color: RED.into(),
             depth_bias: -1.0,
             perspective: false,
+            ..Default::default()
         })),
         ..Default::default()
     });
@@ -93,12 +95,14 @@ fn setup(
         commands.spawn(PolylineBundle {
             polyline: PolylineHandle(polylines.add(Polyline {
                 vertices: vec![left, right],
+                ..Default::default()
             })),
             material: PolylineMaterialHandle(materials.add(PolylineMaterial {
                 width: 1.0,
                 color: Color::hsl((bias + 1.0) / 2.0 * 270.0, 1.0, 0.5).to_linear(),
                 depth_bias: bias,
                 perspective: false,
+                ..Default::default()
             })),
             ..Default::default()
         });
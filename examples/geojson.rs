@@ -0,0 +1,73 @@
+use bevy::{
+    color::palettes::css::{GOLD, LIME, RED},
+    prelude::*,
+};
+use bevy_polyline::prelude::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(PolylinePlugin)
+        .add_systems(Startup, setup)
+        .add_systems(Update, spawn_trails)
+        .run();
+}
+
+#[derive(Resource)]
+struct Trails(Handle<GeoJsonPolylines>);
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    // Positions are projected to meters around the center of the trails, with their elevation
+    let trails = asset_server.load_with_settings(
+        "trails.geojson",
+        |settings: &mut GeoJsonLoaderSettings| {
+            settings.elevation = true;
+        },
+    );
+    commands.insert_resource(Trails(trails));
+
+    // camera
+    commands.spawn((
+        Camera3d::default(),
+        Msaa::Sample4,
+        Transform::from_xyz(0.0, 2500.0, 900.0).looking_at(Vec3::new(0.0, 1700.0, 0.0), Vec3::Y),
+    ));
+}
+
+/// Spawns the trails once loaded, colored by their difficulty property.
+fn spawn_trails(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<GeoJsonPolylines>>,
+    trails: Res<Trails>,
+    layers: Res<Assets<GeoJsonPolylines>>,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+) {
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&trails.0) {
+            continue;
+        }
+        let Some(layer) = layers.get(&trails.0) else {
+            continue;
+        };
+        for feature in &layer.features {
+            let difficulty = feature
+                .properties
+                .get("difficulty")
+                .and_then(|value| value.as_u64());
+            let color = match difficulty {
+                Some(1) => LIME,
+                Some(2) => GOLD,
+                _ => RED,
+            };
+            commands.spawn(PolylineBundle {
+                polyline: PolylineHandle(feature.polyline.clone()),
+                material: PolylineMaterialHandle(polyline_materials.add(PolylineMaterial {
+                    width: 4.0,
+                    color: color.into(),
+                    ..default()
+                })),
+                ..default()
+            });
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use bevy::{
+    color::palettes::css::{DEEP_SKY_BLUE, LIME, ORANGE_RED, RED},
+    prelude::*,
+};
+use bevy_polyline::prelude::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(PolylinePlugin)
+        .add_systems(Startup, setup)
+        .add_systems(Update, draw_gizmos)
+        .run();
+}
+
+fn setup(mut commands: Commands) {
+    // camera
+    commands.spawn((
+        Camera3d::default(),
+        Msaa::Sample4,
+        Transform::from_xyz(0.0, 3.0, 6.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+}
+
+fn draw_gizmos(mut gizmos: PolylineGizmos, time: Res<Time>) {
+    let angle = time.elapsed_secs();
+
+    // Axes
+    gizmos.arrow(Vec3::ZERO, Vec3::X, RED);
+    gizmos.arrow(Vec3::ZERO, Vec3::Y, LIME);
+    gizmos.arrow(Vec3::ZERO, Vec3::Z, DEEP_SKY_BLUE);
+
+    // An orbit with a moving arrow, drawn thicker
+    gizmos
+        .circle(
+            Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+            2.0,
+            ORANGE_RED,
+        )
+        .resolution(64);
+    let position = 2.0 * Vec3::new(angle.cos(), 0.0, -angle.sin());
+    let tangent = Vec3::new(-angle.sin(), 0.0, -angle.cos());
+    gizmos
+        .arrow(position, position + 0.5 * tangent, ORANGE_RED)
+        .width(8.0);
+
+    // A wave with a width in world units
+    gizmos
+        .linestrip(
+            (0..=64).map(|i| {
+                let x = i as f32 / 16.0 - 2.0;
+                Vec3::new(x, (2.0 * x + angle).sin() * 0.3 - 1.0, 0.0)
+            }),
+            Color::WHITE,
+        )
+        .width(30.0)
+        .perspective(true);
+}
@@ -29,6 +29,7 @@ fn setup(
                 Vec3::new(0.5, -0.5, 0.5),
                 Vec3::new(-0.5, -0.5, 0.5),
             ],
+            ..Default::default()
         })),
         material: PolylineMaterialHandle(polyline_materials.add(PolylineMaterial {
             width: 2.0,
@@ -36,6 +37,7 @@ fn setup(
             perspective: false,
             // Bias the line toward the camera so the line at the cube-plane intersection is visible
             depth_bias: -0.0002,
+            ..Default::default()
         })),
         ..Default::default()
     });
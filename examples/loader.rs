@@ -0,0 +1,41 @@
+use bevy::prelude::*;
+use bevy_polyline::prelude::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins((PolylinePlugin, PolylineFilePlugin))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+) {
+    // The file also describes its material, loaded with the `material` label, and tapers the
+    // first strip with per-vertex widths
+    commands.spawn(PolylineBundle {
+        polyline: PolylineHandle(asset_server.load("route.polyline.ron")),
+        material: PolylineMaterialHandle(asset_server.load("route.polyline.ron#material")),
+        ..default()
+    });
+
+    // A list of colored points, with an empty line between the two strips
+    commands.spawn(PolylineBundle {
+        polyline: PolylineHandle(asset_server.load("points.polyline.csv")),
+        material: PolylineMaterialHandle(polyline_materials.add(PolylineMaterial {
+            width: 4.0,
+            ..default()
+        })),
+        ..default()
+    });
+
+    // camera
+    commands.spawn((
+        Camera3d::default(),
+        Msaa::Sample4,
+        Transform::from_xyz(0.0, 0.0, 6.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+}
@@ -17,6 +17,7 @@ fn setup(
     commands.spawn(PolylineBundle {
         polyline: PolylineHandle(polylines.add(Polyline {
             vertices: vec![-Vec3::ONE, Vec3::ONE],
+            ..default()
         })),
         material: PolylineMaterialHandle(polyline_materials.add(PolylineMaterial {
             width: 10.0,
@@ -61,6 +61,7 @@ fn setup(
             PolylineBundle {
                 polyline: PolylineHandle(polylines.add(Polyline {
                     vertices: Vec::with_capacity(TRAIL_LENGTH),
+                    ..Default::default()
                 })),
                 material: PolylineMaterialHandle(
                     polyline_materials.add(PolylineMaterial {
@@ -81,7 +82,7 @@ fn setup(
         Camera3d::default(),
         Camera {
             hdr: true,
-            ..default()
+            ..Default::default()
         },
         Msaa::Sample4,
         Tonemapping::TonyMcMapface,
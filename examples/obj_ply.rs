@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+use bevy_polyline::prelude::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins((PolylinePlugin, PolylineFilePlugin))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+) {
+    // The per-vertex colors of the files are multiplied with the white color of the material
+    let material = polyline_materials.add(PolylineMaterial {
+        width: 6.0,
+        ..default()
+    });
+
+    // OBJ `l` elements, written a segment per line and joined into a strip per hole
+    commands.spawn(PolylineBundle {
+        polyline: PolylineHandle(asset_server.load::<Polyline>("drillholes.obj")),
+        material: PolylineMaterialHandle(material.clone()),
+        ..default()
+    });
+
+    // PLY edges, joined into closed rings
+    commands.spawn(PolylineBundle {
+        polyline: PolylineHandle(asset_server.load::<Polyline>("contours.ply")),
+        material: PolylineMaterialHandle(material),
+        ..default()
+    });
+
+    // camera
+    commands.spawn((
+        Camera3d::default(),
+        Msaa::Sample4,
+        Transform::from_xyz(0.0, 2.0, 6.0).looking_at(Vec3::new(0.0, -0.5, 0.0), Vec3::Y),
+    ));
+}
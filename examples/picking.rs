@@ -0,0 +1,89 @@
+use bevy::{
+    color::palettes::css::{GOLD, STEEL_BLUE},
+    prelude::*,
+};
+use bevy_polyline::prelude::*;
+
+// This example demonstrates how to click and hover polylines with `bevy_picking`.
+//
+// It should display on screen a row of drill holes. Hovering a drill hole highlights it, and
+// clicking it logs the segment that was clicked and where.
+//
+// Run with `cargo run --example picking --features picking`.
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins((PolylinePlugin, PolylinePickingPlugin))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+    mut polylines: ResMut<Assets<Polyline>>,
+) {
+    for i in -4..=4 {
+        let x = i as f32;
+        let polyline = polylines.add(Polyline {
+            vertices: (0..=10)
+                .map(|j| {
+                    let depth = j as f32 * 0.4;
+                    Vec3::new(
+                        x + 0.1 * depth * x.signum(),
+                        2.0 - depth,
+                        -0.05 * depth * depth,
+                    )
+                })
+                .collect(),
+            ..default()
+        });
+        let material = polyline_materials.add(PolylineMaterial {
+            width: 6.0,
+            color: STEEL_BLUE.into(),
+            ..default()
+        });
+        commands
+            .spawn(PolylineBundle {
+                polyline: PolylineHandle(polyline),
+                material: PolylineMaterialHandle(material),
+                ..default()
+            })
+            .observe(highlight::<Pointer<Over>>(GOLD.into()))
+            .observe(highlight::<Pointer<Out>>(STEEL_BLUE.into()))
+            .observe(log_click);
+    }
+
+    // camera
+    commands.spawn((
+        Camera3d::default(),
+        Msaa::Sample4,
+        Transform::from_xyz(0.0, 0.0, 8.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+}
+
+fn highlight<E: Event>(
+    color: LinearRgba,
+) -> impl Fn(Trigger<E>, Query<&PolylineMaterialHandle>, ResMut<Assets<PolylineMaterial>>) {
+    move |trigger, handles, mut materials| {
+        if let Some(material) = handles
+            .get(trigger.target())
+            .ok()
+            .and_then(|handle| materials.get_mut(&handle.0))
+        {
+            material.color = color;
+        }
+    }
+}
+
+fn log_click(trigger: Trigger<Pointer<Click>>, hits: Res<PolylinePickingHits>) {
+    if let Some(hit) = hits.get(trigger.pointer_id, trigger.target()) {
+        println!(
+            "Clicked segment {} of {} at {:.0}% of its length, at {}",
+            hit.segment,
+            trigger.target(),
+            hit.t * 100.0,
+            hit.point
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use bevy::{
+    color::palettes::css::{RED, YELLOW},
+    prelude::*,
+    render::{camera::Viewport, view::RenderLayers},
+};
+use bevy_polyline::prelude::*;
+
+// This example demonstrates how `RenderLayers` filter polylines per camera, the same way
+// they filter meshes.
+//
+// It should display on screen:
+// * A red route and a yellow annotation line seen from the main camera.
+// * A top-down minimap in the top left corner showing only the red route, as the
+//   annotation line is on a render layer the minimap camera doesn't render.
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(PolylinePlugin)
+        .add_systems(Startup, setup)
+        .run();
+}
+
+const ANNOTATION_LAYER: usize = 1;
+
+fn setup(
+    mut commands: Commands,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+    mut polylines: ResMut<Assets<Polyline>>,
+) {
+    // The route has no `RenderLayers`, so it is on the default layer 0
+    commands.spawn(PolylineBundle {
+        polyline: PolylineHandle(polylines.add(Polyline {
+            vertices: vec![
+                Vec3::new(-2.0, 0.0, -2.0),
+                Vec3::new(-1.0, 0.0, 1.0),
+                Vec3::new(1.0, 0.0, -1.0),
+                Vec3::new(2.0, 0.0, 2.0),
+            ],
+            ..default()
+        })),
+        material: PolylineMaterialHandle(polyline_materials.add(PolylineMaterial {
+            width: 5.0,
+            color: RED.into(),
+            ..default()
+        })),
+        ..default()
+    });
+
+    // The annotation is only on the annotation layer
+    commands.spawn((
+        PolylineBundle {
+            polyline: PolylineHandle(polylines.add(Polyline {
+                vertices: vec![Vec3::new(1.0, 0.0, -1.0), Vec3::new(1.0, 1.5, -1.0)],
+                ..default()
+            })),
+            material: PolylineMaterialHandle(polyline_materials.add(PolylineMaterial {
+                width: 3.0,
+                color: YELLOW.into(),
+                ..default()
+            })),
+            ..default()
+        },
+        RenderLayers::layer(ANNOTATION_LAYER),
+    ));
+
+    // main camera, rendering both layers
+    commands.spawn((
+        Camera3d::default(),
+        Msaa::Sample4,
+        Transform::from_xyz(0.0, 3.0, 6.0).looking_at(Vec3::ZERO, Vec3::Y),
+        RenderLayers::from_layers(&[0, ANNOTATION_LAYER]),
+    ));
+
+    // minimap camera, rendering only the default layer
+    commands.spawn((
+        Camera3d::default(),
+        Camera {
+            order: 1,
+            viewport: Some(Viewport {
+                physical_position: UVec2::ZERO,
+                physical_size: UVec2::splat(256),
+                ..default()
+            }),
+            clear_color: ClearColorConfig::Custom(Color::BLACK),
+            ..default()
+        },
+        Msaa::Sample4,
+        Transform::from_xyz(0.0, 8.0, 0.0).looking_at(Vec3::ZERO, Vec3::NEG_Z),
+        RenderLayers::layer(0),
+    ));
+}
@@ -0,0 +1,55 @@
+use bevy::prelude::*;
+use bevy_polyline::prelude::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(PolylinePlugin)
+        .add_systems(Startup, setup)
+        .add_systems(Update, spawn_floor_plan)
+        .run();
+}
+
+#[derive(Resource)]
+struct FloorPlan(Handle<SvgPolylines>);
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    // Lay the plan on the ground, with one SVG unit per centimeter
+    let floor_plan =
+        asset_server.load_with_settings("floor_plan.svg", |settings: &mut SvgLoaderSettings| {
+            settings.plane = SvgPlane::Xz;
+            settings.scale = 0.01;
+        });
+    commands.insert_resource(FloorPlan(floor_plan));
+
+    // camera
+    commands.spawn((
+        Camera3d::default(),
+        Msaa::Sample4,
+        Transform::from_xyz(2.0, 4.0, 5.0).looking_at(Vec3::new(2.0, 0.0, 1.5), Vec3::Y),
+    ));
+}
+
+/// Spawns the paths of the plan once loaded, each with the material of its stroke.
+fn spawn_floor_plan(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<SvgPolylines>>,
+    floor_plan: Res<FloorPlan>,
+    images: Res<Assets<SvgPolylines>>,
+) {
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&floor_plan.0) {
+            continue;
+        }
+        let Some(image) = images.get(&floor_plan.0) else {
+            continue;
+        };
+        for path in &image.paths {
+            commands.spawn(PolylineBundle {
+                polyline: PolylineHandle(path.polyline.clone()),
+                material: PolylineMaterialHandle(path.material.clone()),
+                ..default()
+            });
+        }
+    }
+}
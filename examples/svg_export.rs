@@ -0,0 +1,86 @@
+//! Exports polylines to an SVG image without a window or a GPU.
+
+use bevy::{
+    color::palettes::css::{DEEP_SKY_BLUE, ORANGE_RED},
+    prelude::*,
+    render::view::VisibilityPlugin,
+};
+use bevy_polyline::prelude::*;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            TransformPlugin,
+            VisibilityPlugin,
+        ))
+        .init_asset::<Polyline>()
+        .init_asset::<PolylineMaterial>()
+        // Needed by the visibility systems
+        .init_asset::<Mesh>()
+        .add_systems(Startup, setup)
+        .add_systems(Last, export)
+        .run();
+}
+
+#[derive(Component)]
+struct ExportCamera;
+
+fn setup(
+    mut commands: Commands,
+    mut polylines: ResMut<Assets<Polyline>>,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+) {
+    // A grid on the ground, getting thinner with the distance
+    let grid = Polyline::from_strips((-5..=5).flat_map(|i| {
+        let i = i as f32;
+        [
+            vec![Vec3::new(i, 0.0, -5.0), Vec3::new(i, 0.0, 5.0)],
+            vec![Vec3::new(-5.0, 0.0, i), Vec3::new(5.0, 0.0, i)],
+        ]
+    }));
+    commands.spawn(PolylineBundle {
+        polyline: PolylineHandle(polylines.add(grid)),
+        material: PolylineMaterialHandle(polyline_materials.add(PolylineMaterial {
+            width: 20.0,
+            color: DEEP_SKY_BLUE.into(),
+            perspective: true,
+            ..default()
+        })),
+        ..default()
+    });
+
+    // A circle with an outline, the same width at any distance
+    commands.spawn(PolylineBundle {
+        polyline: PolylineHandle(polylines.add(Circle::new(2.0))),
+        material: PolylineMaterialHandle(polyline_materials.add(PolylineMaterial {
+            width: 6.0,
+            color: ORANGE_RED.into(),
+            outline_width: 2.0,
+            ..default()
+        })),
+        transform: Transform::from_xyz(0.0, 2.0, 0.0),
+        ..default()
+    });
+
+    commands.spawn((
+        ExportCamera,
+        Projection::default(),
+        Transform::from_xyz(0.0, 4.0, 9.0).looking_at(Vec3::new(0.0, 1.0, 0.0), Vec3::Y),
+    ));
+}
+
+/// Exports the polylines after their transforms and visibility are computed, then exits.
+fn export(
+    export: PolylineSvgExport,
+    camera: Single<Entity, With<ExportCamera>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let svg = export
+        .export(*camera, Vec2::new(1280.0, 720.0))
+        .expect("the camera has a projection");
+    std::fs::write("polylines.svg", svg).expect("the image can be written");
+    println!("Exported the polylines to polylines.svg");
+    exit.write(AppExit::Success);
+}
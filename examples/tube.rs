@@ -0,0 +1,96 @@
+use std::f32::consts::TAU;
+
+use bevy::{color::palettes::css::STEEL_BLUE, prelude::*};
+use bevy_polyline::prelude::*;
+
+// This example demonstrates the tube shading of polylines.
+//
+// It should display on screen a rotating grid of pipes, shaded like lit cylinders.
+//
+// Press SPACE to switch between tube and flat shading.
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(PolylinePlugin)
+        .add_systems(Startup, setup)
+        .add_systems(Update, (rotate_pipes, toggle_shading))
+        .run();
+}
+
+#[derive(Component)]
+struct Pipes;
+
+#[derive(Resource)]
+struct PipeMaterial(Handle<PolylineMaterial>);
+
+fn setup(
+    mut commands: Commands,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+    mut polylines: ResMut<Assets<Polyline>>,
+) {
+    let material = polyline_materials.add(PolylineMaterial {
+        width: 200.0,
+        color: STEEL_BLUE.into(),
+        perspective: true,
+        shading: PolylineShading::Tube,
+        ..default()
+    });
+    commands.insert_resource(PipeMaterial(material.clone()));
+
+    commands
+        .spawn((Pipes, Transform::default(), Visibility::default()))
+        .with_children(|parent| {
+            for i in -2..=2 {
+                let offset = i as f32;
+                // A pipe along x and a pipe along z, with a vertical bend
+                for vertices in [
+                    vec![
+                        Vec3::new(-3.0, 0.0, offset),
+                        Vec3::new(0.0, 0.0, offset),
+                        Vec3::new(0.0, 1.0, offset),
+                        Vec3::new(3.0, 1.0, offset),
+                    ],
+                    vec![Vec3::new(offset, -1.0, -3.0), Vec3::new(offset, -1.0, 3.0)],
+                ] {
+                    parent.spawn(PolylineBundle {
+                        polyline: PolylineHandle(polylines.add(Polyline {
+                            vertices,
+                            ..default()
+                        })),
+                        material: PolylineMaterialHandle(material.clone()),
+                        ..default()
+                    });
+                }
+            }
+        });
+
+    // camera
+    commands.spawn((
+        Camera3d::default(),
+        Msaa::Sample4,
+        Transform::from_xyz(0.0, 4.0, 8.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+}
+
+fn rotate_pipes(time: Res<Time>, mut pipes: Query<&mut Transform, With<Pipes>>) {
+    for mut transform in &mut pipes {
+        transform.rotate_y(0.05 * TAU * time.delta_secs());
+    }
+}
+
+fn toggle_shading(
+    input: Res<ButtonInput<KeyCode>>,
+    pipe_material: Res<PipeMaterial>,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+) {
+    if !input.just_pressed(KeyCode::Space) {
+        return;
+    }
+    let Some(material) = polyline_materials.get_mut(&pipe_material.0) else {
+        return;
+    };
+    material.shading = match material.shading {
+        PolylineShading::Tube => PolylineShading::Flat,
+        PolylineShading::Flat => PolylineShading::Tube,
+    };
+}
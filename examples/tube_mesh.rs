@@ -0,0 +1,75 @@
+use std::f32::consts::TAU;
+
+use bevy::{color::palettes::css::ORANGE, prelude::*};
+use bevy_polyline::prelude::*;
+
+// This example demonstrates how to convert a polyline into real tube and ribbon meshes.
+//
+// It should display on screen, from left to right:
+// * A helix drawn as a polyline.
+// * The same helix as a lit tube mesh.
+// * The same helix as a lit ribbon mesh.
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(PolylinePlugin)
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+    mut polylines: ResMut<Assets<Polyline>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let helix = Polyline {
+        vertices: (0..=200)
+            .map(|i| {
+                let t = i as f32 / 200.0;
+                let angle = t * 3.0 * TAU;
+                Vec3::new(0.5 * angle.cos(), t * 3.0 - 1.5, 0.5 * angle.sin())
+            })
+            .collect(),
+        ..default()
+    };
+
+    let tube = meshes.add(helix.tube(0.05).radial_segments(12));
+    let ribbon = meshes.add(helix.ribbon(0.2, Vec3::Y));
+    let material = materials.add(StandardMaterial {
+        base_color: ORANGE.into(),
+        double_sided: true,
+        cull_mode: None,
+        ..default()
+    });
+
+    commands.spawn(PolylineBundle {
+        polyline: PolylineHandle(polylines.add(helix)),
+        material: PolylineMaterialHandle(polyline_materials.add(PolylineMaterial {
+            width: 5.0,
+            color: ORANGE.into(),
+            ..default()
+        })),
+        transform: Transform::from_xyz(-2.0, 0.0, 0.0),
+        ..default()
+    });
+    commands.spawn((Mesh3d(tube), MeshMaterial3d(material.clone())));
+    commands.spawn((
+        Mesh3d(ribbon),
+        MeshMaterial3d(material),
+        Transform::from_xyz(2.0, 0.0, 0.0),
+    ));
+
+    commands.spawn((
+        DirectionalLight::default(),
+        Transform::from_xyz(1.0, 2.0, 3.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+
+    // camera
+    commands.spawn((
+        Camera3d::default(),
+        Msaa::Sample4,
+        Transform::from_xyz(0.0, 1.0, 6.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+}
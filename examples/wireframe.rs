@@ -0,0 +1,71 @@
+use bevy::{color::palettes::css::ORANGE_RED, prelude::*};
+use bevy_polyline::prelude::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(PolylinePlugin)
+        .add_systems(Startup, setup)
+        .add_systems(Update, rotator_system)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut standard_materials: ResMut<Assets<StandardMaterial>>,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+) {
+    let wireframe_material = PolylineMaterialHandle(polyline_materials.add(PolylineMaterial {
+        width: 3.0,
+        color: ORANGE_RED.into(),
+        perspective: false,
+        // Bias the lines toward the camera so they are drawn over the faces
+        depth_bias: -0.0002,
+        ..default()
+    }));
+    let mesh_material = MeshMaterial3d(standard_materials.add(Color::srgb_u8(124, 144, 255)));
+
+    // Every edge of the triangles
+    let torus = meshes.add(Torus::new(0.4, 0.8));
+    commands.spawn((
+        Mesh3d(torus.clone()),
+        mesh_material.clone(),
+        PolylineWireframe::new(torus),
+        wireframe_material.clone(),
+        Transform::from_xyz(-1.2, 0.0, 0.0),
+    ));
+
+    // Only the edges where the faces meet at more than 30 degrees
+    let cylinder = meshes.add(Cylinder::new(0.6, 1.5));
+    commands.spawn((
+        Mesh3d(cylinder.clone()),
+        mesh_material,
+        PolylineWireframe::new(cylinder).with_feature_angle(30f32.to_radians()),
+        wireframe_material,
+        Transform::from_xyz(1.2, 0.0, 0.0),
+    ));
+
+    // light
+    commands.spawn((PointLight::default(), Transform::from_xyz(4.0, 8.0, 4.0)));
+
+    // camera
+    commands.spawn((
+        Camera3d::default(),
+        Msaa::Sample4,
+        Transform::from_xyz(-2.0, 2.5, -5.0).looking_at(Vec3::ZERO, Vec3::Y),
+        Rotates,
+    ));
+}
+
+/// this component indicates what entities should rotate
+#[derive(Component)]
+struct Rotates;
+
+fn rotator_system(time: Res<Time>, mut query: Query<&mut Transform, With<Rotates>>) {
+    for mut transform in query.iter_mut() {
+        *transform = Transform::from_rotation(Quat::from_rotation_y(
+            (4.0 * std::f32::consts::PI / 20.0) * time.delta_secs(),
+        )) * *transform;
+    }
+}
@@ -0,0 +1,246 @@
+use crate::polyline::Polyline;
+use bevy::{platform::collections::HashMap, prelude::*};
+
+/// Heights sampled on a regular grid, such as a terrain heightmap, from which contour lines
+/// are generated with the marching squares algorithm.
+///
+/// The grid lies in the XZ plane, with the sample at column `x` and row `z` at
+/// `(x * spacing.x, height, z * spacing.y)`, so the contours can be spawned with the same
+/// [`Transform`] as the terrain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeightGrid {
+    /// Heights of the samples, row by row. `NaN` marks samples without data, and the contours
+    /// stop at the cells around them.
+    pub heights: Vec<f32>,
+    /// Number of columns and rows of the grid.
+    pub size: UVec2,
+    /// Distance between two samples along X and Z. The default is 1.
+    pub spacing: Vec2,
+}
+
+impl HeightGrid {
+    /// Creates a grid of `size` columns and rows from `heights`, given row by row.
+    ///
+    /// Returns `None` if the number of heights doesn't match the size.
+    pub fn new(size: UVec2, heights: Vec<f32>) -> Option<Self> {
+        (heights.len() == size.element_product() as usize).then_some(Self {
+            heights,
+            size,
+            spacing: Vec2::ONE,
+        })
+    }
+
+    /// Creates a grid from the red channel of a heightmap, with one sample per pixel.
+    ///
+    /// Heights are read in linear space, between 0 and 1 for normalized formats. Heightmaps
+    /// should use a linear format such as `R16Unorm` or `R32Float`. Returns `None` if the
+    /// pixels of the image can't be read.
+    pub fn from_image(image: &Image) -> Option<Self> {
+        let size = image.size();
+        let heights = (0..size.y)
+            .flat_map(|y| (0..size.x).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                image
+                    .get_color_at(x, y)
+                    .ok()
+                    .map(|color| LinearRgba::from(color).red)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Self::new(size, heights)
+    }
+
+    /// Sets the distance between two samples along X and Z.
+    pub fn with_spacing(mut self, spacing: Vec2) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Returns the height of the sample at column `x` and row `z`.
+    pub fn height(&self, x: u32, z: u32) -> f32 {
+        self.heights[(z * self.size.x + x) as usize]
+    }
+
+    /// Generates the contour lines at `level`, at the height of `level` along Y.
+    ///
+    /// Each continuous line is a strip of the polyline. Lines closing on themselves end with
+    /// their first vertex, and the others end at the border of the grid or of the samples
+    /// without data.
+    pub fn contour(&self, level: f32) -> Polyline {
+        let UVec2 { x: width, y: depth } = self.size;
+        if width < 2 || depth < 2 {
+            return Polyline::default();
+        }
+
+        // Crossings are identified by the edge of the grid they are on, the edges along X
+        // first, then the edges along Z
+        let edges_along_x = (width - 1) * depth;
+        let edge_along_x = |x: u32, z: u32| z * (width - 1) + x;
+        let edge_along_z = |x: u32, z: u32| edges_along_x + z * width + x;
+
+        let mut vertices = Vec::new();
+        let mut crossings = HashMap::<u32, usize>::default();
+        let mut crossing = |edge: u32, (x0, z0): (u32, u32), (x1, z1): (u32, u32)| {
+            *crossings.entry(edge).or_insert_with(|| {
+                let (h0, h1) = (self.height(x0, z0), self.height(x1, z1));
+                let t = (level - h0) / (h1 - h0);
+                let start = Vec2::new(x0 as f32, z0 as f32);
+                let end = Vec2::new(x1 as f32, z1 as f32);
+                let point = start.lerp(end, t) * self.spacing;
+                vertices.push(Vec3::new(point.x, level, point.y));
+                vertices.len() - 1
+            })
+        };
+
+        let mut segments = Vec::new();
+        for z in 0..depth - 1 {
+            for x in 0..width - 1 {
+                let corners = [(x, z), (x + 1, z), (x + 1, z + 1), (x, z + 1)];
+                let heights = corners.map(|(x, z)| self.height(x, z));
+                if heights.iter().any(|height| height.is_nan()) {
+                    continue;
+                }
+                let case = heights.iter().enumerate().fold(0, |case, (i, &height)| {
+                    case | ((height >= level) as u8) << i
+                });
+                if case == 0 || case == 15 {
+                    continue;
+                }
+
+                // The sides of the cell, between consecutive corners
+                let sides = [
+                    (edge_along_x(x, z), corners[0], corners[1]),
+                    (edge_along_z(x + 1, z), corners[1], corners[2]),
+                    (edge_along_x(x, z + 1), corners[3], corners[2]),
+                    (edge_along_z(x, z), corners[0], corners[3]),
+                ];
+                let crosses = |side: usize| {
+                    let (a, b) = (side, (side + 1) % 4);
+                    (case >> a & 1) != (case >> b & 1)
+                };
+                let mut crossing_on = |side: usize| {
+                    let (edge, start, end) = sides[side];
+                    crossing(edge, start, end)
+                };
+
+                if case == 5 || case == 10 {
+                    // Saddle, the center of the cell decides which opposite corners connect
+                    let center_above = heights.iter().sum::<f32>() / 4.0 >= level;
+                    let (first, second) = if (case == 5) == center_above {
+                        ((0, 1), (2, 3))
+                    } else {
+                        ((3, 0), (1, 2))
+                    };
+                    for (a, b) in [first, second] {
+                        segments.push([crossing_on(a), crossing_on(b)]);
+                    }
+                } else {
+                    let mut crossed = (0..4).filter(|&side| crosses(side));
+                    if let (Some(a), Some(b)) = (crossed.next(), crossed.next()) {
+                        segments.push([crossing_on(a), crossing_on(b)]);
+                    }
+                }
+            }
+        }
+
+        Polyline::from_edges(&vertices, segments)
+    }
+
+    /// Generates the contour lines at each of `levels`, with one polyline per level.
+    pub fn contours(&self, levels: impl IntoIterator<Item = f32>) -> Vec<Polyline> {
+        levels
+            .into_iter()
+            .map(|level| self.contour(level))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The segments of the contour in the XZ plane, each with its ends in increasing order.
+    fn segments(polyline: &Polyline) -> Vec<[(f32, f32); 2]> {
+        let mut segments: Vec<_> = polyline
+            .segments()
+            .map(|(_, start, end)| {
+                let mut ends = [(start.x, start.z), (end.x, end.z)];
+                ends.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                ends
+            })
+            .collect();
+        segments.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        segments
+    }
+
+    #[test]
+    fn peak_gives_a_closed_loop() {
+        let grid = HeightGrid::new(UVec2::splat(3), vec![0., 0., 0., 0., 1., 0., 0., 0., 0.])
+            .unwrap()
+            .with_spacing(Vec2::splat(2.0));
+        let contour = grid.contour(0.5);
+        assert!(contour.breaks.is_empty());
+        assert_eq!(contour.vertices.len(), 5);
+        assert_eq!(contour.vertices[0], contour.vertices[4]);
+        assert!(contour.vertices.iter().all(|vertex| vertex.y == 0.5));
+        assert_eq!(
+            segments(&contour),
+            [
+                [(1.0, 2.0), (2.0, 1.0)],
+                [(1.0, 2.0), (2.0, 3.0)],
+                [(2.0, 1.0), (3.0, 2.0)],
+                [(2.0, 3.0), (3.0, 2.0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn saddle_follows_the_center_of_the_cell() {
+        let grid = HeightGrid::new(UVec2::splat(2), vec![1.0, 0.0, 0.0, 1.0]).unwrap();
+
+        // The center is at the level, so the high corners are connected through it
+        let contour = grid.contour(0.5);
+        assert_eq!(contour.strips().count(), 2);
+        assert_eq!(
+            segments(&contour),
+            [[(0.0, 0.5), (0.5, 1.0)], [(0.5, 0.0), (1.0, 0.5)]]
+        );
+
+        // The center is below the level, so the high corners are cut off
+        let contour = grid.contour(0.75);
+        assert_eq!(contour.strips().count(), 2);
+        assert_eq!(
+            segments(&contour),
+            [[(0.0, 0.25), (0.25, 0.0)], [(0.75, 1.0), (1.0, 0.75)]]
+        );
+    }
+
+    #[test]
+    fn separate_contours_are_separate_strips() {
+        #[rustfmt::skip]
+        let heights = vec![
+            0., 0., 0., 0., 0.,
+            0., 1., 0., 1., 0.,
+            0., 0., 0., 0., 0.,
+        ];
+        let grid = HeightGrid::new(UVec2::new(5, 3), heights).unwrap();
+        let contour = grid.contour(0.5);
+        assert_eq!(contour.breaks, [5]);
+        for strip in contour.strips() {
+            assert_eq!(strip.len(), 5);
+            assert_eq!(
+                contour.vertices[strip.start],
+                contour.vertices[strip.end - 1]
+            );
+        }
+
+        assert!(grid.contour(2.0).vertices.is_empty());
+    }
+
+    #[test]
+    fn contours_stop_at_missing_samples() {
+        let heights = vec![0.0, 1.0, f32::NAN, 0.0, 1.0, 1.0];
+        let grid = HeightGrid::new(UVec2::new(3, 2), heights).unwrap();
+        let contour = grid.contour(0.5);
+        assert_eq!(segments(&contour), [[(0.5, 0.0), (0.5, 1.0)]]);
+    }
+}
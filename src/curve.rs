@@ -0,0 +1,318 @@
+use crate::polyline::Polyline;
+use bevy::{
+    math::cubic_splines::{CubicCurve, CubicGenerator, CyclicCubicGenerator},
+    prelude::*,
+};
+use std::f32::consts::PI;
+
+/// Settings for tessellating curves into polylines, see [`Polyline::from_curve`].
+///
+/// Curves are first split uniformly into [`min_segments`](Self::min_segments), then each
+/// segment is split in two while it is too far from the curve or bends too much.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolylineTessellation {
+    /// Maximum angle in radians between two consecutive segments.
+    pub max_angle: f32,
+    /// Maximum distance between the middle of the curve spanned by a segment and the
+    /// segment, in the units of the curve.
+    pub max_chord_error: f32,
+    /// Number of uniform segments the curve is split into before being refined. Curves
+    /// bending back and forth within one of these segments may not be refined.
+    pub min_segments: u32,
+    /// Maximum number of times a segment can be split in two.
+    pub max_depth: u32,
+}
+
+impl Default for PolylineTessellation {
+    fn default() -> Self {
+        Self {
+            max_angle: 5.0 * PI / 180.0,
+            max_chord_error: 0.01,
+            min_segments: 4,
+            max_depth: 12,
+        }
+    }
+}
+
+impl PolylineTessellation {
+    /// Sets the maximum angle in radians between two consecutive segments.
+    pub fn with_max_angle(mut self, max_angle: f32) -> Self {
+        self.max_angle = max_angle;
+        self
+    }
+
+    /// Sets the maximum distance between a segment and the curve it spans.
+    pub fn with_max_chord_error(mut self, max_chord_error: f32) -> Self {
+        self.max_chord_error = max_chord_error;
+        self
+    }
+
+    /// Sets the number of uniform segments the curve is split into before being refined.
+    pub fn with_min_segments(mut self, min_segments: u32) -> Self {
+        self.min_segments = min_segments;
+        self
+    }
+}
+
+/// Tessellation of curves into polylines, with segments concentrated where curves bend.
+impl Polyline {
+    /// Tessellates `curve` over its whole domain.
+    ///
+    /// Curves with an unbounded domain produce an empty polyline.
+    pub fn from_curve(curve: &impl Curve<Vec3>, tessellation: &PolylineTessellation) -> Polyline {
+        let mut vertices = Vec::new();
+        tessellate(curve, tessellation, &mut vertices);
+        Polyline {
+            vertices,
+            ..default()
+        }
+    }
+
+    /// Tessellates a cubic curve, such as the ones built from Bézier, Catmull-Rom or B-spline
+    /// control points.
+    ///
+    /// Each segment of the curve is tessellated separately, so the knots between segments are
+    /// always vertices of the polyline.
+    pub fn from_cubic_curve(
+        curve: &CubicCurve<Vec3>,
+        tessellation: &PolylineTessellation,
+    ) -> Polyline {
+        let mut vertices = Vec::new();
+        for segment in curve.segments() {
+            // Consecutive segments share their knot
+            vertices.pop();
+            tessellate(segment, tessellation, &mut vertices);
+        }
+        Polyline {
+            vertices,
+            ..default()
+        }
+    }
+
+    /// Tessellates the curve built by a cubic spline such as [`CubicBezier`],
+    /// [`CubicCardinalSpline`] or [`CubicBSpline`].
+    pub fn from_cubic_spline<G: CubicGenerator<Vec3>>(
+        spline: &G,
+        tessellation: &PolylineTessellation,
+    ) -> Result<Polyline, G::Error> {
+        Ok(Self::from_cubic_curve(&spline.to_curve()?, tessellation))
+    }
+
+    /// Tessellates the closed curve built by a cyclic cubic spline. The last vertex of the
+    /// polyline is the same as the first one.
+    pub fn from_cyclic_cubic_spline<G: CyclicCubicGenerator<Vec3>>(
+        spline: &G,
+        tessellation: &PolylineTessellation,
+    ) -> Result<Polyline, G::Error> {
+        Ok(Self::from_cubic_curve(
+            &spline.to_curve_cyclic()?,
+            tessellation,
+        ))
+    }
+}
+
+/// Appends the vertices of the tessellated `curve` to `vertices`.
+fn tessellate(
+    curve: &impl Curve<Vec3>,
+    tessellation: &PolylineTessellation,
+    vertices: &mut Vec<Vec3>,
+) {
+    let domain = curve.domain();
+    if !domain.is_bounded() {
+        return;
+    }
+    let segments = tessellation.min_segments.max(1);
+    let parameter = |i: u32| domain.start() + domain.length() * i as f32 / segments as f32;
+
+    let mut start = curve.sample_unchecked(domain.start());
+    vertices.push(start);
+    for i in 0..segments {
+        let (t0, t1) = (parameter(i), parameter(i + 1));
+        let end = curve.sample_unchecked(t1);
+        subdivide(curve, tessellation, (t0, start), (t1, end), 0, vertices);
+        start = end;
+    }
+}
+
+/// Appends the vertices of the curve after `start` up to `end`, splitting the segment
+/// between them in two until it is close enough to the curve.
+fn subdivide(
+    curve: &impl Curve<Vec3>,
+    tessellation: &PolylineTessellation,
+    (t0, start): (f32, Vec3),
+    (t1, end): (f32, Vec3),
+    depth: u32,
+    vertices: &mut Vec<Vec3>,
+) {
+    let t = 0.5 * (t0 + t1);
+    let middle = curve.sample_unchecked(t);
+
+    let chord = end - start;
+    let length_squared = chord.length_squared();
+    let chord_error = if length_squared > 0.0 {
+        let s = ((middle - start).dot(chord) / length_squared).clamp(0.0, 1.0);
+        middle.distance(start + s * chord)
+    } else {
+        middle.distance(start)
+    };
+    let (before, after) = (middle - start, end - middle);
+    let angle = if before.length_squared() > 0.0 && after.length_squared() > 0.0 {
+        before.angle_between(after)
+    } else {
+        0.0
+    };
+
+    // The segment turns by about twice the angle at its middle from the neighbors it would
+    // have if the curve kept bending the same way
+    if depth < tessellation.max_depth
+        && (chord_error > tessellation.max_chord_error || 2.0 * angle > tessellation.max_angle)
+    {
+        subdivide(
+            curve,
+            tessellation,
+            (t0, start),
+            (t, middle),
+            depth + 1,
+            vertices,
+        );
+        subdivide(
+            curve,
+            tessellation,
+            (t, middle),
+            (t1, end),
+            depth + 1,
+            vertices,
+        );
+    } else {
+        vertices.push(end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::{
+        cubic_splines::{CubicBSpline, CubicBezier, CubicCardinalSpline},
+        curve::{interval, FunctionCurve},
+    };
+
+    /// Only limits the distance to the curve.
+    fn chord_error(max_chord_error: f32) -> PolylineTessellation {
+        PolylineTessellation::default()
+            .with_max_angle(PI)
+            .with_max_chord_error(max_chord_error)
+            .with_min_segments(1)
+    }
+
+    /// Checks that the curve sampled between its ends stays within `max_error` of the polyline.
+    fn assert_within(polyline: &Polyline, curve: &impl Curve<Vec3>, max_error: f32) {
+        for point in curve.samples(1000).unwrap() {
+            let closest = polyline.closest_location(point).unwrap().point;
+            let error = closest.distance(point);
+            assert!(error <= max_error, "{point} is {error} from the polyline");
+        }
+    }
+
+    fn half_circle() -> impl Curve<Vec3> {
+        FunctionCurve::new(interval(0.0, PI).unwrap(), |t| {
+            Vec3::new(t.cos(), t.sin(), 0.0)
+        })
+    }
+
+    #[test]
+    fn straight_curves_are_single_segments() {
+        let parabola =
+            FunctionCurve::new(interval(0.0, 2.0).unwrap(), |t| Vec3::new(t, t * t, 0.0));
+        let straight = FunctionCurve::new(interval(0.0, 2.0).unwrap(), |t| Vec3::X * t * t);
+        let tessellation = PolylineTessellation::default().with_min_segments(1);
+        assert_eq!(
+            Polyline::from_curve(&straight, &tessellation).vertices,
+            [Vec3::ZERO, Vec3::new(4.0, 0.0, 0.0)]
+        );
+        let polyline = Polyline::from_curve(&parabola, &tessellation);
+        assert!(polyline.vertices.len() > 2);
+
+        let bezier = CubicBezier::new([[Vec3::ZERO, Vec3::Y, Vec3::X + Vec3::Y, Vec3::X]]);
+        let straight_bezier =
+            CubicBezier::new([[Vec3::ZERO, 0.1 * Vec3::X, 0.2 * Vec3::X, Vec3::X]]);
+        let polyline = Polyline::from_cubic_spline(&straight_bezier, &tessellation).unwrap();
+        assert_eq!(polyline.vertices.len(), 2);
+        let polyline = Polyline::from_cubic_spline(&bezier, &tessellation).unwrap();
+        assert!(polyline.vertices.len() > 2);
+
+        // The uniform segments are kept
+        let polyline = Polyline::from_curve(&straight, &default());
+        assert_eq!(polyline.vertices.len(), 5);
+
+        let unbounded = FunctionCurve::new(Interval::EVERYWHERE, |t| Vec3::X * t);
+        assert!(Polyline::from_curve(&unbounded, &default())
+            .vertices
+            .is_empty());
+    }
+
+    #[test]
+    fn tighter_tolerances_add_vertices() {
+        let curve = half_circle();
+        let count = |tessellation| Polyline::from_curve(&curve, &tessellation).vertices.len();
+
+        let coarse = count(chord_error(0.1));
+        assert!(count(chord_error(0.01)) > coarse);
+        assert!(count(chord_error(0.001)) > count(chord_error(0.01)));
+
+        let angle = |max_angle| {
+            PolylineTessellation::default()
+                .with_max_chord_error(f32::INFINITY)
+                .with_max_angle(max_angle)
+        };
+        assert!(count(angle(0.05)) > count(angle(0.2)));
+        // The angle between segments of the half circle stays below the limit
+        let polyline = Polyline::from_curve(&curve, &angle(0.05));
+        for segments in polyline.vertices.windows(3) {
+            let angle = (segments[1] - segments[0]).angle_between(segments[2] - segments[1]);
+            assert!(angle <= 0.05 + 1e-4, "{angle}");
+        }
+    }
+
+    #[test]
+    fn chords_stay_within_the_chord_error() {
+        for max_chord_error in [0.1, 0.01, 0.001] {
+            let curve = half_circle();
+            let polyline = Polyline::from_curve(&curve, &chord_error(max_chord_error));
+            assert_within(&polyline, &curve, max_chord_error);
+        }
+
+        let bezier = CubicBezier::new([[Vec3::ZERO, Vec3::new(1.0, 2.0, 0.0), Vec3::X, Vec3::Z]])
+            .to_curve()
+            .unwrap();
+        let polyline = Polyline::from_cubic_curve(&bezier, &chord_error(0.01));
+        assert_within(&polyline, &bezier, 0.01);
+    }
+
+    #[test]
+    fn knots_are_vertices() {
+        let points = [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::Y];
+        let tessellation = chord_error(0.01);
+
+        let spline = CubicCardinalSpline::new_catmull_rom(points);
+        let polyline = Polyline::from_cubic_spline(&spline, &tessellation).unwrap();
+        let curve = spline.to_curve().unwrap();
+        for segment in curve.segments() {
+            assert!(polyline.vertices.contains(&segment.position(0.0)));
+        }
+        assert_eq!(polyline.vertices.first(), Some(&Vec3::ZERO));
+        assert_eq!(polyline.vertices.last(), Some(&Vec3::Y));
+        assert_within(&polyline, &curve, 0.01);
+
+        // Cyclic splines close the polyline
+        let spline = CubicBSpline::new(points);
+        let polyline = Polyline::from_cyclic_cubic_spline(&spline, &tessellation).unwrap();
+        let curve = spline.to_curve_cyclic().unwrap();
+        assert_eq!(curve.segments().len(), 4);
+        assert!(polyline
+            .vertices
+            .first()
+            .unwrap()
+            .abs_diff_eq(*polyline.vertices.last().unwrap(), 1e-6));
+        assert_within(&polyline, &curve, 0.01);
+    }
+}
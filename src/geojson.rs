@@ -0,0 +1,429 @@
+use crate::polyline::Polyline;
+use ::geojson::{feature::Id, Feature, GeoJson, JsonObject, LineStringType, Value};
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    math::DVec2,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Radius of the Earth in meters used by the projections, the semi-major axis of WGS 84.
+const EARTH_RADIUS: f64 = 6_378_137.0;
+
+/// How longitudes and latitudes are projected to local coordinates.
+///
+/// The projected coordinates are east along X and north along -Z, relative to the origin of
+/// the [`GeoJsonLoaderSettings`], so that the lines lie on the ground of a Y up scene.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GeoProjection {
+    /// Meters on a plane tangent to the Earth at the origin. This keeps distances and angles
+    /// for areas up to a few hundred kilometers.
+    #[default]
+    LocalTangent,
+    /// Meters of the Web Mercator projection used by web maps, EPSG:3857. Distances are
+    /// scaled up away from the equator.
+    WebMercator,
+    /// Degrees of longitude and latitude, used as is.
+    Degrees,
+}
+
+impl GeoProjection {
+    /// Projects a longitude and latitude in degrees to east and north coordinates relative to
+    /// `origin`.
+    pub fn project(&self, position: DVec2, origin: DVec2) -> DVec2 {
+        match self {
+            GeoProjection::LocalTangent => {
+                // Meters per degree along a meridian
+                let offset = (position - origin) * EARTH_RADIUS.to_radians();
+                DVec2::new(offset.x * origin.y.to_radians().cos(), offset.y)
+            }
+            GeoProjection::WebMercator => web_mercator(position) - web_mercator(origin),
+            GeoProjection::Degrees => position - origin,
+        }
+    }
+}
+
+fn web_mercator(position: DVec2) -> DVec2 {
+    let DVec2 { x: lon, y: lat } = position;
+    let lat = lat.clamp(-85.051_129, 85.051_129).to_radians();
+    EARTH_RADIUS
+        * DVec2::new(
+            lon.to_radians(),
+            (std::f64::consts::FRAC_PI_4 + lat / 2.0).tan().ln(),
+        )
+}
+
+/// Settings of the [`GeoJsonLoader`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GeoJsonLoaderSettings {
+    pub projection: GeoProjection,
+    /// Longitude and latitude in degrees at the origin of the polylines. When not set, this
+    /// is the center of the bounding box of the lines of the file.
+    pub origin: Option<[f64; 2]>,
+    /// Whether the elevation of the positions, their third coordinate, is used along Y. When
+    /// not set or missing, the lines are at Y 0.
+    pub elevation: bool,
+    /// Name of a numeric property of the features copied to the
+    /// [`scalars`](Polyline::scalars) of their vertices, to color them with a colormap.
+    ///
+    /// Features without a numeric value for the property have no scalars, and the polyline of
+    /// all the features only has scalars when every feature has one.
+    pub scalar_property: Option<String>,
+}
+
+/// The lines of a GeoJSON file, loaded by the [`GeoJsonLoader`].
+#[derive(Asset, TypePath, Debug)]
+pub struct GeoJsonPolylines {
+    /// The lines of all the features in a single polyline, labeled `Polyline`.
+    pub polyline: Handle<Polyline>,
+    /// The features with lines, in the order of the file.
+    pub features: Vec<GeoJsonFeature>,
+    /// Longitude and latitude in degrees at the origin of the polylines.
+    pub origin: DVec2,
+}
+
+/// A feature of a GeoJSON file with lines.
+#[derive(Debug, Clone)]
+pub struct GeoJsonFeature {
+    /// The lines of the feature, labeled `Feature{index}` with the index of the feature in the
+    /// file.
+    pub polyline: Handle<Polyline>,
+    pub id: Option<String>,
+    pub properties: JsonObject,
+}
+
+/// Loads the `LineString`, `MultiLineString`, `Polygon` and `MultiPolygon` geometries of
+/// `.geojson` files as [`GeoJsonPolylines`].
+///
+/// Each line and polygon ring is a strip of the polyline of its feature. Other geometries,
+/// such as points, are ignored.
+#[derive(Default)]
+pub struct GeoJsonLoader;
+
+/// An error when loading a GeoJSON file.
+#[derive(Debug, Error)]
+pub enum GeoJsonLoaderError {
+    #[error("could not read the GeoJSON file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse the JSON of the GeoJSON file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid GeoJSON: {0}")]
+    GeoJson(#[from] ::geojson::Error),
+}
+
+impl AssetLoader for GeoJsonLoader {
+    type Asset = GeoJsonPolylines;
+    type Settings = GeoJsonLoaderSettings;
+    type Error = GeoJsonLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &GeoJsonLoaderSettings,
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<GeoJsonPolylines, GeoJsonLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let geojson = GeoJson::from_json_value(serde_json::from_slice(&bytes)?)?;
+
+        let lines = GeoJsonLines::new(&geojson, settings);
+
+        let features = lines
+            .features
+            .into_iter()
+            .map(|feature| GeoJsonFeature {
+                polyline: load_context
+                    .add_labeled_asset(format!("Feature{}", feature.index), feature.polyline),
+                id: feature.id,
+                properties: feature.properties,
+            })
+            .collect();
+        Ok(GeoJsonPolylines {
+            polyline: load_context.add_labeled_asset("Polyline".into(), lines.polyline),
+            features,
+            origin: lines.origin,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["geojson"]
+    }
+}
+
+/// The lines of a GeoJSON document, before the [`GeoJsonLoader`] adds them as assets.
+struct GeoJsonLines {
+    polyline: Polyline,
+    features: Vec<GeoJsonFeatureLines>,
+    origin: DVec2,
+}
+
+/// The lines of a feature, with its index in the document.
+struct GeoJsonFeatureLines {
+    index: usize,
+    id: Option<String>,
+    properties: JsonObject,
+    polyline: Polyline,
+}
+
+impl GeoJsonLines {
+    fn new(geojson: &GeoJson, settings: &GeoJsonLoaderSettings) -> Self {
+        // The id, properties and lines of each feature
+        fn feature(feature: &Feature) -> (Option<&Id>, Option<&JsonObject>, Option<&Value>) {
+            (
+                feature.id.as_ref(),
+                feature.properties.as_ref(),
+                feature.geometry.as_ref().map(|geometry| &geometry.value),
+            )
+        }
+        let features: Vec<_> = match geojson {
+            GeoJson::FeatureCollection(collection) => {
+                collection.features.iter().map(feature).collect()
+            }
+            GeoJson::Feature(geojson_feature) => vec![feature(geojson_feature)],
+            GeoJson::Geometry(geometry) => vec![(None, None, Some(&geometry.value))],
+        };
+        let feature_lines: Vec<Vec<&LineStringType>> = features
+            .iter()
+            .map(|(_, _, value)| {
+                let mut lines = Vec::new();
+                if let Some(value) = value {
+                    collect_lines(value, &mut lines);
+                }
+                lines
+            })
+            .collect();
+
+        let positions = feature_lines
+            .iter()
+            .flatten()
+            .flat_map(|line| line.iter())
+            .filter(|position| position.len() >= 2);
+        let origin = match settings.origin {
+            Some(origin) => DVec2::from(origin),
+            None => {
+                let (min, max) = positions.fold(
+                    (DVec2::INFINITY, DVec2::NEG_INFINITY),
+                    |(min, max), position| {
+                        let position = DVec2::new(position[0], position[1]);
+                        (min.min(position), max.max(position))
+                    },
+                );
+                if min.x <= max.x {
+                    (min + max) / 2.0
+                } else {
+                    DVec2::ZERO
+                }
+            }
+        };
+        let project = |position: &Vec<f64>| {
+            let projected = settings
+                .projection
+                .project(DVec2::new(position[0], position[1]), origin);
+            let elevation = match position.get(2) {
+                Some(&elevation) if settings.elevation => elevation,
+                _ => 0.0,
+            };
+            Vec3::new(projected.x as f32, elevation as f32, -projected.y as f32)
+        };
+
+        let mut all = Polyline::default();
+        let mut all_scalars = true;
+        let mut loaded_features = Vec::new();
+        for (index, ((id, properties, _), lines)) in
+            features.into_iter().zip(feature_lines).enumerate()
+        {
+            let mut polyline = Polyline::from_strips(lines.into_iter().map(|line| {
+                line.iter()
+                    .filter(|position| position.len() >= 2)
+                    .map(project)
+            }));
+            if polyline.vertices.is_empty() {
+                continue;
+            }
+            let properties = properties.cloned().unwrap_or_default();
+            let scalar = settings
+                .scalar_property
+                .as_ref()
+                .and_then(|name| properties.get(name))
+                .and_then(|value| value.as_f64());
+            if let Some(scalar) = scalar {
+                polyline.scalars = vec![scalar as f32; polyline.vertices.len()];
+            }
+            all_scalars &= scalar.is_some();
+
+            for strip in polyline.strips() {
+                all.push_strip(polyline.vertices[strip].iter().copied());
+            }
+            all.scalars.extend(&polyline.scalars);
+            loaded_features.push(GeoJsonFeatureLines {
+                index,
+                id: id.map(|id| match id {
+                    Id::String(id) => id.clone(),
+                    Id::Number(id) => id.to_string(),
+                }),
+                properties,
+                polyline,
+            });
+        }
+        if !all_scalars {
+            all.scalars.clear();
+        }
+
+        Self {
+            polyline: all,
+            features: loaded_features,
+            origin,
+        }
+    }
+}
+
+/// Adds the lines and polygon rings of a geometry to `lines`.
+fn collect_lines<'a>(value: &'a Value, lines: &mut Vec<&'a LineStringType>) {
+    match value {
+        Value::LineString(line) => lines.push(line),
+        Value::MultiLineString(multi_line) => lines.extend(multi_line),
+        Value::Polygon(rings) => lines.extend(rings),
+        Value::MultiPolygon(polygons) => lines.extend(polygons.iter().flatten()),
+        Value::GeometryCollection(geometries) => {
+            for geometry in geometries {
+                collect_lines(&geometry.value, lines);
+            }
+        }
+        Value::Point(_) | Value::MultiPoint(_) => {}
+    }
+}
+
+/// Loads GeoJSON files with the [`GeoJsonLoader`].
+pub struct PolylineGeoJsonPlugin;
+
+impl Plugin for PolylineGeoJsonPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<GeoJsonPolylines>()
+            .init_asset_loader::<GeoJsonLoader>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(json: &str, settings: &GeoJsonLoaderSettings) -> GeoJsonLines {
+        GeoJsonLines::new(&json.parse().unwrap(), settings)
+    }
+
+    const DEGREES: GeoJsonLoaderSettings = GeoJsonLoaderSettings {
+        projection: GeoProjection::Degrees,
+        origin: Some([0.0, 0.0]),
+        elevation: true,
+        scalar_property: None,
+    };
+
+    #[test]
+    fn rings_and_lines_are_strips() {
+        let json = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "id": 7, "properties": {"name": "field"}, "geometry": {
+                "type": "Polygon",
+                "coordinates": [[[0, 0], [4, 0], [4, 4], [0, 0]], [[1, 1], [2, 1], [1, 1]]]
+            }},
+            {"type": "Feature", "properties": null, "geometry": {"type": "Point", "coordinates": [0, 0]}},
+            {"type": "Feature", "properties": null, "geometry": {
+                "type": "MultiLineString",
+                "coordinates": [[[0, 0, 5], [1, 2, 6]], [[3, 3], [3, 4]]]
+            }}
+        ]}"#;
+        let lines = lines(json, &DEGREES);
+
+        // The point feature has no lines
+        let [field, roads] = &lines.features[..] else {
+            panic!("expected 2 features with lines");
+        };
+        assert_eq!((field.index, field.id.as_deref()), (0, Some("7")));
+        assert_eq!(field.properties["name"], "field");
+        assert_eq!(field.polyline.breaks, [4]);
+        assert_eq!(field.polyline.vertices[2], Vec3::new(4.0, 0.0, -4.0));
+
+        // Elevations are along Y
+        assert_eq!(roads.index, 2);
+        assert_eq!(
+            roads.polyline.vertices,
+            [
+                Vec3::new(0.0, 5.0, 0.0),
+                Vec3::new(1.0, 6.0, -2.0),
+                Vec3::new(3.0, 0.0, -3.0),
+                Vec3::new(3.0, 0.0, -4.0)
+            ]
+        );
+        assert_eq!(roads.polyline.breaks, [2]);
+
+        assert_eq!(lines.polyline.vertices.len(), 11);
+        assert_eq!(lines.polyline.breaks, [4, 7, 9]);
+    }
+
+    #[test]
+    fn origin_defaults_to_the_center_of_the_lines() {
+        let json = r#"{"type": "LineString", "coordinates": [[10, 20], [12, 24]]}"#;
+        let settings = GeoJsonLoaderSettings {
+            origin: None,
+            ..DEGREES
+        };
+        let lines = lines(json, &settings);
+        assert_eq!(lines.origin, DVec2::new(11.0, 22.0));
+        assert_eq!(
+            lines.polyline.vertices,
+            [Vec3::new(-1.0, 0.0, 2.0), Vec3::new(1.0, 0.0, -2.0)]
+        );
+    }
+
+    #[test]
+    fn scalars_need_the_property_on_every_feature() {
+        let feature = |depth: &str| {
+            format!(
+                r#"{{"type": "Feature", "properties": {{"depth": {depth}}},
+                "geometry": {{"type": "LineString", "coordinates": [[0, 0], [1, 0]]}}}}"#
+            )
+        };
+        let settings = GeoJsonLoaderSettings {
+            scalar_property: Some("depth".into()),
+            ..DEGREES
+        };
+        let collection = |features: &[String]| {
+            format!(
+                r#"{{"type": "FeatureCollection", "features": [{}]}}"#,
+                features.join(",")
+            )
+        };
+
+        let loaded = lines(&collection(&[feature("1"), feature("2.5")]), &settings);
+        assert_eq!(loaded.polyline.scalars, [1.0, 1.0, 2.5, 2.5]);
+
+        let loaded = lines(&collection(&[feature("1"), feature("\"deep\"")]), &settings);
+        assert!(loaded.polyline.scalars.is_empty());
+        assert_eq!(loaded.features[0].polyline.scalars, [1.0, 1.0]);
+        assert!(loaded.features[1].polyline.scalars.is_empty());
+    }
+
+    #[test]
+    fn projections() {
+        let meters_per_degree = EARTH_RADIUS.to_radians();
+        let east = DVec2::new(1.0, 0.0);
+
+        let local = GeoProjection::LocalTangent.project(east, DVec2::ZERO);
+        assert!((local - DVec2::new(meters_per_degree, 0.0)).length() < 1e-6);
+        // Degrees of longitude are shorter away from the equator
+        let local =
+            GeoProjection::LocalTangent.project(DVec2::new(1.0, 60.0), DVec2::new(0.0, 60.0));
+        assert!((local.x - meters_per_degree / 2.0).abs() < 1e-6);
+
+        let mercator = GeoProjection::WebMercator.project(east, DVec2::ZERO);
+        assert!((mercator - DVec2::new(meters_per_degree, 0.0)).length() < 1e-6);
+        // Web Mercator stretches the latitudes away from the equator
+        let mercator = GeoProjection::WebMercator.project(DVec2::new(0.0, 60.0), DVec2::ZERO);
+        assert!(mercator.y > 60.0 * meters_per_degree);
+
+        assert_eq!(
+            GeoProjection::Degrees.project(DVec2::new(3.0, 4.0), DVec2::new(1.0, 1.0)),
+            DVec2::new(2.0, 3.0)
+        );
+    }
+}
@@ -0,0 +1,432 @@
+use crate::polyline::Polyline;
+use bevy::{math::bounding::Aabb3d, prelude::*};
+use std::ops::Range;
+
+/// A location on a [`Polyline`], as returned by the geometric queries on it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolylineLocation {
+    /// Index of the segment, going from `vertices[segment]` to `vertices[segment + 1]`.
+    pub segment: usize,
+    /// Position along the segment, from 0 at its start to 1 at its end.
+    pub t: f32,
+    /// The location, in the local space of the polyline.
+    pub point: Vec3,
+    /// Distance from the start of the polyline to `point`, following the polyline.
+    pub distance: f32,
+}
+
+/// Geometric queries on the vertices of a polyline, in its local space.
+///
+/// Queries returning a location need at least one segment, and return `None` when the
+/// polyline has none. Distances along a polyline with several strips go through the strips
+/// one after the other, the gaps between them having no length.
+impl Polyline {
+    /// Total length of the polyline.
+    pub fn length(&self) -> f32 {
+        self.segments()
+            .map(|(_, start, end)| start.distance(end))
+            .sum()
+    }
+
+    /// Location at `distance` from the start of the polyline, following it.
+    ///
+    /// `distance` is clamped between 0 and the length of the polyline.
+    pub fn location_at_distance(&self, distance: f32) -> Option<PolylineLocation> {
+        let mut start = 0.0;
+        let mut location = None;
+        for (segment, segment_start, segment_end) in self.segments() {
+            let length = segment_start.distance(segment_end);
+            let t = if length > 0.0 {
+                ((distance - start) / length).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            location = Some(PolylineLocation {
+                segment,
+                t,
+                point: segment_start.lerp(segment_end, t),
+                distance: start + t * length,
+            });
+            if distance < start + length {
+                break;
+            }
+            start += length;
+        }
+        location
+    }
+
+    /// Point at `distance` from the start of the polyline, following it.
+    ///
+    /// `distance` is clamped between 0 and the length of the polyline.
+    pub fn point_at_distance(&self, distance: f32) -> Option<Vec3> {
+        self.location_at_distance(distance)
+            .map(|location| location.point)
+    }
+
+    /// Direction of the polyline at `distance` from its start, following it.
+    ///
+    /// Returns `None` if the segment at `distance` has a length of 0.
+    pub fn tangent_at_distance(&self, distance: f32) -> Option<Dir3> {
+        let location = self.location_at_distance(distance)?;
+        Dir3::new(self.vertices[location.segment + 1] - self.vertices[location.segment]).ok()
+    }
+
+    /// Location on the polyline closest to `point`.
+    pub fn closest_location(&self, point: Vec3) -> Option<PolylineLocation> {
+        self.closest_location_by(|start, end| {
+            let direction = end - start;
+            let length_squared = direction.length_squared();
+            let t = if length_squared > 0.0 {
+                ((point - start).dot(direction) / length_squared).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            (t, point.distance_squared(start.lerp(end, t)))
+        })
+    }
+
+    /// Location on the polyline closest to `ray`, for example to snap to a line under the
+    /// cursor.
+    pub fn closest_location_to_ray(&self, ray: Ray3d) -> Option<PolylineLocation> {
+        let ray_direction = *ray.direction;
+        self.closest_location_by(|start, end| {
+            // Closest points of two segments from Real-Time Collision Detection, Ericson,
+            // with the ray being a segment unbounded at its end.
+            let direction = end - start;
+            let r = start - ray.origin;
+            let a = direction.length_squared();
+            let b = direction.dot(ray_direction);
+            let c = direction.dot(r);
+            let f = ray_direction.dot(r);
+            let denominator = a - b * b;
+            let mut t = if denominator > f32::EPSILON {
+                ((b * f - c) / denominator).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let mut u = b * t + f;
+            if u < 0.0 {
+                u = 0.0;
+                t = if a > 0.0 {
+                    (-c / a).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+            }
+            let closest_on_ray = ray.origin + u * ray_direction;
+            (t, closest_on_ray.distance_squared(start.lerp(end, t)))
+        })
+    }
+
+    /// Smallest axis-aligned box containing the polyline, or `None` if it has no vertices.
+    pub fn bounding_box(&self) -> Option<Aabb3d> {
+        let (first, rest) = self.vertices.split_first()?;
+        let (min, max) = rest
+            .iter()
+            .fold((*first, *first), |(min, max), v| (min.min(*v), max.max(*v)));
+        Some(Aabb3d {
+            min: min.into(),
+            max: max.into(),
+        })
+    }
+
+    /// Splits the polyline in two at `distance` from its start, following it.
+    ///
+    /// Both polylines contain the point at `distance`, and the [`scalars`](Self::scalars),
+    /// [`colors`](Self::colors) and [`widths`](Self::widths) are interpolated there. The
+    /// strips before and after that point go to the first and second polylines. When the point
+    /// is at the start of a strip, the polyline is split between the strips instead, so the
+    /// first polyline ends with the previous strip. `distance` is clamped between 0 and the
+    /// length of the polyline.
+    pub fn split_at_distance(&self, distance: f32) -> Option<(Polyline, Polyline)> {
+        let location = self.location_at_distance(distance)?;
+        let segment = location.segment;
+        let count = self.vertices.len();
+
+        // On a vertex, split there rather than adding a segment of length 0
+        if location.t == 0.0 && segment > 0 {
+            let end = if self.breaks.contains(&segment) {
+                segment
+            } else {
+                segment + 1
+            };
+            return Some((self.slice(0..end), self.slice(segment..count)));
+        }
+
+        let split = segment + 1;
+        let mut first = self.slice(0..split);
+        first.vertices.push(location.point);
+        let mut second = self.slice(split..count);
+        second.vertices.insert(0, location.point);
+        for index in &mut second.breaks {
+            *index += 1;
+        }
+
+        if self.scalars.len() == count {
+            let scalar =
+                self.scalars[segment] + (self.scalars[split] - self.scalars[segment]) * location.t;
+            first.scalars.push(scalar);
+            second.scalars.insert(0, scalar);
+        }
+        if self.colors.len() == count {
+            let color = self.colors[segment].mix(&self.colors[split], location.t);
+            first.colors.push(color);
+            second.colors.insert(0, color);
+        }
+        if self.widths.len() == count {
+            let width =
+                self.widths[segment] + (self.widths[split] - self.widths[segment]) * location.t;
+            first.widths.push(width);
+            second.widths.insert(0, width);
+        }
+
+        Some((first, second))
+    }
+
+    /// The vertices in `range` with their per-vertex values and the breaks between them.
+    fn slice(&self, range: Range<usize>) -> Polyline {
+        fn values<T: Clone>(values: &[T], count: usize, range: &Range<usize>) -> Vec<T> {
+            if values.len() == count {
+                values[range.clone()].to_vec()
+            } else {
+                Vec::new()
+            }
+        }
+        let count = self.vertices.len();
+        Polyline {
+            vertices: self.vertices[range.clone()].to_vec(),
+            scalars: values(&self.scalars, count, &range),
+            colors: values(&self.colors, count, &range),
+            widths: values(&self.widths, count, &range),
+            breaks: self
+                .breaks
+                .iter()
+                .filter(|&&index| index > range.start && index < range.end)
+                .map(|index| index - range.start)
+                .collect(),
+        }
+    }
+
+    /// Finds the segment closest to a query, `measure` returning the position along the
+    /// segment from `start` to `end` closest to the query and its squared distance to it.
+    fn closest_location_by(
+        &self,
+        measure: impl Fn(Vec3, Vec3) -> (f32, f32),
+    ) -> Option<PolylineLocation> {
+        let mut start_distance = 0.0;
+        let mut closest: Option<(PolylineLocation, f32)> = None;
+        for (segment, start, end) in self.segments() {
+            let (t, distance_squared) = measure(start, end);
+            let length = start.distance(end);
+            if closest.is_none_or(|(_, closest)| distance_squared < closest) {
+                closest = Some((
+                    PolylineLocation {
+                        segment,
+                        t,
+                        point: start.lerp(end, t),
+                        distance: start_distance + t * length,
+                    },
+                    distance_squared,
+                ));
+            }
+            start_distance += length;
+        }
+        closest.map(|(location, _)| location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A strip of length 2 along X, then a strip of length 1 above it.
+    fn two_strips() -> Polyline {
+        let mut polyline = Polyline::from_strips([
+            vec![Vec3::ZERO, Vec3::X, Vec3::new(2.0, 0.0, 0.0)],
+            vec![Vec3::Y, Vec3::new(1.0, 1.0, 0.0)],
+        ]);
+        polyline.scalars = vec![0.0, 1.0, 2.0, 3.0, 4.0];
+        polyline
+    }
+
+    #[test]
+    fn split_moves_the_following_breaks_to_the_second_half() {
+        let polyline = two_strips();
+        let (first, second) = polyline.split_at_distance(1.5).unwrap();
+
+        let point = Vec3::new(1.5, 0.0, 0.0);
+        assert_eq!(first.vertices, [Vec3::ZERO, Vec3::X, point]);
+        assert!(first.breaks.is_empty());
+        assert_eq!(first.scalars, [0.0, 1.0, 1.5]);
+
+        assert_eq!(
+            second.vertices,
+            [
+                point,
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::Y,
+                Vec3::new(1.0, 1.0, 0.0)
+            ]
+        );
+        assert_eq!(second.breaks, [2]);
+        assert_eq!(second.scalars, [1.5, 2.0, 3.0, 4.0]);
+        assert_eq!(first.length() + second.length(), polyline.length());
+    }
+
+    #[test]
+    fn split_keeps_the_previous_breaks_in_the_first_half() {
+        let (first, second) = two_strips().split_at_distance(2.5).unwrap();
+        assert_eq!(first.breaks, [3]);
+        assert_eq!(first.vertices[3..], [Vec3::Y, Vec3::new(0.5, 1.0, 0.0)]);
+        assert!(second.breaks.is_empty());
+        assert_eq!(
+            second.vertices,
+            [Vec3::new(0.5, 1.0, 0.0), Vec3::new(1.0, 1.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn split_at_the_ends() {
+        let polyline = two_strips();
+
+        let (first, second) = polyline.split_at_distance(0.0).unwrap();
+        assert_eq!(first.vertices, [Vec3::ZERO, Vec3::ZERO]);
+        assert_eq!(first.length(), 0.0);
+        assert_eq!(second, polyline);
+
+        // Distances past the end are clamped to the length
+        for distance in [polyline.length(), 10.0] {
+            let (first, second) = polyline.split_at_distance(distance).unwrap();
+            assert_eq!(first, polyline);
+            assert_eq!(second.vertices, [Vec3::new(1.0, 1.0, 0.0); 2]);
+            assert_eq!(second.length(), 0.0);
+        }
+    }
+
+    #[test]
+    fn split_without_segments() {
+        assert!(Polyline::default().split_at_distance(1.0).is_none());
+    }
+
+    #[test]
+    fn split_at_a_vertex() {
+        let polyline = two_strips();
+
+        // At a break, the strips go to either half without a strip of length 0
+        let (first, second) = polyline.split_at_distance(2.0).unwrap();
+        assert_eq!(
+            first.vertices,
+            [Vec3::ZERO, Vec3::X, Vec3::new(2.0, 0.0, 0.0)]
+        );
+        assert!(first.breaks.is_empty());
+        assert_eq!(first.scalars, [0.0, 1.0, 2.0]);
+        assert_eq!(second.vertices, [Vec3::Y, Vec3::new(1.0, 1.0, 0.0)]);
+        assert!(second.breaks.is_empty());
+        assert_eq!(second.scalars, [3.0, 4.0]);
+
+        // Inside a strip, the vertex is in both halves
+        let (first, second) = polyline.split_at_distance(1.0).unwrap();
+        assert_eq!(first.vertices, [Vec3::ZERO, Vec3::X]);
+        assert_eq!(second.vertices[..2], [Vec3::X, Vec3::new(2.0, 0.0, 0.0)]);
+        assert_eq!(second.breaks, [2]);
+        assert_eq!(second.scalars, [1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn length_skips_the_gaps_between_strips() {
+        assert_eq!(two_strips().length(), 3.0);
+        assert_eq!(Polyline::default().length(), 0.0);
+    }
+
+    #[test]
+    fn points_and_tangents_at_distances() {
+        let polyline = two_strips();
+        assert_eq!(
+            polyline.point_at_distance(0.5),
+            Some(Vec3::new(0.5, 0.0, 0.0))
+        );
+        assert_eq!(
+            polyline.point_at_distance(2.5),
+            Some(Vec3::new(0.5, 1.0, 0.0))
+        );
+        assert_eq!(polyline.point_at_distance(-1.0), Some(Vec3::ZERO));
+        assert_eq!(
+            polyline.point_at_distance(10.0),
+            Some(Vec3::new(1.0, 1.0, 0.0))
+        );
+        assert_eq!(Polyline::default().point_at_distance(0.0), None);
+
+        let bent = Polyline {
+            vertices: vec![Vec3::ZERO, Vec3::X, Vec3::X, Vec3::new(1.0, 1.0, 0.0)],
+            ..default()
+        };
+        assert_eq!(bent.tangent_at_distance(0.5), Some(Dir3::X));
+        assert_eq!(bent.tangent_at_distance(1.5), Some(Dir3::Y));
+        // At a vertex, the tangent is the one of the next segment with a length
+        assert_eq!(bent.tangent_at_distance(1.0), Some(Dir3::Y));
+        // A segment of length 0 at the end has no direction
+        let ending_on_a_point = Polyline {
+            vertices: vec![Vec3::ZERO, Vec3::X, Vec3::X],
+            ..default()
+        };
+        assert_eq!(ending_on_a_point.tangent_at_distance(10.0), None);
+    }
+
+    #[test]
+    fn closest_location_to_a_point() {
+        let polyline = two_strips();
+        let location = polyline
+            .closest_location(Vec3::new(1.5, -0.2, 0.0))
+            .unwrap();
+        assert_eq!(location.segment, 1);
+        assert!((location.t - 0.5).abs() < 1e-6);
+        assert!((location.distance - 1.5).abs() < 1e-6);
+
+        let location = polyline
+            .closest_location(Vec3::new(0.25, 1.5, 0.0))
+            .unwrap();
+        assert_eq!(location.segment, 3);
+        assert_eq!(location.point, Vec3::new(0.25, 1.0, 0.0));
+        assert_eq!(location.distance, 2.25);
+
+        // Past the end of the polyline, its last vertex is the closest
+        let location = polyline.closest_location(Vec3::new(5.0, 0.0, 0.0)).unwrap();
+        assert_eq!((location.segment, location.t), (1, 1.0));
+        assert!(Polyline::default().closest_location(Vec3::ZERO).is_none());
+    }
+
+    #[test]
+    fn closest_location_to_a_ray() {
+        let polyline = two_strips();
+        let ray = Ray3d::new(Vec3::new(1.5, 0.2, 5.0), Dir3::NEG_Z);
+        let location = polyline.closest_location_to_ray(ray).unwrap();
+        assert_eq!(location.segment, 1);
+        assert!(location.point.abs_diff_eq(Vec3::new(1.5, 0.0, 0.0), 1e-6));
+
+        let segment = Polyline {
+            vertices: vec![Vec3::ZERO, Vec3::X],
+            ..default()
+        };
+        // A parallel ray is as close to the segment from its origin onward, the location is
+        // the closest to the origin
+        let parallel = Ray3d::new(Vec3::new(0.5, 1.0, 0.0), Dir3::X);
+        let location = segment.closest_location_to_ray(parallel).unwrap();
+        assert!((location.t - 0.5).abs() < 1e-6);
+
+        // A ray pointing away is closest at its origin
+        let away = Ray3d::new(Vec3::new(0.25, 1.0, 0.0), Dir3::Y);
+        let location = segment.closest_location_to_ray(away).unwrap();
+        assert!((location.t - 0.25).abs() < 1e-6);
+        let behind = Ray3d::new(Vec3::new(2.0, 1.0, 0.0), Dir3::Y);
+        let location = segment.closest_location_to_ray(behind).unwrap();
+        assert_eq!(location.t, 1.0);
+    }
+
+    #[test]
+    fn bounding_box_of_the_vertices() {
+        let bounds = two_strips().bounding_box().unwrap();
+        assert_eq!(Vec3::from(bounds.min), Vec3::ZERO);
+        assert_eq!(Vec3::from(bounds.max), Vec3::new(2.0, 1.0, 0.0));
+        assert!(Polyline::default().bounding_box().is_none());
+    }
+}
@@ -0,0 +1,500 @@
+use crate::{
+    material::{PolylineMarker, PolylineMaterial, PolylineMaterialHandle},
+    polyline::{Polyline, PolylineBundle, PolylineHandle},
+    primitives::{PolylineBuilder, PolylineDrawable},
+};
+use bevy::{
+    asset::AssetEvents,
+    ecs::system::SystemParam,
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+    render::{view::VisibilitySystems, Extract, RenderApp},
+    transform::TransformSystem,
+};
+
+/// Default style of the lines drawn with [`PolylineGizmos`], used unless a call overrides it.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct PolylineGizmoConfig {
+    /// See [`PolylineMaterial::width`]. The default is 4.
+    pub width: f32,
+    /// See [`PolylineMaterial::perspective`]. The default is `false`.
+    pub perspective: bool,
+    /// See [`PolylineMaterial::depth_bias`]. The default is 0.
+    pub depth_bias: f32,
+}
+
+impl Default for PolylineGizmoConfig {
+    fn default() -> Self {
+        Self {
+            width: 4.0,
+            perspective: false,
+            depth_bias: 0.0,
+        }
+    }
+}
+
+/// Style of a line, the lines sharing a style are drawn as the strips of a single polyline.
+#[derive(Clone, Copy, Debug)]
+struct PolylineGizmoStyle {
+    color: LinearRgba,
+    width: f32,
+    perspective: bool,
+    depth_bias: f32,
+    /// Size of the arrowhead at the end of the lines, if any.
+    head_size: Option<f32>,
+}
+
+impl PolylineGizmoStyle {
+    fn key(&self) -> [u32; 8] {
+        let LinearRgba {
+            red,
+            green,
+            blue,
+            alpha,
+        } = self.color;
+        [
+            red.to_bits(),
+            green.to_bits(),
+            blue.to_bits(),
+            alpha.to_bits(),
+            self.width.to_bits(),
+            self.perspective as u32,
+            self.depth_bias.to_bits(),
+            self.head_size.map_or(u32::MAX, f32::to_bits),
+        ]
+    }
+
+    fn material(&self) -> PolylineMaterial {
+        PolylineMaterial {
+            width: self.width,
+            color: self.color,
+            perspective: self.perspective,
+            depth_bias: self.depth_bias,
+            end_marker: self.head_size.map(|_| PolylineMarker::Arrow),
+            marker_size: self.head_size.unwrap_or_default(),
+            ..default()
+        }
+    }
+}
+
+/// The lines drawn with [`PolylineGizmos`] since the last frame, grouped by style.
+#[derive(Resource, Default)]
+pub struct PolylineGizmoBuffer {
+    lines: HashMap<[u32; 8], (PolylineGizmoStyle, Polyline)>,
+}
+
+/// A system parameter drawing polylines for a single frame, like Bevy's gizmos but with thick
+/// lines rendered by the polyline pipeline.
+///
+/// Each call returns a builder that can change the style of the line before it is dropped.
+/// Lines drawn before [`PostUpdate`] are displayed in the same frame.
+#[derive(SystemParam)]
+pub struct PolylineGizmos<'w> {
+    buffer: ResMut<'w, PolylineGizmoBuffer>,
+    config: Res<'w, PolylineGizmoConfig>,
+}
+
+impl PolylineGizmos<'_> {
+    /// Draws a line from `start` to `end`.
+    pub fn line(
+        &mut self,
+        start: Vec3,
+        end: Vec3,
+        color: impl Into<Color>,
+    ) -> PolylineGizmoBuilder<'_> {
+        self.builder(PolylineGizmoShape::Strip(vec![start, end]), color)
+    }
+
+    /// Draws a line going through `positions`.
+    pub fn linestrip(
+        &mut self,
+        positions: impl IntoIterator<Item = Vec3>,
+        color: impl Into<Color>,
+    ) -> PolylineGizmoBuilder<'_> {
+        self.builder(
+            PolylineGizmoShape::Strip(positions.into_iter().collect()),
+            color,
+        )
+    }
+
+    /// Draws a circle of `radius` in the XY plane of `isometry`, like the circles of Bevy's
+    /// gizmos.
+    pub fn circle(
+        &mut self,
+        isometry: impl Into<Isometry3d>,
+        radius: f32,
+        color: impl Into<Color>,
+    ) -> PolylineGizmoBuilder<'_> {
+        self.builder(
+            PolylineGizmoShape::Circle {
+                isometry: isometry.into(),
+                radius,
+                resolution: 32,
+            },
+            color,
+        )
+    }
+
+    /// Draws a line from `start` to `end` with an arrowhead at `end`.
+    ///
+    /// The arrowhead is 3 times as wide as the line unless changed with
+    /// [`head_size`](PolylineGizmoBuilder::head_size).
+    pub fn arrow(
+        &mut self,
+        start: Vec3,
+        end: Vec3,
+        color: impl Into<Color>,
+    ) -> PolylineGizmoBuilder<'_> {
+        let mut builder = self.builder(PolylineGizmoShape::Strip(vec![start, end]), color);
+        builder.arrow = true;
+        builder
+    }
+
+    fn builder(
+        &mut self,
+        shape: PolylineGizmoShape,
+        color: impl Into<Color>,
+    ) -> PolylineGizmoBuilder<'_> {
+        PolylineGizmoBuilder {
+            width: self.config.width,
+            perspective: self.config.perspective,
+            depth_bias: self.config.depth_bias,
+            color: color.into().to_linear(),
+            arrow: false,
+            head_size: None,
+            shape,
+            buffer: &mut self.buffer,
+        }
+    }
+}
+
+enum PolylineGizmoShape {
+    Strip(Vec<Vec3>),
+    Circle {
+        isometry: Isometry3d,
+        radius: f32,
+        resolution: u32,
+    },
+}
+
+/// A line drawn with [`PolylineGizmos`], added to the frame when dropped.
+pub struct PolylineGizmoBuilder<'a> {
+    buffer: &'a mut PolylineGizmoBuffer,
+    shape: PolylineGizmoShape,
+    color: LinearRgba,
+    width: f32,
+    perspective: bool,
+    depth_bias: f32,
+    arrow: bool,
+    head_size: Option<f32>,
+}
+
+impl PolylineGizmoBuilder<'_> {
+    /// Sets the width of the line, see [`PolylineMaterial::width`].
+    pub fn width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Sets whether the width of the line is in world units, see
+    /// [`PolylineMaterial::perspective`].
+    pub fn perspective(mut self, perspective: bool) -> Self {
+        self.perspective = perspective;
+        self
+    }
+
+    /// Sets the depth bias of the line, see [`PolylineMaterial::depth_bias`].
+    pub fn depth_bias(mut self, depth_bias: f32) -> Self {
+        self.depth_bias = depth_bias;
+        self
+    }
+
+    /// Sets the number of segments of a circle. This does nothing for other lines.
+    pub fn resolution(mut self, resolution: u32) -> Self {
+        if let PolylineGizmoShape::Circle {
+            resolution: shape_resolution,
+            ..
+        } = &mut self.shape
+        {
+            *shape_resolution = resolution;
+        }
+        self
+    }
+
+    /// Sets the size of the arrowhead of an arrow, in the same units as the width. This does
+    /// nothing for other lines.
+    pub fn head_size(mut self, head_size: f32) -> Self {
+        self.head_size = Some(head_size);
+        self
+    }
+}
+
+impl Drop for PolylineGizmoBuilder<'_> {
+    fn drop(&mut self) {
+        let vertices = match &mut self.shape {
+            PolylineGizmoShape::Strip(vertices) => std::mem::take(vertices),
+            PolylineGizmoShape::Circle {
+                isometry,
+                radius,
+                resolution,
+            } => Circle::new(*radius)
+                .polyline()
+                .resolution(*resolution)
+                .build()
+                .vertices
+                .into_iter()
+                .map(|vertex| *isometry * vertex)
+                .collect(),
+        };
+        let style = PolylineGizmoStyle {
+            color: self.color,
+            width: self.width,
+            perspective: self.perspective,
+            depth_bias: self.depth_bias,
+            head_size: self
+                .arrow
+                .then(|| self.head_size.unwrap_or(3.0 * self.width)),
+        };
+        self.buffer
+            .lines
+            .entry(style.key())
+            .or_insert_with(|| (style, Polyline::default()))
+            .1
+            .push_strip(vertices);
+    }
+}
+
+/// Marks the entities drawing the lines of [`PolylineGizmos`].
+///
+/// Picking and [`PolylineSvgExport`](crate::svg_export::PolylineSvgExport) leave them out.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct PolylineGizmo;
+
+/// An entity drawing the lines of a style, along with its assets. It is reused for another
+/// style once its style is no longer drawn.
+struct PolylineGizmoEntity {
+    entity: Entity,
+    polyline: Handle<Polyline>,
+    material: Handle<PolylineMaterial>,
+    /// The key of the style drawn by the entity, `None` while it is hidden.
+    key: Option<[u32; 8]>,
+}
+
+#[derive(Resource, Default)]
+struct PolylineGizmoEntities(Vec<PolylineGizmoEntity>);
+
+/// The polylines of the gizmos in the render world, which don't get levels of detail.
+#[derive(Resource, Default, Deref)]
+pub struct ExtractedPolylineGizmos(HashSet<AssetId<Polyline>>);
+
+/// Draws the lines of [`PolylineGizmos`].
+pub struct PolylineGizmoPlugin;
+
+impl Plugin for PolylineGizmoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PolylineGizmoConfig>()
+            .init_resource::<PolylineGizmoBuffer>()
+            .init_resource::<PolylineGizmoEntities>()
+            .add_systems(
+                PostUpdate,
+                update_polyline_gizmos
+                    .before(AssetEvents)
+                    .before(TransformSystem::TransformPropagate)
+                    .before(VisibilitySystems::VisibilityPropagate),
+            );
+
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ExtractedPolylineGizmos>()
+                .add_systems(ExtractSchedule, extract_polyline_gizmos);
+        }
+    }
+}
+
+fn extract_polyline_gizmos(
+    entities: Extract<Res<PolylineGizmoEntities>>,
+    mut extracted: ResMut<ExtractedPolylineGizmos>,
+) {
+    if entities.is_changed() {
+        extracted.0 = entities.0.iter().map(|gizmo| gizmo.polyline.id()).collect();
+    }
+}
+
+/// Moves the lines drawn this frame to one polyline entity per style.
+///
+/// The entities and assets are kept while their style is still drawn, and only updated when
+/// the lines change, so drawing the same lines every frame doesn't upload them again. The
+/// entities of the styles no longer drawn are reused by the new styles, updating their
+/// material in place, so animated colors or widths don't spawn entities every frame. The
+/// entities left over are hidden until a style needs them.
+fn update_polyline_gizmos(
+    mut commands: Commands,
+    mut buffer: ResMut<PolylineGizmoBuffer>,
+    mut entities: ResMut<PolylineGizmoEntities>,
+    mut polylines: ResMut<Assets<Polyline>>,
+    mut materials: ResMut<Assets<PolylineMaterial>>,
+) {
+    let mut lines = std::mem::take(&mut buffer.lines);
+
+    let mut free = Vec::new();
+    for (index, gizmo) in entities.0.iter_mut().enumerate() {
+        let Some((_, polyline)) = gizmo.key.and_then(|key| lines.remove(&key)) else {
+            free.push(index);
+            continue;
+        };
+        if polylines.get(&gizmo.polyline) != Some(&polyline) {
+            polylines.insert(&gizmo.polyline, polyline);
+        }
+    }
+
+    let mut free = free.into_iter();
+    for (key, (style, polyline)) in lines {
+        let Some(index) = free.next() else {
+            let polyline = polylines.add(polyline);
+            let material = materials.add(style.material());
+            let entity = commands
+                .spawn((
+                    PolylineBundle {
+                        polyline: PolylineHandle(polyline.clone()),
+                        material: PolylineMaterialHandle(material.clone()),
+                        ..default()
+                    },
+                    PolylineGizmo,
+                ))
+                .id();
+            entities.0.push(PolylineGizmoEntity {
+                entity,
+                polyline,
+                material,
+                key: Some(key),
+            });
+            continue;
+        };
+        let gizmo = &mut entities.0[index];
+        if gizmo.key.replace(key).is_none() {
+            commands
+                .entity(gizmo.entity)
+                .try_insert(Visibility::Inherited);
+        }
+        materials.insert(&gizmo.material, style.material());
+        polylines.insert(&gizmo.polyline, polyline);
+    }
+
+    // Hide the entities without a style, emptying their polyline to free its buffers
+    for index in free {
+        let gizmo = &mut entities.0[index];
+        if gizmo.key.take().is_some() {
+            commands.entity(gizmo.entity).try_insert(Visibility::Hidden);
+            polylines.insert(&gizmo.polyline, Polyline::default());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            TaskPoolPlugin::default(),
+            AssetPlugin::default(),
+            PolylineGizmoPlugin,
+        ))
+        .init_asset::<Polyline>()
+        .init_asset::<PolylineMaterial>();
+        app
+    }
+
+    /// Draws lines and runs a frame.
+    fn update(app: &mut App, draw: fn(&mut PolylineGizmos)) {
+        app.world_mut()
+            .run_system_once(move |mut gizmos: PolylineGizmos| draw(&mut gizmos))
+            .unwrap();
+        app.update();
+    }
+
+    /// The gizmo entities with their visibility, polyline and material.
+    fn gizmos(app: &mut App) -> Vec<(Entity, Visibility, Polyline, PolylineMaterial)> {
+        let world = app.world_mut();
+        let mut query = world.query_filtered::<(
+            Entity,
+            &Visibility,
+            &PolylineHandle,
+            &PolylineMaterialHandle,
+        ), With<PolylineGizmo>>();
+        let polylines = world.resource::<Assets<Polyline>>();
+        let materials = world.resource::<Assets<PolylineMaterial>>();
+        let mut gizmos: Vec<_> = query
+            .iter(world)
+            .map(|(entity, visibility, polyline, material)| {
+                (
+                    entity,
+                    *visibility,
+                    polylines.get(&polyline.0).unwrap().clone(),
+                    materials.get(&material.0).unwrap().clone(),
+                )
+            })
+            .collect();
+        gizmos.sort_by_key(|(entity, ..)| *entity);
+        gizmos
+    }
+
+    #[test]
+    fn lines_of_a_style_are_strips_of_a_polyline() {
+        let mut app = app();
+        update(&mut app, |gizmos| {
+            gizmos.line(Vec3::ZERO, Vec3::X, Color::WHITE);
+            gizmos.linestrip([Vec3::Y, Vec3::ONE, Vec3::Z], Color::WHITE);
+            gizmos.line(Vec3::ZERO, Vec3::X, Color::WHITE).width(8.0);
+        });
+
+        let gizmos = gizmos(&mut app);
+        assert_eq!(gizmos.len(), 2);
+        let (_, _, polyline, material) = gizmos
+            .iter()
+            .find(|(.., material)| material.width == 4.0)
+            .unwrap();
+        assert_eq!(polyline.vertices.len(), 5);
+        assert_eq!(polyline.breaks, [2]);
+        assert_eq!(material.color, LinearRgba::WHITE);
+    }
+
+    #[test]
+    fn entities_are_reused_and_hidden() {
+        let mut app = app();
+        update(&mut app, |gizmos| {
+            gizmos.line(Vec3::ZERO, Vec3::X, Color::WHITE);
+        });
+        let [(entity, ..)] = gizmos(&mut app)[..] else {
+            panic!("expected a gizmo entity");
+        };
+
+        // A new style takes the entity of the style no longer drawn
+        update(&mut app, |gizmos| {
+            gizmos.line(Vec3::ZERO, Vec3::Y, Color::BLACK);
+        });
+        let gizmos_after = gizmos(&mut app);
+        assert_eq!(gizmos_after.len(), 1);
+        let (reused, visibility, polyline, material) = &gizmos_after[0];
+        assert_eq!(*reused, entity);
+        assert_eq!(*visibility, Visibility::Inherited);
+        assert_eq!(polyline.vertices, [Vec3::ZERO, Vec3::Y]);
+        assert_eq!(material.color, LinearRgba::BLACK);
+
+        // Without lines, the entity is hidden and its polyline emptied until drawn again
+        update(&mut app, |_| {});
+        let (_, visibility, polyline, _) = &gizmos(&mut app)[0];
+        assert_eq!(*visibility, Visibility::Hidden);
+        assert!(polyline.vertices.is_empty());
+
+        update(&mut app, |gizmos| {
+            gizmos.circle(Isometry3d::IDENTITY, 1.0, Color::WHITE);
+        });
+        let gizmos_after = gizmos(&mut app);
+        assert_eq!(gizmos_after.len(), 1);
+        assert_eq!(gizmos_after[0].0, entity);
+        assert_eq!(gizmos_after[0].1, Visibility::Inherited);
+    }
+}
@@ -5,15 +5,58 @@ use bevy::{
     asset::{load_internal_asset, weak_handle},
     prelude::*,
 };
+use gizmos::PolylineGizmoPlugin;
 use material::PolylineMaterialPlugin;
 use polyline::{PolylineBasePlugin, PolylineRenderPlugin};
+use prepass::PolylinePrepassPlugin;
+use simplify::PolylineSimplificationPlugin;
+use wireframe::PolylineWireframePlugin;
 
+pub mod contour;
+pub mod curve;
+#[cfg(feature = "geojson")]
+pub mod geojson;
+pub mod geometry;
+pub mod gizmos;
+pub mod loader;
 pub mod material;
+pub mod mesh;
+pub mod obj;
+#[cfg(feature = "picking")]
+pub mod picking;
+pub mod ply;
 pub mod polyline;
+pub mod prepass;
+pub mod primitives;
+mod scene;
+pub mod simplify;
+#[cfg(feature = "svg")]
+pub mod svg;
+pub mod svg_export;
+pub mod wireframe;
 
 pub mod prelude {
-    pub use crate::material::{PolylineMaterial, PolylineMaterialHandle};
+    pub use crate::contour::HeightGrid;
+    pub use crate::curve::PolylineTessellation;
+    #[cfg(feature = "geojson")]
+    pub use crate::geojson::{GeoJsonLoaderSettings, GeoJsonPolylines, GeoProjection};
+    pub use crate::gizmos::{PolylineGizmo, PolylineGizmoConfig, PolylineGizmos};
+    pub use crate::loader::{PolylineFile, PolylineFilePlugin};
+    pub use crate::material::{
+        PolylineColormap, PolylineMarker, PolylineMaterial, PolylineMaterialHandle,
+        PolylineOccludedStyle, PolylineShading,
+    };
+    #[cfg(feature = "picking")]
+    pub use crate::picking::{
+        PolylinePickingCamera, PolylinePickingHits, PolylinePickingPlugin, PolylinePickingSettings,
+    };
     pub use crate::polyline::{Polyline, PolylineBundle, PolylineHandle};
+    pub use crate::primitives::{PolylineBuilder, PolylineDrawable};
+    pub use crate::simplify::{PolylineSimplification, PolylineSimplificationMethod};
+    #[cfg(feature = "svg")]
+    pub use crate::svg::{SvgLoaderSettings, SvgPlane, SvgPolylines};
+    pub use crate::svg_export::{PolylineSvgExport, PolylineSvgWriter};
+    pub use crate::wireframe::{PolylineWireframe, PolylineWireframeBuilder};
     pub use crate::PolylinePlugin;
 }
 pub struct PolylinePlugin;
@@ -33,6 +76,14 @@ impl Plugin for PolylinePlugin {
             PolylineBasePlugin,
             PolylineRenderPlugin,
             PolylineMaterialPlugin,
+            PolylinePrepassPlugin,
+            PolylineSimplificationPlugin,
+            PolylineWireframePlugin,
+            PolylineGizmoPlugin,
         ));
+        #[cfg(feature = "geojson")]
+        app.add_plugins(geojson::PolylineGeoJsonPlugin);
+        #[cfg(feature = "svg")]
+        app.add_plugins(svg::PolylineSvgPlugin);
     }
 }
@@ -0,0 +1,463 @@
+use crate::{material::PolylineMaterial, obj::ObjLoader, ply::PlyLoader, polyline::Polyline};
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The content of a `.polyline.ron` or `.polyline.json` file.
+///
+/// ```ron
+/// (
+///     vertices: [(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (1.0, 1.0, 0.0)],
+///     strips: [[(0.0, 0.0, 1.0), (0.0, 1.0, 1.0)]],
+///     widths: [1.0, 2.0, 1.0, 1.0, 1.0],
+///     color: Some((1.0, 0.5, 0.0, 1.0)),
+///     width: Some(4.0),
+/// )
+/// ```
+///
+/// Every field but `vertices` is optional. In JSON, the vertices and colors are arrays.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PolylineFile {
+    /// Vertices of the first strip.
+    #[serde(default)]
+    pub vertices: Vec<[f32; 3]>,
+    /// Strips following the first one, not connected to it nor to each other.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub strips: Vec<Vec<[f32; 3]>>,
+    /// See [`Polyline::scalars`], for the vertices of all the strips in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scalars: Vec<f32>,
+    /// See [`Polyline::colors`], in sRGB with an alpha channel, for the vertices of all the
+    /// strips in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub colors: Vec<[f32; 4]>,
+    /// See [`Polyline::widths`], for the vertices of all the strips in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub widths: Vec<f32>,
+    /// Color of the line in sRGB, with an alpha channel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<[f32; 4]>,
+    /// See [`PolylineMaterial::width`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<f32>,
+}
+
+impl PolylineFile {
+    /// Creates the polyline described by the file.
+    pub fn polyline(&self) -> Result<Polyline, PolylineLoaderError> {
+        let mut polyline = Polyline::from_strips(
+            std::iter::once(&self.vertices)
+                .chain(&self.strips)
+                .map(|strip| strip.iter().copied().map(Vec3::from)),
+        );
+        if !self.scalars.is_empty() && self.scalars.len() != polyline.vertices.len() {
+            return Err(PolylineLoaderError::ScalarCount {
+                scalars: self.scalars.len(),
+                vertices: polyline.vertices.len(),
+            });
+        }
+        if !self.colors.is_empty() && self.colors.len() != polyline.vertices.len() {
+            return Err(PolylineLoaderError::ColorCount {
+                colors: self.colors.len(),
+                vertices: polyline.vertices.len(),
+            });
+        }
+        if !self.widths.is_empty() && self.widths.len() != polyline.vertices.len() {
+            return Err(PolylineLoaderError::WidthCount {
+                widths: self.widths.len(),
+                vertices: polyline.vertices.len(),
+            });
+        }
+        polyline.scalars = self.scalars.clone();
+        polyline.colors = self
+            .colors
+            .iter()
+            .map(|&color| Srgba::from_f32_array(color).into())
+            .collect();
+        polyline.widths = self.widths.clone();
+        Ok(polyline)
+    }
+
+    /// Creates the material described by the file, if it has a color or a width.
+    pub fn material(&self) -> Option<PolylineMaterial> {
+        if self.color.is_none() && self.width.is_none() {
+            return None;
+        }
+        let default = PolylineMaterial::default();
+        Some(PolylineMaterial {
+            color: self
+                .color
+                .map_or(default.color, |color| Srgba::from_f32_array(color).into()),
+            width: self.width.unwrap_or(default.width),
+            ..default
+        })
+    }
+}
+
+impl From<&Polyline> for PolylineFile {
+    fn from(polyline: &Polyline) -> Self {
+        let mut strips = polyline.strips().map(|strip| {
+            polyline.vertices[strip]
+                .iter()
+                .map(|v| v.to_array())
+                .collect()
+        });
+        Self {
+            vertices: strips.next().unwrap_or_default(),
+            strips: strips.collect(),
+            scalars: polyline.scalars.clone(),
+            colors: polyline
+                .colors
+                .iter()
+                .map(|&color| Srgba::from(color).to_f32_array())
+                .collect(),
+            widths: polyline.widths.clone(),
+            ..default()
+        }
+    }
+}
+
+/// Loads [`Polyline`] assets from `.polyline.ron`, `.polyline.json` and `.polyline.csv` files.
+///
+/// The RON and JSON files contain a [`PolylineFile`]. When they have a color or a width, the
+/// [`PolylineMaterial`] is added with the `material` label, and can be loaded with a path like
+/// `route.polyline.ron#material`.
+///
+/// The CSV files contain one vertex per line, and empty lines start a new strip. A first line
+/// that isn't made of numbers is a header. When it names the `x` and `y` columns, the values
+/// are read from the named columns:
+/// - `x`, `y` and an optional `z` for the position,
+/// - `scalar` for the [`scalars`](Polyline::scalars),
+/// - `red`, `green`, `blue` and an optional `alpha` in sRGB, or `r`, `g`, `b` and `a`, for the
+///   [`colors`](Polyline::colors),
+/// - `width` for the [`widths`](Polyline::widths).
+///
+/// Other columns are ignored. Without such a header, the lines are `x,y`, `x,y,z` or
+/// `x,y,z,scalar`, so 2D points with a scalar need a header.
+#[derive(Default)]
+pub struct PolylineLoader;
+
+/// An error when loading a [`Polyline`] file.
+#[derive(Debug, Error)]
+pub enum PolylineLoaderError {
+    #[error("could not read the polyline file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse the RON polyline: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("could not parse the JSON polyline: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid CSV polyline at line {line}: {message}")]
+    Csv { line: usize, message: String },
+    #[error("the polyline has {scalars} scalars for {vertices} vertices")]
+    ScalarCount { scalars: usize, vertices: usize },
+    #[error("the polyline has {colors} colors for {vertices} vertices")]
+    ColorCount { colors: usize, vertices: usize },
+    #[error("the polyline has {widths} widths for {vertices} vertices")]
+    WidthCount { widths: usize, vertices: usize },
+}
+
+impl AssetLoader for PolylineLoader {
+    type Asset = Polyline;
+    type Settings = ();
+    type Error = PolylineLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Polyline, PolylineLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let path = load_context.path().to_string_lossy();
+        if path.ends_with(".csv") {
+            return parse_csv(&String::from_utf8_lossy(&bytes));
+        }
+        let file = if path.ends_with(".json") {
+            serde_json::from_slice::<PolylineFile>(&bytes)?
+        } else {
+            ron::de::from_bytes::<PolylineFile>(&bytes)?
+        };
+        if let Some(material) = file.material() {
+            load_context.add_labeled_asset("material".into(), material);
+        }
+        file.polyline()
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["polyline.ron", "polyline.json", "polyline.csv"]
+    }
+}
+
+/// Indices of the columns of a CSV polyline.
+#[derive(Clone, Copy)]
+struct CsvColumns {
+    x: usize,
+    y: usize,
+    z: Option<usize>,
+    scalar: Option<usize>,
+    color: Option<[usize; 3]>,
+    alpha: Option<usize>,
+    width: Option<usize>,
+}
+
+impl CsvColumns {
+    /// The columns named by a header, if it names the `x` and `y` columns.
+    fn from_header(names: &[&str]) -> Option<Self> {
+        let column = |aliases: &[&str]| {
+            names
+                .iter()
+                .position(|name| aliases.iter().any(|alias| name.eq_ignore_ascii_case(alias)))
+        };
+        let color = match [
+            column(&["red", "r"]),
+            column(&["green", "g"]),
+            column(&["blue", "b"]),
+        ] {
+            [Some(red), Some(green), Some(blue)] => Some([red, green, blue]),
+            _ => None,
+        };
+        Some(Self {
+            x: column(&["x"])?,
+            y: column(&["y"])?,
+            z: column(&["z"]),
+            scalar: column(&["scalar"]),
+            color,
+            alpha: column(&["alpha", "a"]),
+            width: column(&["width"]),
+        })
+    }
+
+    /// The columns of a line without header, from its number of values.
+    fn from_count(count: usize) -> Option<Self> {
+        let (z, scalar) = match count {
+            2 => (None, None),
+            3 => (Some(2), None),
+            4 => (Some(2), Some(3)),
+            _ => return None,
+        };
+        Some(Self {
+            x: 0,
+            y: 1,
+            z,
+            scalar,
+            color: None,
+            alpha: None,
+            width: None,
+        })
+    }
+}
+
+/// Loads polyline files with the [`PolylineLoader`], [`ObjLoader`] and [`PlyLoader`].
+///
+/// It isn't part of the [`PolylinePlugin`](crate::PolylinePlugin), so that `.obj` and `.ply`
+/// files are left to other loaders in apps that don't need them as polylines.
+pub struct PolylineFilePlugin;
+
+impl Plugin for PolylineFilePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset_loader::<PolylineLoader>()
+            .init_asset_loader::<ObjLoader>()
+            .init_asset_loader::<PlyLoader>();
+    }
+}
+
+fn parse_csv(text: &str) -> Result<Polyline, PolylineLoaderError> {
+    let mut polyline = Polyline::default();
+    let mut header = None;
+    let mut new_strip = false;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            new_strip = true;
+            continue;
+        }
+        let error = |message: &str| PolylineLoaderError::Csv {
+            line: index + 1,
+            message: message.into(),
+        };
+        let values: Vec<&str> = line.split(',').map(str::trim).collect();
+        if index == 0 && values.iter().any(|value| value.parse::<f32>().is_err()) {
+            header = CsvColumns::from_header(&values);
+            continue;
+        }
+        let columns = match header {
+            Some(columns) => columns,
+            None => CsvColumns::from_count(values.len())
+                .ok_or_else(|| error("expected 2 to 4 values"))?,
+        };
+        let value = |column: usize| {
+            values
+                .get(column)
+                .and_then(|value| value.parse::<f32>().ok())
+                .ok_or_else(|| error("expected numbers"))
+        };
+        let position = Vec3::new(
+            value(columns.x)?,
+            value(columns.y)?,
+            columns.z.map_or(Ok(0.0), value)?,
+        );
+        let scalar = columns.scalar.map(value).transpose()?;
+        if !polyline.vertices.is_empty() {
+            if scalar.is_some() == polyline.scalars.is_empty() {
+                return Err(error(
+                    "either every vertex or none of them must have a scalar",
+                ));
+            }
+            if new_strip {
+                polyline.breaks.push(polyline.vertices.len());
+            }
+        }
+        new_strip = false;
+        polyline.vertices.push(position);
+        polyline.scalars.extend(scalar);
+        if let Some([red, green, blue]) = columns.color {
+            let alpha = columns.alpha.map_or(Ok(1.0), value)?;
+            let color = Srgba::new(value(red)?, value(green)?, value(blue)?, alpha);
+            polyline.colors.push(color.into());
+        }
+        if let Some(width) = columns.width {
+            polyline.widths.push(value(width)?);
+        }
+    }
+    Ok(polyline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_header_is_skipped() {
+        let polyline = parse_csv("easting,northing,elevation\n1,2,3\n4,5,6\n").unwrap();
+        assert_eq!(
+            polyline.vertices,
+            [Vec3::new(1.0, 2.0, 3.0), Vec3::new(4.0, 5.0, 6.0)]
+        );
+        assert!(polyline.scalars.is_empty());
+    }
+
+    #[test]
+    fn csv_rows_without_header() {
+        // 2D and 3D rows can be mixed, and empty lines start a new strip
+        let polyline = parse_csv("1,2\n3,4,5\n\n6,7\n").unwrap();
+        assert_eq!(
+            polyline.vertices,
+            [
+                Vec3::new(1.0, 2.0, 0.0),
+                Vec3::new(3.0, 4.0, 5.0),
+                Vec3::new(6.0, 7.0, 0.0)
+            ]
+        );
+        assert_eq!(polyline.breaks, [2]);
+
+        let polyline = parse_csv("1,2,3,0.5\n4,5,6,1.5\n").unwrap();
+        assert_eq!(polyline.scalars, [0.5, 1.5]);
+    }
+
+    #[test]
+    fn csv_mismatched_scalars_are_errors() {
+        for csv in ["1,2,3,0.5\n4,5,6\n", "1,2,3\n4,5,6,0.5\n"] {
+            assert!(matches!(
+                parse_csv(csv),
+                Err(PolylineLoaderError::Csv { line: 2, .. })
+            ));
+        }
+        assert!(matches!(
+            parse_csv("1\n"),
+            Err(PolylineLoaderError::Csv { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_csv("1,2\n3,four\n"),
+            Err(PolylineLoaderError::Csv { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn csv_header_names_the_columns() {
+        let csv = "id,Y,X,scalar,r,g,b,width\na,2,1,0.5,1,0,0,2\nb,4,3,1.5,0,0,1,3\n";
+        let polyline = parse_csv(csv).unwrap();
+        assert_eq!(
+            polyline.vertices,
+            [Vec3::new(1.0, 2.0, 0.0), Vec3::new(3.0, 4.0, 0.0)]
+        );
+        assert_eq!(polyline.scalars, [0.5, 1.5]);
+        assert_eq!(polyline.colors, [LinearRgba::RED, LinearRgba::BLUE]);
+        assert_eq!(polyline.widths, [2.0, 3.0]);
+
+        assert!(matches!(
+            parse_csv("x,y,width\n1,2\n"),
+            Err(PolylineLoaderError::Csv { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn file_per_vertex_values() {
+        let file: PolylineFile = ron::from_str(
+            "(vertices: [(0, 0, 0), (1, 0, 0)], strips: [[(0, 1, 0), (1, 1, 0)]], \
+             colors: [(1, 0, 0, 1), (1, 0, 0, 1), (0, 0, 1, 1), (0, 0, 1, 1)], \
+             widths: [1, 2, 3, 4])",
+        )
+        .unwrap();
+        let polyline = file.polyline().unwrap();
+        assert_eq!(polyline.breaks, [2]);
+        assert_eq!(polyline.colors[2], LinearRgba::BLUE);
+        assert_eq!(polyline.widths, [1.0, 2.0, 3.0, 4.0]);
+        assert!(file.material().is_none());
+
+        let json: PolylineFile = serde_json::from_str(
+            r#"{"vertices": [[0, 0, 0], [1, 0, 0]], "widths": [1, 2], "width": 4}"#,
+        )
+        .unwrap();
+        assert_eq!(json.polyline().unwrap().widths, [1.0, 2.0]);
+        assert_eq!(json.material().unwrap().width, 4.0);
+
+        // The colors go through linear space, so they are only kept up to rounding errors
+        let written = PolylineFile::from(&polyline);
+        assert_eq!(written.vertices, file.vertices);
+        assert_eq!(written.strips, file.strips);
+        assert_eq!(written.widths, file.widths);
+        for (written, color) in written.colors.iter().zip(&file.colors) {
+            assert!(Vec4::from(*written).abs_diff_eq(Vec4::from(*color), 1e-5));
+        }
+    }
+
+    #[test]
+    fn file_value_counts_must_match_the_vertices() {
+        let file = PolylineFile {
+            vertices: vec![[0.0; 3], [1.0; 3]],
+            scalars: vec![1.0],
+            ..default()
+        };
+        assert!(matches!(
+            file.polyline(),
+            Err(PolylineLoaderError::ScalarCount {
+                scalars: 1,
+                vertices: 2
+            })
+        ));
+
+        let file = PolylineFile {
+            vertices: vec![[0.0; 3], [1.0; 3]],
+            colors: vec![[1.0; 4]; 3],
+            ..default()
+        };
+        assert!(matches!(
+            file.polyline(),
+            Err(PolylineLoaderError::ColorCount { colors: 3, .. })
+        ));
+
+        let file = PolylineFile {
+            vertices: vec![[0.0; 3], [1.0; 3]],
+            widths: vec![1.0],
+            ..default()
+        };
+        assert!(matches!(
+            file.polyline(),
+            Err(PolylineLoaderError::WidthCount { widths: 1, .. })
+        ));
+    }
+}
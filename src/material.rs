@@ -1,12 +1,18 @@
-use crate::polyline::{
-    DrawPolyline, PolylineHandle, PolylinePipeline, PolylinePipelineKey, PolylineUniform,
-    PolylineViewBindGroup, SetPolylineBindGroup,
+use crate::{
+    polyline::{
+        DrawPolyline, GpuPolyline, Polyline, PolylineHandle, PolylinePipeline, PolylinePipelineKey,
+        PolylineUniform, PolylineViewBindGroup, SetPolylineBindGroup,
+    },
+    scene::{deserialize_handle, load_scene_handle, serialize_handle},
 };
 
 use bevy::{
     core_pipeline::{
         core_3d::{AlphaMask3d, Opaque3d, Opaque3dBatchSetKey, Opaque3dBinKey, Transparent3d},
-        prepass::{OpaqueNoLightmap3dBatchSetKey, OpaqueNoLightmap3dBinKey},
+        prepass::{
+            DeferredPrepass, Opaque3dPrepass, OpaqueNoLightmap3dBatchSetKey,
+            OpaqueNoLightmap3dBinKey, PreviousViewUniformOffset,
+        },
     },
     ecs::{
         component::Tick,
@@ -21,18 +27,37 @@ use bevy::{
         extract_component::{ExtractComponent, ExtractComponentPlugin},
         render_asset::{PrepareAssetError, RenderAsset, RenderAssetPlugin, RenderAssets},
         render_phase::*,
-        render_resource::{binding_types::uniform_buffer, *},
+        render_resource::{
+            binding_types::{sampler, texture_2d, uniform_buffer},
+            *,
+        },
         renderer::{RenderDevice, RenderQueue},
+        texture::{FallbackImage, GpuImage},
         view::{ExtractedView, RenderVisibleEntities, ViewUniformOffset},
         Render, RenderApp, RenderSet,
     },
 };
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 
-#[derive(Debug, Clone, Default, Component, ExtractComponent)]
-pub struct PolylineMaterialHandle(pub Handle<PolylineMaterial>);
+/// The [`PolylineMaterial`] of a polyline entity.
+///
+/// Saved in scenes like [`PolylineHandle`].
+#[derive(Debug, Clone, Default, Component, ExtractComponent, Reflect, Serialize, Deserialize)]
+#[reflect(opaque)]
+#[reflect(Component, Default, Debug, Serialize, Deserialize)]
+#[component(on_insert = load_scene_handle::<PolylineMaterialHandle>)]
+#[serde(transparent)]
+pub struct PolylineMaterialHandle(
+    #[serde(
+        serialize_with = "serialize_handle",
+        deserialize_with = "deserialize_handle"
+    )]
+    pub Handle<PolylineMaterial>,
+);
 
-#[derive(Asset, Debug, PartialEq, Clone, Copy, TypePath)]
+#[derive(Asset, Debug, PartialEq, Clone, Reflect)]
+#[reflect(Default, Debug)]
 pub struct PolylineMaterial {
     /// Width of the line.
     ///
@@ -62,6 +87,71 @@ pub struct PolylineMaterial {
     ///
     /// Note that `depth_bias` **does not** interact with this in any way.
     pub perspective: bool,
+    /// Colormap used to color the line from its per-vertex [`Polyline::scalars`](crate::polyline::Polyline::scalars).
+    ///
+    /// The colormap value is multiplied with `color`. Polylines without scalars are drawn
+    /// with `color` only.
+    pub colormap: Option<PolylineColormap>,
+    /// Scalar value mapped to the start of the colormap.
+    pub scalar_min: f32,
+    /// Scalar value mapped to the end of the colormap.
+    ///
+    /// Values outside of `scalar_min..=scalar_max` are clamped to the ends of the colormap.
+    pub scalar_max: f32,
+    /// Width of the outline drawn on each side of the line, in the same units as `width`.
+    ///
+    /// The outline is drawn in the same quad as the line, so it is affected by
+    /// `perspective` and `depth_bias` the same way. A width of 0 disables the outline.
+    pub outline_width: f32,
+    /// Color of the outline, typically chosen to contrast with `color`.
+    pub outline_color: LinearRgba,
+    /// Style of the portions of the line hidden behind other geometry.
+    ///
+    /// When set, the line is drawn a second time in the transparent pass with an inverted
+    /// depth test, so only the occluded fragments are drawn with this style. This keeps lines
+    /// such as underground drill holes readable through the terrain, without making the whole
+    /// line float on top like a `depth_bias` of -1 does.
+    ///
+    /// When `None`, occluded portions of the line are not drawn.
+    pub occluded: Option<PolylineOccludedStyle>,
+    /// Whether the line is drawn in the prepasses of cameras with a
+    /// [`DepthPrepass`](bevy::core_pipeline::prepass::DepthPrepass),
+    /// [`NormalPrepass`](bevy::core_pipeline::prepass::NormalPrepass) or
+    /// [`MotionVectorPrepass`](bevy::core_pipeline::prepass::MotionVectorPrepass).
+    ///
+    /// This lets effects that read the prepass textures, like SSAO or depth of field, take
+    /// the line into account. Only opaque lines are drawn in the prepass.
+    ///
+    /// Motion vectors follow changes of the line's `GlobalTransform`, which keeps moving lines
+    /// stable under TAA and motion blur. Changes to the vertices themselves are not tracked.
+    pub prepass: bool,
+    /// Whether the line casts shadows from lights with shadows enabled.
+    ///
+    /// Requires the `pbr` feature. Only opaque lines cast shadows. The `width` of a line that
+    /// isn't in `perspective` mode is measured in shadow map texels.
+    pub cast_shadows: bool,
+    /// Whether the `DistanceFog` of the camera is applied to the line.
+    ///
+    /// Requires the `pbr` feature. The fog is applied with its base color only, its
+    /// `directional_light_color` is ignored as lines are not lit.
+    pub fog: bool,
+    /// How the line is shaded across its width.
+    pub shading: PolylineShading,
+    /// Maximum error in screen pixels of the simplified versions drawn for long polylines.
+    ///
+    /// Polylines with many vertices are simplified into several levels of detail when they are
+    /// uploaded to the GPU, and the least detailed level whose error stays below this tolerance
+    /// is drawn in each view. A tolerance of 0 always draws the full resolution polyline.
+    pub lod_tolerance: f32,
+    /// Marker drawn at the start of each strip of the line, such as an arrowhead.
+    ///
+    /// Markers are drawn with the line, so they share its color, depth bias and
+    /// perspective. They are oriented along the first or last segment of the strip on screen.
+    pub start_marker: Option<PolylineMarker>,
+    /// Marker drawn at the end of each strip of the line, see `start_marker`.
+    pub end_marker: Option<PolylineMarker>,
+    /// Size of the markers, in the same units as `width`.
+    pub marker_size: f32,
 }
 
 impl Default for PolylineMaterial {
@@ -71,6 +161,143 @@ impl Default for PolylineMaterial {
             color: Color::WHITE.to_linear(),
             depth_bias: 0.0,
             perspective: false,
+            colormap: None,
+            scalar_min: 0.0,
+            scalar_max: 1.0,
+            outline_width: 0.0,
+            outline_color: Color::BLACK.to_linear(),
+            occluded: None,
+            prepass: false,
+            cast_shadows: false,
+            fog: false,
+            shading: PolylineShading::Flat,
+            lod_tolerance: 0.5,
+            start_marker: None,
+            end_marker: None,
+            marker_size: 30.0,
+        }
+    }
+}
+
+/// How the occluded portions of a polyline are drawn, see [`PolylineMaterial::occluded`].
+#[derive(Debug, PartialEq, Clone, Copy, Reflect)]
+#[reflect(Debug)]
+pub struct PolylineOccludedStyle {
+    /// Color of the occluded portions, replacing [`PolylineMaterial::color`].
+    ///
+    /// This is usually a dimmed version of the line color, with an alpha lower than 1.
+    pub color: LinearRgba,
+    /// Length of the dashes and of the gaps between them, in screen pixels.
+    ///
+    /// A length of 0 draws a solid line.
+    pub dash_length: f32,
+}
+
+impl Default for PolylineOccludedStyle {
+    fn default() -> Self {
+        Self {
+            color: LinearRgba::new(1.0, 1.0, 1.0, 0.25),
+            dash_length: 0.0,
+        }
+    }
+}
+
+/// Shading of a polyline across its width, see [`PolylineMaterial::shading`].
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Reflect)]
+#[reflect(Default, Debug)]
+pub enum PolylineShading {
+    /// The line is drawn with a flat color.
+    #[default]
+    Flat,
+    /// The line is shaded like a cylinder lit by a headlight slightly above the camera.
+    ///
+    /// The normal of the cylinder is reconstructed in the fragment shader from the position
+    /// across the line, which gives depth cues to 3D pipe networks without generating meshes.
+    /// Scene lights are not taken into account.
+    Tube,
+}
+
+/// A shape drawn at an end of the strips of a polyline, see [`PolylineMaterial::end_marker`].
+#[derive(Debug, PartialEq, Clone, Reflect)]
+#[reflect(Debug)]
+pub enum PolylineMarker {
+    /// A triangle pointing away from the line, with its base centered on the end of the line.
+    Arrow,
+    /// A disc centered on the end of the line.
+    Circle,
+    /// A square centered on the end of the line, aligned with it.
+    Square,
+    /// The segments of a polyline, drawn with the width of the line.
+    ///
+    /// The vertices are in units of `marker_size`, with the end of the line at the origin, X
+    /// pointing away from the line and Y across it. Their Z is ignored. Only the first
+    /// 16 segments are drawn.
+    Custom(Polyline),
+}
+
+impl PolylineMarker {
+    fn index(marker: Option<&Self>) -> u32 {
+        match marker {
+            None => 0,
+            Some(PolylineMarker::Arrow) => 1,
+            Some(PolylineMarker::Circle) => 2,
+            Some(PolylineMarker::Square) => 3,
+            Some(PolylineMarker::Custom(_)) => 4,
+        }
+    }
+
+    /// Number of vertices drawn for the marker, matching `marker_position` in `polyline.wgsl`.
+    fn vertex_count(marker: Option<&Self>) -> u32 {
+        match marker {
+            None => 0,
+            Some(PolylineMarker::Arrow) => 3,
+            Some(PolylineMarker::Circle) => 3 * MARKER_CIRCLE_SEGMENTS,
+            Some(PolylineMarker::Square) => 6,
+            Some(PolylineMarker::Custom(polyline)) => {
+                6 * polyline.segments().count().min(MARKER_MAX_SEGMENTS) as u32
+            }
+        }
+    }
+
+    /// Segments of a custom marker, as their start and end packed in a `Vec4`.
+    fn segments(marker: Option<&Self>) -> [Vec4; MARKER_MAX_SEGMENTS] {
+        let mut segments = [Vec4::ZERO; MARKER_MAX_SEGMENTS];
+        if let Some(PolylineMarker::Custom(polyline)) = marker {
+            for (segment, (_, start, end)) in segments.iter_mut().zip(polyline.segments()) {
+                *segment = Vec4::new(start.x, start.y, end.x, end.y);
+            }
+        }
+        segments
+    }
+}
+
+const MARKER_CIRCLE_SEGMENTS: u32 = 16;
+const MARKER_MAX_SEGMENTS: usize = 16;
+/// Vertices reserved for each marker, the end marker starting after the start marker.
+pub(crate) const MARKER_MAX_VERTICES: u32 = 6 * MARKER_MAX_SEGMENTS as u32;
+
+/// Maps per-vertex scalars of a [`Polyline`](crate::polyline::Polyline) to colors.
+#[derive(Debug, PartialEq, Clone, Reflect)]
+#[reflect(Debug)]
+pub enum PolylineColormap {
+    /// The perceptually uniform viridis colormap from matplotlib.
+    Viridis,
+    /// Google's turbo colormap, an improved rainbow colormap.
+    Turbo,
+    /// A user supplied lookup table.
+    ///
+    /// The image is sampled horizontally along its vertical center, with `scalar_min` at the
+    /// left edge and `scalar_max` at the right edge, using the image's own sampler.
+    Image(Handle<Image>),
+}
+
+impl PolylineColormap {
+    fn index(colormap: Option<&Self>) -> u32 {
+        match colormap {
+            None => 0,
+            Some(PolylineColormap::Viridis) => 1,
+            Some(PolylineColormap::Turbo) => 2,
+            Some(PolylineColormap::Image(_)) => 3,
         }
     }
 }
@@ -79,9 +306,13 @@ impl PolylineMaterial {
     pub fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
         render_device.create_bind_group_layout(
             "polyline_material_layout",
-            &BindGroupLayoutEntries::single(
-                ShaderStages::VERTEX,
-                uniform_buffer::<PolylineMaterialUniform>(false),
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::VERTEX_FRAGMENT,
+                (
+                    uniform_buffer::<PolylineMaterialUniform>(false),
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                ),
             ),
         )
     }
@@ -103,32 +334,105 @@ pub struct PolylineMaterialUniform {
     pub color: Vec4,
     pub depth_bias: f32,
     pub width: f32,
+    pub scalar_min: f32,
+    pub scalar_max: f32,
+    pub colormap: u32,
+    pub outline_width: f32,
+    pub outline_color: Vec4,
+    pub occluded_color: Vec4,
+    pub occluded_dash_length: f32,
+    pub start_marker: u32,
+    pub end_marker: u32,
+    pub marker_size: f32,
+    pub start_marker_segments: [Vec4; MARKER_MAX_SEGMENTS],
+    pub end_marker_segments: [Vec4; MARKER_MAX_SEGMENTS],
 }
 
 pub struct GpuPolylineMaterial {
     pub buffer: UniformBuffer<PolylineMaterialUniform>,
     pub perspective: bool,
+    /// Whether per-vertex scalars should be mapped through a colormap.
+    pub colormap: bool,
+    /// Whether the line is drawn with an outline.
+    pub outline: bool,
+    /// Whether the occluded portions of the line are drawn in a second pass.
+    pub occluded: bool,
+    pub prepass: bool,
+    pub cast_shadows: bool,
+    pub fog: bool,
+    /// Whether the line is shaded like a lit tube.
+    pub tube: bool,
+    pub lod_tolerance: f32,
+    /// Number of vertices drawn for the marker at the start of each strip.
+    pub start_marker_vertices: u32,
+    /// Number of vertices drawn for the marker at the end of each strip.
+    pub end_marker_vertices: u32,
     pub bind_group: BindGroup,
     pub alpha_mode: AlphaMode,
 }
 
+impl GpuPolylineMaterial {
+    /// Pipeline key flags that change the shape of the line, which must match between all
+    /// the passes drawing it.
+    pub fn geometry_key(&self) -> PolylinePipelineKey {
+        let mut key = PolylinePipelineKey::NONE;
+        if self.perspective {
+            key |= PolylinePipelineKey::PERSPECTIVE
+        }
+        if self.outline {
+            key |= PolylinePipelineKey::OUTLINE
+        }
+        key
+    }
+}
+
 impl RenderAsset for GpuPolylineMaterial {
     type SourceAsset = PolylineMaterial;
     type Param = (
         SRes<RenderDevice>,
         SRes<RenderQueue>,
         SRes<PolylineMaterialPipeline>,
+        SRes<RenderAssets<GpuImage>>,
+        SRes<FallbackImage>,
     );
 
     fn prepare_asset(
         polyline_material: Self::SourceAsset,
         _: AssetId<Self::SourceAsset>,
-        (device, queue, polyline_pipeline): &mut bevy::ecs::system::SystemParamItem<Self::Param>,
+        (device, queue, polyline_pipeline, gpu_images, fallback_image): &mut bevy::ecs::system::SystemParamItem<Self::Param>,
     ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        let colormap_image = match &polyline_material.colormap {
+            Some(PolylineColormap::Image(handle)) => {
+                let Some(gpu_image) = gpu_images.get(handle) else {
+                    return Err(PrepareAssetError::RetryNextUpdate(polyline_material));
+                };
+                gpu_image
+            }
+            _ => &fallback_image.d2,
+        };
+
         let value = PolylineMaterialUniform {
             width: polyline_material.width,
             depth_bias: polyline_material.depth_bias,
             color: polyline_material.color.to_f32_array().into(),
+            scalar_min: polyline_material.scalar_min,
+            scalar_max: polyline_material.scalar_max,
+            colormap: PolylineColormap::index(polyline_material.colormap.as_ref()),
+            outline_width: polyline_material.outline_width,
+            outline_color: polyline_material.outline_color.to_f32_array().into(),
+            occluded_color: polyline_material
+                .occluded
+                .map_or(Vec4::ZERO, |style| style.color.to_f32_array().into()),
+            occluded_dash_length: polyline_material
+                .occluded
+                .map_or(0.0, |style| style.dash_length),
+            start_marker: PolylineMarker::index(polyline_material.start_marker.as_ref()),
+            end_marker: PolylineMarker::index(polyline_material.end_marker.as_ref()),
+            marker_size: polyline_material.marker_size,
+            start_marker_segments: PolylineMarker::segments(
+                polyline_material.start_marker.as_ref(),
+            ),
+            end_marker_segments: PolylineMarker::segments(polyline_material.end_marker.as_ref()),
         };
 
         let mut buffer = UniformBuffer::from(value);
@@ -141,10 +445,17 @@ impl RenderAsset for GpuPolylineMaterial {
         let bind_group = device.create_bind_group(
             Some("polyline_material_bind_group"),
             &polyline_pipeline.material_layout,
-            &BindGroupEntries::single(buffer_binding),
+            &BindGroupEntries::sequential((
+                buffer_binding,
+                &colormap_image.texture_view,
+                &colormap_image.sampler,
+            )),
         );
 
-        let alpha_mode = if polyline_material.color.alpha() < 1.0 {
+        let outline = polyline_material.outline_width > 0.0;
+        let alpha_mode = if polyline_material.color.alpha() < 1.0
+            || (outline && polyline_material.outline_color.alpha() < 1.0)
+        {
             AlphaMode::Blend
         } else {
             AlphaMode::Opaque
@@ -153,6 +464,20 @@ impl RenderAsset for GpuPolylineMaterial {
         Ok(GpuPolylineMaterial {
             buffer,
             perspective: polyline_material.perspective,
+            colormap: polyline_material.colormap.is_some(),
+            outline,
+            occluded: polyline_material.occluded.is_some(),
+            prepass: polyline_material.prepass,
+            cast_shadows: polyline_material.cast_shadows,
+            fog: polyline_material.fog,
+            tube: polyline_material.shading == PolylineShading::Tube,
+            lod_tolerance: polyline_material.lod_tolerance,
+            start_marker_vertices: PolylineMarker::vertex_count(
+                polyline_material.start_marker.as_ref(),
+            ),
+            end_marker_vertices: PolylineMarker::vertex_count(
+                polyline_material.end_marker.as_ref(),
+            ),
             alpha_mode,
             bind_group,
         })
@@ -166,8 +491,10 @@ pub struct PolylineMaterialPlugin;
 impl Plugin for PolylineMaterialPlugin {
     fn build(&self, app: &mut App) {
         app.init_asset::<PolylineMaterial>()
+            .register_asset_reflect::<PolylineMaterial>()
+            .register_type::<PolylineMaterialHandle>()
             .add_plugins(ExtractComponentPlugin::<PolylineMaterialHandle>::default())
-            .add_plugins(RenderAssetPlugin::<GpuPolylineMaterial>::default());
+            .add_plugins(RenderAssetPlugin::<GpuPolylineMaterial, GpuImage>::default());
     }
 
     fn finish(&self, app: &mut App) {
@@ -179,6 +506,11 @@ impl Plugin for PolylineMaterialPlugin {
                 .init_resource::<PolylineMaterialPipeline>()
                 .init_resource::<SpecializedRenderPipelines<PolylineMaterialPipeline>>()
                 .add_systems(Render, queue_material_polylines.in_set(RenderSet::Queue));
+            #[cfg(feature = "pbr")]
+            render_app
+                .add_render_command::<Transparent3d, DrawPolylineFog>()
+                .add_render_command::<Opaque3d, DrawPolylineFog>()
+                .add_render_command::<AlphaMask3d, DrawPolylineFog>();
         }
     }
 }
@@ -211,8 +543,20 @@ impl SpecializedRenderPipeline for PolylineMaterialPipeline {
                 .shader_defs
                 .push("POLYLINE_PERSPECTIVE".into());
         }
+        let view_layout = if key.contains(PolylinePipelineKey::MOTION_VECTOR_PREPASS) {
+            &self.polyline_pipeline.view_motion_vectors_layout
+        } else {
+            #[cfg(feature = "pbr")]
+            if key.contains(PolylinePipelineKey::FOG) {
+                &self.polyline_pipeline.view_fog_layout
+            } else {
+                &self.polyline_pipeline.view_layout
+            }
+            #[cfg(not(feature = "pbr"))]
+            &self.polyline_pipeline.view_layout
+        };
         descriptor.layout = vec![
-            self.polyline_pipeline.view_layout.clone(),
+            view_layout.clone(),
             self.polyline_pipeline.polyline_layout.clone(),
             self.material_layout.clone(),
         ];
@@ -220,7 +564,7 @@ impl SpecializedRenderPipeline for PolylineMaterialPipeline {
     }
 }
 
-type DrawPolylineMaterial = (
+pub(crate) type DrawPolylineMaterial = (
     SetItemPipeline,
     SetPolylineViewBindGroup<0>,
     SetPolylineBindGroup<1>,
@@ -228,6 +572,25 @@ type DrawPolylineMaterial = (
     DrawPolyline,
 );
 
+/// Like [`DrawPolylineMaterial`], but binding the previous frame's view for motion vectors.
+pub(crate) type DrawPolylineMotionVectors = (
+    SetItemPipeline,
+    SetPolylineMotionVectorsViewBindGroup<0>,
+    SetPolylineBindGroup<1>,
+    SetMaterialBindGroup<2>,
+    DrawPolyline,
+);
+
+/// Like [`DrawPolylineMaterial`], but binding the view's distance fog.
+#[cfg(feature = "pbr")]
+pub(crate) type DrawPolylineFog = (
+    SetItemPipeline,
+    SetPolylineFogViewBindGroup<0>,
+    SetPolylineBindGroup<1>,
+    SetMaterialBindGroup<2>,
+    DrawPolyline,
+);
+
 pub struct SetPolylineViewBindGroup<const I: usize>;
 impl<const I: usize, P: PhaseItem> RenderCommand<P> for SetPolylineViewBindGroup<I> {
     type ViewQuery = (Read<ViewUniformOffset>, Read<PolylineViewBindGroup>);
@@ -247,6 +610,71 @@ impl<const I: usize, P: PhaseItem> RenderCommand<P> for SetPolylineViewBindGroup
     }
 }
 
+pub struct SetPolylineMotionVectorsViewBindGroup<const I: usize>;
+impl<const I: usize, P: PhaseItem> RenderCommand<P> for SetPolylineMotionVectorsViewBindGroup<I> {
+    type ViewQuery = (
+        Read<ViewUniformOffset>,
+        Option<Read<PreviousViewUniformOffset>>,
+        Read<PolylineViewBindGroup>,
+    );
+    type ItemQuery = ();
+    type Param = ();
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        (view_uniform, previous_view_uniform, view_bind_group): ROQueryItem<'w, Self::ViewQuery>,
+        _entity: Option<ROQueryItem<'w, Self::ItemQuery>>,
+        _param: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let (Some(previous_view_uniform), Some(bind_group)) =
+            (previous_view_uniform, &view_bind_group.motion_vectors)
+        else {
+            return RenderCommandResult::Skip;
+        };
+        pass.set_bind_group(
+            I,
+            bind_group,
+            &[view_uniform.offset, previous_view_uniform.offset],
+        );
+        RenderCommandResult::Success
+    }
+}
+
+#[cfg(feature = "pbr")]
+pub struct SetPolylineFogViewBindGroup<const I: usize>;
+#[cfg(feature = "pbr")]
+impl<const I: usize, P: PhaseItem> RenderCommand<P> for SetPolylineFogViewBindGroup<I> {
+    type ViewQuery = (
+        Read<ViewUniformOffset>,
+        Option<Read<bevy::pbr::ViewFogUniformOffset>>,
+        Read<PolylineViewBindGroup>,
+    );
+    type ItemQuery = ();
+    type Param = ();
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        (view_uniform, view_fog_uniform, view_bind_group): ROQueryItem<'w, Self::ViewQuery>,
+        _entity: Option<ROQueryItem<'w, Self::ItemQuery>>,
+        _param: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let (Some(view_fog_uniform), Some(bind_group)) = (view_fog_uniform, &view_bind_group.fog)
+        else {
+            return RenderCommandResult::Skip;
+        };
+        pass.set_bind_group(
+            I,
+            bind_group,
+            &[view_uniform.offset, view_fog_uniform.offset],
+        );
+        RenderCommandResult::Success
+    }
+}
+
 pub struct SetMaterialBindGroup<const I: usize>;
 impl<const I: usize, P: PhaseItem> RenderCommand<P> for SetMaterialBindGroup<I> {
     type ViewQuery = ();
@@ -281,8 +709,17 @@ pub fn queue_material_polylines(
     mut pipelines: ResMut<SpecializedRenderPipelines<PolylineMaterialPipeline>>,
     pipeline_cache: Res<PipelineCache>,
     render_materials: Res<RenderAssets<GpuPolylineMaterial>>,
-    material_meshes: Query<(&PolylineMaterialHandle, &PolylineUniform)>,
-    views: Query<(&ExtractedView, &RenderVisibleEntities, &Msaa)>,
+    render_polylines: Res<RenderAssets<GpuPolyline>>,
+    material_meshes: Query<(&PolylineMaterialHandle, &PolylineUniform, &PolylineHandle)>,
+    views: Query<(
+        Entity,
+        &ExtractedView,
+        &RenderVisibleEntities,
+        &Msaa,
+        Has<DeferredPrepass>,
+    )>,
+    prepass_phases: Res<ViewBinnedRenderPhases<Opaque3dPrepass>>,
+    #[cfg(feature = "pbr")] fog_views: Query<(), With<bevy::pbr::DistanceFog>>,
     mut opaque_phases: ResMut<ViewBinnedRenderPhases<Opaque3d>>,
     mut alpha_mask_phases: ResMut<ViewBinnedRenderPhases<AlphaMask3d>>,
     mut transparent_phases: ResMut<ViewSortedRenderPhases<Transparent3d>>,
@@ -295,27 +732,73 @@ pub fn queue_material_polylines(
     let draw_transparent = transparent_draw_functions
         .read()
         .id::<DrawPolylineMaterial>();
+    #[cfg(feature = "pbr")]
+    let (draw_opaque_fog, draw_alpha_mask_fog, draw_transparent_fog) = (
+        opaque_draw_functions.read().id::<DrawPolylineFog>(),
+        alpha_mask_draw_functions.read().id::<DrawPolylineFog>(),
+        transparent_draw_functions.read().id::<DrawPolylineFog>(),
+    );
 
-    for (view, visible_entities, msaa) in &views {
+    for (view_entity, view, visible_entities, msaa, deferred_prepass) in &views {
         let inverse_view_matrix = view.world_from_view.compute_matrix().inverse();
         let inverse_view_row_2 = inverse_view_matrix.row(2);
+        #[cfg(feature = "pbr")]
+        let view_fog = fog_views.contains(view_entity);
+        // Distance fog is part of `bevy_pbr`
+        #[cfg(not(feature = "pbr"))]
+        let view_fog = {
+            let _ = view_entity;
+            false
+        };
 
-        let mut polyline_key = PolylinePipelineKey::from_msaa_samples(msaa.samples());
-        polyline_key |= PolylinePipelineKey::from_hdr(view.hdr);
+        let view_key = PolylinePipelineKey::from_msaa_samples(msaa.samples())
+            | PolylinePipelineKey::from_hdr(view.hdr);
+        // Like in `queue_prepass_polylines`
+        let view_prepass =
+            !deferred_prepass && prepass_phases.contains_key(&view.retained_view_entity);
         for (visible_entity, visible_main_entity) in visible_entities.get::<PolylineHandle>() {
-            let Ok((material_handle, polyline_uniform)) = material_meshes.get(*visible_entity)
+            let Ok((material_handle, polyline_uniform, polyline_handle)) =
+                material_meshes.get(*visible_entity)
             else {
                 continue;
             };
             let Some(material) = render_materials.get(&material_handle.0) else {
                 continue;
             };
+            let mut polyline_key = view_key;
             if material.alpha_mode == AlphaMode::Blend {
                 polyline_key |= PolylinePipelineKey::TRANSPARENT_MAIN_PASS
             }
-            if material.perspective {
-                polyline_key |= PolylinePipelineKey::PERSPECTIVE
+            polyline_key |= material.geometry_key();
+            if view_prepass && material.prepass && material.alpha_mode == AlphaMode::Opaque {
+                polyline_key |= PolylinePipelineKey::DEPTH_PREPASSED
             }
+            if material.tube {
+                polyline_key |= PolylinePipelineKey::TUBE
+            }
+            if material.colormap
+                && render_polylines
+                    .get(&polyline_handle.0)
+                    .is_some_and(|polyline| polyline.scalar_buffer.is_some())
+            {
+                polyline_key |= PolylinePipelineKey::SCALARS
+            }
+            if let Some(render_polyline) = render_polylines.get(&polyline_handle.0) {
+                polyline_key |= render_polyline.geometry_key();
+                if render_polyline.color_buffer.is_some() {
+                    polyline_key |= PolylinePipelineKey::COLORS
+                }
+            }
+            let fog = material.fog && view_fog;
+            if fog {
+                polyline_key |= PolylinePipelineKey::FOG;
+            }
+            #[cfg(feature = "pbr")]
+            let (draw_opaque, draw_alpha_mask, draw_transparent) = if fog {
+                (draw_opaque_fog, draw_alpha_mask_fog, draw_transparent_fog)
+            } else {
+                (draw_opaque, draw_alpha_mask, draw_transparent)
+            };
             let pipeline_id =
                 pipelines.specialize(&pipeline_cache, &material_pipeline, polyline_key);
 
@@ -330,6 +813,30 @@ pub fn queue_material_polylines(
             let this_tick = next_tick.get() + 1;
             next_tick.set(this_tick);
 
+            // NOTE: row 2 of the inverse view matrix dotted with column 3 of the model matrix
+            // gives the z component of translation of the mesh in view space
+            let polyline_z = inverse_view_row_2.dot(polyline_uniform.transform.col(3));
+
+            if material.occluded {
+                // The occluded portions are drawn in a second, always blended pass. The outline
+                // is not drawn for them so the occluded style stays readable.
+                let occluded_key =
+                    (polyline_key - PolylinePipelineKey::OUTLINE) | PolylinePipelineKey::OCCLUDED;
+                transparent_phase.add(Transparent3d {
+                    entity: (*visible_entity, *visible_main_entity),
+                    draw_function: draw_transparent,
+                    pipeline: pipelines.specialize(
+                        &pipeline_cache,
+                        &material_pipeline,
+                        occluded_key,
+                    ),
+                    distance: polyline_z,
+                    batch_range: 0..1,
+                    extra_index: PhaseItemExtraIndex::None,
+                    indexed: false,
+                });
+            }
+
             match material.alpha_mode {
                 AlphaMode::Opaque => {
                     opaque_phase.add(
@@ -373,9 +880,6 @@ pub fn queue_material_polylines(
                 | AlphaMode::Premultiplied
                 | AlphaMode::Add
                 | AlphaMode::Multiply => {
-                    // NOTE: row 2 of the inverse view matrix dotted with column 3 of the model matrix
-                    // gives the z component of translation of the mesh in view space
-                    let polyline_z = inverse_view_row_2.dot(polyline_uniform.transform.col(3));
                     transparent_phase.add(Transparent3d {
                         entity: (*visible_entity, *visible_main_entity),
                         draw_function: draw_transparent,
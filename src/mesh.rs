@@ -0,0 +1,455 @@
+use crate::polyline::Polyline;
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+};
+use std::f32::consts::TAU;
+
+/// Meshes following a polyline, with the same joins and ends for tubes and ribbons.
+///
+/// The shader draws each segment as its own quad ending flat at the vertices of the segment, so
+/// the quads overlap on the inside of bends and leave a notch on the outside. The meshes end
+/// flat at the first and last vertices of each strip like the quads, but the segments meeting
+/// at a vertex share a cross-section in the plane bisecting them, a miter join, so the meshes
+/// have no cracks at the bends. At sharp bends, the cross-section is stretched along the line by
+/// at most 4 times the radius or half width.
+impl Polyline {
+    /// Creates a builder for a tube [`Mesh`] of the given `radius` following this polyline.
+    ///
+    /// Unlike the polyline itself, the tube is real geometry in the polyline's local space, so
+    /// it works where screen space lines don't, such as in VR, for physics colliders or when
+    /// exporting. Each strip of the polyline gets its own tube, closed by
+    /// [`caps`](PolylineTubeMeshBuilder::caps) by default.
+    pub fn tube(&self, radius: f32) -> PolylineTubeMeshBuilder<'_> {
+        PolylineTubeMeshBuilder {
+            polyline: self,
+            radius,
+            radial_segments: 16,
+            caps: true,
+        }
+    }
+
+    /// Creates a builder for a flat ribbon [`Mesh`] of the given `width` following this
+    /// polyline, facing `up` as much as possible. Each strip of the polyline gets its own
+    /// ribbon.
+    pub fn ribbon(&self, width: f32, up: Vec3) -> PolylineRibbonMeshBuilder<'_> {
+        PolylineRibbonMeshBuilder {
+            polyline: self,
+            width,
+            up,
+        }
+    }
+}
+
+/// A builder used for creating a tube [`Mesh`] from a [`Polyline`], see [`Polyline::tube`].
+#[derive(Clone, Copy, Debug)]
+pub struct PolylineTubeMeshBuilder<'a> {
+    pub polyline: &'a Polyline,
+    /// Radius of the tube, in the units of the polyline's local space.
+    pub radius: f32,
+    /// Number of vertices around the tube. Clamped to at least 3.
+    pub radial_segments: u32,
+    /// Whether the two ends of the tube are closed with flat caps.
+    pub caps: bool,
+}
+
+impl PolylineTubeMeshBuilder<'_> {
+    /// Sets the number of vertices around the tube.
+    pub fn radial_segments(mut self, radial_segments: u32) -> Self {
+        self.radial_segments = radial_segments;
+        self
+    }
+
+    /// Sets whether the two ends of the tube are closed with flat caps.
+    pub fn caps(mut self, caps: bool) -> Self {
+        self.caps = caps;
+        self
+    }
+}
+
+impl MeshBuilder for PolylineTubeMeshBuilder<'_> {
+    fn build(&self) -> Mesh {
+        let radial_segments = self.radial_segments.max(3);
+
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let mut indices = Vec::new();
+
+        // Each strip is a separate tube
+        for strip in self.polyline.strips() {
+            let segments = segments(&self.polyline.vertices[strip]);
+            if segments.is_empty() {
+                continue;
+            }
+            let total_length: f32 = segments.iter().map(|segment| segment.length).sum();
+
+            // A ring of vertices at each vertex of the strip, shared by the segments around it
+            // so the tube has no cracks at the bends
+            let mut distance = 0.0;
+            for ring in 0..=segments.len() {
+                let before = &segments[ring.saturating_sub(1)];
+                let after = &segments[ring.min(segments.len() - 1)];
+                let center = if ring < segments.len() {
+                    after.start
+                } else {
+                    before.end
+                };
+                let joint = Joint::new(before, after);
+                let rotation = Quat::from_rotation_arc(before.direction, after.direction);
+                let binormal = before.direction.cross(before.normal);
+                let base = positions.len() as u32;
+                for i in 0..=radial_segments {
+                    let angle = i as f32 / radial_segments as f32 * TAU;
+                    let radial = before.normal * angle.cos() + binormal * angle.sin();
+                    let offset = radial * self.radius;
+                    positions.push(center + joint.project(offset));
+                    // Halfway between the normals of the two cylinders
+                    normals.push(
+                        (radial + rotation * radial)
+                            .try_normalize()
+                            .unwrap_or(radial),
+                    );
+                    uvs.push([i as f32 / radial_segments as f32, distance / total_length]);
+                }
+                if ring > 0 {
+                    for i in 0..radial_segments {
+                        let end = base + i;
+                        let start = end - radial_segments - 1;
+                        indices.extend_from_slice(&[
+                            start,
+                            start + 1,
+                            end,
+                            start + 1,
+                            end + 1,
+                            end,
+                        ]);
+                    }
+                }
+                if ring < segments.len() {
+                    distance += after.length;
+                }
+            }
+
+            if self.caps {
+                if let (Some(first), Some(last)) = (segments.first(), segments.last()) {
+                    for (segment, center, is_end) in
+                        [(first, first.start, false), (last, last.end, true)]
+                    {
+                        let normal = if is_end {
+                            segment.direction
+                        } else {
+                            -segment.direction
+                        };
+                        let binormal = segment.direction.cross(segment.normal);
+                        let base = positions.len() as u32;
+                        positions.push(center);
+                        normals.push(normal);
+                        uvs.push([0.5, 0.5]);
+                        for i in 0..radial_segments {
+                            let angle = i as f32 / radial_segments as f32 * TAU;
+                            let (sin, cos) = angle.sin_cos();
+                            positions.push(
+                                center + (segment.normal * cos + binormal * sin) * self.radius,
+                            );
+                            normals.push(normal);
+                            uvs.push([0.5 + 0.5 * cos, 0.5 + 0.5 * sin]);
+                        }
+                        for i in 0..radial_segments {
+                            let a = base + 1 + i;
+                            let b = base + 1 + (i + 1) % radial_segments;
+                            // The ring goes counter clockwise around the direction of the segment
+                            if is_end {
+                                indices.extend_from_slice(&[base, a, b]);
+                            } else {
+                                indices.extend_from_slice(&[base, b, a]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_indices(Indices::U32(indices))
+    }
+}
+
+/// A builder used for creating a flat ribbon [`Mesh`] from a [`Polyline`], see
+/// [`Polyline::ribbon`].
+#[derive(Clone, Copy, Debug)]
+pub struct PolylineRibbonMeshBuilder<'a> {
+    pub polyline: &'a Polyline,
+    /// Width of the ribbon, in the units of the polyline's local space.
+    pub width: f32,
+    /// Direction the ribbon faces, as much as possible while containing the polyline.
+    ///
+    /// Segments parallel to `up` keep the orientation of the previous segment.
+    pub up: Vec3,
+}
+
+impl MeshBuilder for PolylineRibbonMeshBuilder<'_> {
+    fn build(&self) -> Mesh {
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let mut indices = Vec::new();
+
+        for strip in self.polyline.strips() {
+            let segments = segments(&self.polyline.vertices[strip]);
+            if segments.is_empty() {
+                continue;
+            }
+            let total_length: f32 = segments.iter().map(|segment| segment.length).sum();
+
+            // The direction across each segment, in the plane of the ribbon
+            let mut previous_side = None;
+            let sides: Vec<Vec3> = segments
+                .iter()
+                .map(|segment| {
+                    let side = segment
+                        .direction
+                        .cross(self.up)
+                        .try_normalize()
+                        .or(previous_side)
+                        .unwrap_or(segment.normal);
+                    previous_side = Some(side);
+                    side
+                })
+                .collect();
+
+            // A pair of vertices at each vertex of the strip, shared by the segments around it
+            let mut distance = 0.0;
+            for vertex in 0..=segments.len() {
+                let before = vertex.saturating_sub(1);
+                let after = vertex.min(segments.len() - 1);
+                let center = if vertex < segments.len() {
+                    segments[after].start
+                } else {
+                    segments[before].end
+                };
+                let joint = Joint::new(&segments[before], &segments[after]);
+                let normal_before = sides[before].cross(segments[before].direction);
+                let normal = (normal_before + sides[after].cross(segments[after].direction))
+                    .try_normalize()
+                    .unwrap_or(normal_before);
+                let half_width = sides[before] * 0.5 * self.width;
+
+                let base = positions.len() as u32;
+                for (offset, u) in [(-half_width, 0.0), (half_width, 1.0)] {
+                    positions.push(center + joint.project(offset));
+                    normals.push(normal);
+                    uvs.push([u, distance / total_length]);
+                }
+                if vertex > 0 {
+                    indices.extend_from_slice(&[
+                        base - 2,
+                        base - 1,
+                        base,
+                        base - 1,
+                        base + 1,
+                        base,
+                    ]);
+                }
+                if vertex < segments.len() {
+                    distance += segments[after].length;
+                }
+            }
+        }
+
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_indices(Indices::U32(indices))
+    }
+}
+
+/// Smallest cosine between a segment and the plane of the cross-section at its end, which
+/// limits how much the cross-sections are stretched at sharp bends.
+const MIN_MITER_COS: f32 = 0.25;
+
+/// The vertex between two segments, where the meshes of both meet.
+struct Joint {
+    direction: Vec3,
+    /// Normal of the plane bisecting the segments.
+    miter: Vec3,
+    miter_cos: f32,
+}
+
+impl Joint {
+    fn new(before: &Segment, after: &Segment) -> Self {
+        let miter = (before.direction + after.direction)
+            .try_normalize()
+            .unwrap_or(before.direction);
+        Self {
+            direction: before.direction,
+            miter,
+            miter_cos: before.direction.dot(miter).max(MIN_MITER_COS),
+        }
+    }
+
+    /// Moves an `offset` across the segment before the joint along it, onto the plane
+    /// bisecting the segments where their meshes meet.
+    fn project(&self, offset: Vec3) -> Vec3 {
+        offset - self.direction * (offset.dot(self.miter) / self.miter_cos)
+    }
+}
+
+/// A non degenerate segment of a polyline with its frame.
+struct Segment {
+    start: Vec3,
+    end: Vec3,
+    length: f32,
+    direction: Vec3,
+    /// Unit vector orthogonal to `direction`.
+    normal: Vec3,
+}
+
+/// Splits `vertices` into segments, skipping the degenerate ones.
+///
+/// The normals of the segments are computed by parallel transport, so consecutive segments
+/// don't twist relative to each other.
+fn segments(vertices: &[Vec3]) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    for pair in vertices.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let Some(direction) = (end - start).try_normalize() else {
+            continue;
+        };
+        let normal = match segments.last() {
+            Some(previous) => {
+                let normal =
+                    Quat::from_rotation_arc(previous.direction, direction) * previous.normal;
+                // Remove the drift accumulated over many segments
+                (normal - direction * normal.dot(direction))
+                    .try_normalize()
+                    .unwrap_or_else(|| direction.any_orthonormal_vector())
+            }
+            None => direction.any_orthonormal_vector(),
+        };
+        segments.push(Segment {
+            start,
+            end,
+            length: start.distance(end),
+            direction,
+            normal,
+        });
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(mesh: &Mesh) -> Vec<Vec3> {
+        let positions = mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap();
+        positions
+            .as_float3()
+            .unwrap()
+            .iter()
+            .copied()
+            .map(Vec3::from)
+            .collect()
+    }
+
+    /// Distance from `point` to the infinite line through `start` along `direction`.
+    fn distance_to_line(point: Vec3, start: Vec3, direction: Vec3) -> f32 {
+        let offset = point - start;
+        (offset - direction * offset.dot(direction)).length()
+    }
+
+    #[test]
+    fn bends_share_a_ring() {
+        let polyline = Polyline {
+            vertices: vec![Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0)],
+            ..default()
+        };
+        let mesh = polyline.tube(0.1).radial_segments(8).caps(false).build();
+        let positions = positions(&mesh);
+        // Three rings of 9 vertices, the last one repeating the first for the seam
+        assert_eq!(positions.len(), 3 * 9);
+        assert_eq!(mesh.indices().unwrap().len(), 2 * 8 * 6);
+
+        // The ring of the bend is on both cylinders, in the plane bisecting them
+        let miter = Vec3::new(1.0, 1.0, 0.0).normalize();
+        for &position in &positions[9..18] {
+            assert!((distance_to_line(position, Vec3::ZERO, Vec3::X) - 0.1).abs() < 1e-5);
+            assert!((distance_to_line(position, Vec3::X, Vec3::Y) - 0.1).abs() < 1e-5);
+            assert!((position - Vec3::X).dot(miter).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn sharp_bends_stay_close_to_the_line() {
+        let polyline = Polyline {
+            vertices: vec![Vec3::ZERO, Vec3::X, Vec3::new(0.0, 0.01, 0.0)],
+            ..default()
+        };
+        let mesh = polyline.tube(0.1).caps(false).build();
+        for position in positions(&mesh) {
+            let closest = polyline
+                .vertices
+                .iter()
+                .map(|vertex| vertex.distance(position))
+                .fold(f32::INFINITY, f32::min);
+            // At most the radius across the line and the stretch of the ring along it
+            let max_distance = 0.1 * (1.0 + 1.0 / (MIN_MITER_COS * MIN_MITER_COS)).sqrt();
+            assert!(closest <= max_distance + 1e-5);
+        }
+    }
+
+    #[test]
+    fn strips_are_separate_tubes() {
+        let polyline = Polyline::from_strips([
+            vec![Vec3::ZERO, Vec3::X, Vec3::new(2.0, 0.0, 0.0)],
+            vec![Vec3::Y, Vec3::Y],
+            vec![Vec3::Z, Vec3::new(0.0, 1.0, 1.0)],
+        ]);
+        let mesh = polyline.tube(0.1).radial_segments(4).build();
+        // Rings of 5 vertices, and caps of 5 vertices at each end of the tubes. The strip
+        // without length has no tube.
+        assert_eq!(positions(&mesh).len(), (3 + 2) * 5 + 4 * 5);
+
+        // Rings of straight joints are circles
+        for &position in &positions(&mesh)[5..10] {
+            assert!((position.distance(Vec3::X) - 0.1).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn ribbon_bends_share_a_pair_of_vertices() {
+        let polyline = Polyline {
+            vertices: vec![Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0)],
+            ..default()
+        };
+        let mesh = polyline.ribbon(0.2, Vec3::Z).build();
+        let positions = positions(&mesh);
+        assert_eq!(positions.len(), 3 * 2);
+        assert_eq!(mesh.indices().unwrap().len(), 2 * 6);
+
+        // The vertices of the bend are on the edges of both segments, at the miter corners
+        for &position in &positions[2..4] {
+            assert!((distance_to_line(position, Vec3::ZERO, Vec3::X) - 0.1).abs() < 1e-5);
+            assert!((distance_to_line(position, Vec3::X, Vec3::Y) - 0.1).abs() < 1e-5);
+        }
+        assert!(positions[2].abs_diff_eq(Vec3::new(0.9, 0.1, 0.0), 1e-5));
+        assert!(positions[3].abs_diff_eq(Vec3::new(1.1, -0.1, 0.0), 1e-5));
+
+        let normals = mesh.attribute(Mesh::ATTRIBUTE_NORMAL).unwrap();
+        for normal in normals.as_float3().unwrap() {
+            assert_eq!(Vec3::from(*normal), Vec3::Z);
+        }
+    }
+}
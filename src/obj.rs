@@ -0,0 +1,223 @@
+use crate::polyline::Polyline;
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use std::fmt::Write;
+use thiserror::Error;
+
+/// Loads the line elements of Wavefront `.obj` files as [`Polyline`] assets.
+///
+/// Each `l` element is a strip going through the `v` vertices it refers to, and is joined to
+/// the previous one when it starts where that one ends, so files writing a line per segment
+/// still have joins. Vertex colors in sRGB after the positions, like `v x y z r g b`, are
+/// loaded as the [`colors`](Polyline::colors) of the polyline when every vertex of the lines
+/// has one. Faces and other elements are ignored.
+///
+/// When another loader also handles `.obj` files, such as a mesh loader, the asset type
+/// requested from the `AssetServer` selects this one.
+#[derive(Default)]
+pub struct ObjLoader;
+
+/// An error when loading an OBJ file.
+#[derive(Debug, Error)]
+pub enum ObjLoaderError {
+    #[error("could not read the OBJ file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid OBJ file at line {line}: {message}")]
+    Parse { line: usize, message: String },
+}
+
+impl AssetLoader for ObjLoader {
+    type Asset = Polyline;
+    type Settings = ();
+    type Error = ObjLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _: &(),
+        _: &mut LoadContext<'_>,
+    ) -> Result<Polyline, ObjLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Polyline::from_obj(&String::from_utf8_lossy(&bytes))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["obj"]
+    }
+}
+
+impl Polyline {
+    /// Reads the line elements of the text of an OBJ file, see [`ObjLoader`].
+    pub fn from_obj(text: &str) -> Result<Polyline, ObjLoaderError> {
+        let mut positions = Vec::new();
+        let mut vertex_colors = Vec::new();
+        let mut polyline = Polyline::default();
+        // Index of the last vertex of the last strip, to join the strips following it
+        let mut last = None;
+        let mut missing_colors = false;
+
+        let mut lines = text.lines().enumerate();
+        while let Some((index, line)) = lines.next() {
+            let error = |message: &str| ObjLoaderError::Parse {
+                line: index + 1,
+                message: message.into(),
+            };
+            // A backslash at the end of a line continues the statement on the next line
+            let mut statement = line.split('#').next().unwrap_or_default().to_owned();
+            while statement.trim_end().ends_with('\\') {
+                statement.truncate(statement.trim_end().len() - 1);
+                let Some((_, next)) = lines.next() else {
+                    break;
+                };
+                statement.push(' ');
+                statement.push_str(next.split('#').next().unwrap_or_default());
+            }
+
+            let mut words = statement.split_whitespace();
+            match words.next() {
+                Some("v") => {
+                    let values: Vec<f32> = words
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| error("expected numbers"))?;
+                    let color = match values[..] {
+                        [_, _, _] | [_, _, _, _] => None,
+                        [_, _, _, red, green, blue] => Some(Srgba::new(red, green, blue, 1.0)),
+                        [_, _, _, red, green, blue, alpha] => {
+                            Some(Srgba::new(red, green, blue, alpha))
+                        }
+                        _ => return Err(error("expected 3 coordinates and an optional color")),
+                    };
+                    positions.push(Vec3::new(values[0], values[1], values[2]));
+                    vertex_colors.push(color.map(LinearRgba::from));
+                }
+                Some("l") => {
+                    let indices = words
+                        .map(|word| {
+                            // Ignore the texture coordinates of `v/vt` references
+                            let vertex = word.split('/').next().unwrap_or_default();
+                            match vertex.parse::<isize>() {
+                                Ok(index @ 1..) => Some(index as usize - 1),
+                                Ok(index @ ..=-1) => positions.len().checked_add_signed(index),
+                                _ => None,
+                            }
+                            .filter(|&index| index < positions.len())
+                        })
+                        .collect::<Option<Vec<usize>>>()
+                        .ok_or_else(|| error("expected indices of existing vertices"))?;
+                    let Some((&first, rest)) = indices.split_first() else {
+                        continue;
+                    };
+                    let joined = last == Some(first);
+                    let strip = if joined { rest } else { &indices[..] };
+                    let vertices = strip.iter().map(|&index| positions[index]);
+                    if joined {
+                        polyline.vertices.extend(vertices);
+                    } else {
+                        polyline.push_strip(vertices);
+                    }
+                    missing_colors |= strip.iter().any(|&index| vertex_colors[index].is_none());
+                    polyline.colors.extend(
+                        strip
+                            .iter()
+                            .map(|&index| vertex_colors[index].unwrap_or_default()),
+                    );
+                    last = indices.last().copied();
+                }
+                _ => {}
+            }
+        }
+
+        // Only keep the colors if every vertex of the lines has one
+        if missing_colors {
+            polyline.colors.clear();
+        }
+        Ok(polyline)
+    }
+
+    /// Writes the polyline as the text of an OBJ file, with an `l` element per strip.
+    ///
+    /// The [`colors`](Self::colors) are written in sRGB after the positions of the vertices,
+    /// without their alpha. Strips of a single vertex are left out, as OBJ lines need two.
+    pub fn to_obj(&self) -> String {
+        let has_colors = self.colors.len() == self.vertices.len() && !self.colors.is_empty();
+        let mut obj = String::new();
+        for (index, vertex) in self.vertices.iter().enumerate() {
+            let _ = write!(obj, "v {} {} {}", vertex.x, vertex.y, vertex.z);
+            if has_colors {
+                let color = Srgba::from(self.colors[index]);
+                let _ = write!(obj, " {} {} {}", color.red, color.green, color.blue);
+            }
+            obj.push('\n');
+        }
+        for strip in self.strips().filter(|strip| strip.len() > 1) {
+            obj.push('l');
+            for index in strip {
+                let _ = write!(obj, " {}", index + 1);
+            }
+            obj.push('\n');
+        }
+        obj
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_keeps_strips_and_colors() {
+        let mut polyline = Polyline::from_strips([
+            vec![Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0)],
+            vec![Vec3::Z, Vec3::new(0.0, 1.0, 1.0)],
+        ]);
+        polyline.colors = vec![
+            LinearRgba::RED,
+            LinearRgba::GREEN,
+            LinearRgba::BLUE,
+            LinearRgba::WHITE,
+            LinearRgba::BLACK,
+        ];
+
+        let loaded = Polyline::from_obj(&polyline.to_obj()).unwrap();
+        assert_eq!(loaded.vertices, polyline.vertices);
+        assert_eq!(loaded.breaks, polyline.breaks);
+        assert_eq!(loaded.colors, polyline.colors);
+    }
+
+    #[test]
+    fn lines_per_segment_are_joined() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 2 0 0\nv 0 1 0\nv 0 2 0\nl 1 2\nl 2 3\nl 4 5\n";
+        let polyline = Polyline::from_obj(obj).unwrap();
+        assert_eq!(polyline.vertices.len(), 5);
+        assert_eq!(polyline.strips().collect::<Vec<_>>(), [0..3, 3..5]);
+    }
+
+    #[test]
+    fn negative_indices_are_relative_to_the_last_vertex() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 2 0 0\nl -1 -3\n";
+        let polyline = Polyline::from_obj(obj).unwrap();
+        assert_eq!(polyline.vertices, [Vec3::new(2.0, 0.0, 0.0), Vec3::ZERO]);
+    }
+
+    #[test]
+    fn out_of_range_indices_are_errors() {
+        for obj in ["v 0 0 0\nl 1 2\n", "v 0 0 0\nl 1 0\n", "v 0 0 0\nl 1 -2\n"] {
+            assert!(matches!(
+                Polyline::from_obj(obj),
+                Err(ObjLoaderError::Parse { line: 2, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn colors_are_dropped_when_a_vertex_has_none() {
+        let obj = "v 0 0 0 1 0 0\nv 1 0 0\nl 1 2\n";
+        let polyline = Polyline::from_obj(obj).unwrap();
+        assert_eq!(polyline.vertices.len(), 2);
+        assert!(polyline.colors.is_empty());
+    }
+}
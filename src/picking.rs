@@ -0,0 +1,359 @@
+use crate::{
+    gizmos::PolylineGizmo,
+    material::{PolylineMaterial, PolylineMaterialHandle},
+    polyline::{Polyline, PolylineHandle},
+};
+use bevy::{
+    picking::{
+        backend::{ray::RayMap, HitData, PointerHits},
+        pointer::PointerId,
+        PickSet, Pickable,
+    },
+    platform::collections::HashMap,
+    prelude::*,
+    render::view::RenderLayers,
+};
+
+/// Adds a `bevy_picking` backend for polylines.
+///
+/// Pointers hit a polyline when they are over the quads drawn for it, using the same width
+/// math as the shader, plus [`PolylinePickingSettings::margin`]. The segment hit by each
+/// pointer is available in the [`PolylinePickingHits`] resource.
+#[derive(Clone, Default)]
+pub struct PolylinePickingPlugin;
+
+impl Plugin for PolylinePickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PolylinePickingSettings>()
+            .init_resource::<PolylinePickingHits>()
+            .add_systems(PreUpdate, update_hits.in_set(PickSet::Backend));
+    }
+}
+
+/// An optional component that marks cameras that should be used by the
+/// [`PolylinePickingPlugin`].
+///
+/// Only needed if [`PolylinePickingSettings::require_markers`] is set to `true`.
+#[derive(Debug, Clone, Default, Component)]
+pub struct PolylinePickingCamera;
+
+/// Runtime settings for the [`PolylinePickingPlugin`].
+#[derive(Resource)]
+pub struct PolylinePickingSettings {
+    /// When set to `true`, only cameras marked with [`PolylinePickingCamera`] will pick
+    /// polylines.
+    pub require_markers: bool,
+    /// Distance in pixels added on each side of the lines, to make thin lines easier to hit.
+    pub margin: f32,
+}
+
+impl Default for PolylinePickingSettings {
+    fn default() -> Self {
+        Self {
+            require_markers: false,
+            margin: 2.0,
+        }
+    }
+}
+
+/// Where a pointer hit a polyline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolylineHit {
+    /// Index of the segment that was hit, going from `vertices[segment]` to
+    /// `vertices[segment + 1]` of the [`Polyline`].
+    pub segment: usize,
+    /// Position of the hit along the segment, from 0 at its start to 1 at its end.
+    pub t: f32,
+    /// Point of the hit on the center line of the segment, in world space.
+    pub point: Vec3,
+    /// Distance from the camera to `point`.
+    pub depth: f32,
+}
+
+/// The polylines hit by each pointer during the last update.
+///
+/// This complements the [`HitData`] reported to `bevy_picking`, which can't hold the
+/// segment that was hit.
+#[derive(Resource, Default, Debug)]
+pub struct PolylinePickingHits {
+    hits: HashMap<(PointerId, Entity), PolylineHit>,
+}
+
+impl PolylinePickingHits {
+    /// Returns where `pointer` hit the polyline `entity`, if it did.
+    pub fn get(&self, pointer: PointerId, entity: Entity) -> Option<&PolylineHit> {
+        self.hits.get(&(pointer, entity))
+    }
+
+    /// Iterates over all the hits, with the pointer and polyline entity of each hit.
+    pub fn iter(&self) -> impl Iterator<Item = (PointerId, Entity, &PolylineHit)> {
+        self.hits
+            .iter()
+            .map(|((pointer, entity), hit)| (*pointer, *entity, hit))
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn update_hits(
+    settings: Res<PolylinePickingSettings>,
+    ray_map: Res<RayMap>,
+    picking_cameras: Query<(
+        &Camera,
+        &GlobalTransform,
+        Has<PolylinePickingCamera>,
+        Option<&RenderLayers>,
+    )>,
+    polylines: Query<
+        (
+            Entity,
+            &PolylineHandle,
+            &PolylineMaterialHandle,
+            &GlobalTransform,
+            &ViewVisibility,
+            Option<&RenderLayers>,
+            Option<&Pickable>,
+        ),
+        Without<PolylineGizmo>,
+    >,
+    polyline_assets: Res<Assets<Polyline>>,
+    materials: Res<Assets<PolylineMaterial>>,
+    mut hits: ResMut<PolylinePickingHits>,
+    mut output: EventWriter<PointerHits>,
+) {
+    hits.hits.clear();
+
+    for (&ray_id, &ray) in ray_map.iter() {
+        let Ok((camera, camera_transform, camera_can_pick, camera_layers)) =
+            picking_cameras.get(ray_id.camera)
+        else {
+            continue;
+        };
+        if settings.require_markers && !camera_can_pick {
+            continue;
+        }
+        let (Some(viewport_size), Some(pointer_ndc)) = (
+            camera.physical_viewport_size(),
+            camera.world_to_ndc(camera_transform, ray.origin),
+        ) else {
+            continue;
+        };
+        let viewport_size = viewport_size.as_vec2();
+        let pointer = viewport_size * (0.5 * pointer_ndc.truncate() + 0.5);
+        let clip_from_world = camera.clip_from_view() * camera_transform.compute_matrix().inverse();
+        let camera_layers = camera_layers.cloned().unwrap_or_default();
+
+        let mut picks = Vec::new();
+        for (entity, polyline_handle, material_handle, transform, visibility, layers, pickable) in
+            &polylines
+        {
+            if !visibility.get()
+                || !camera_layers.intersects(&layers.cloned().unwrap_or_default())
+                || pickable.is_some_and(|pickable| !pickable.is_hoverable)
+            {
+                continue;
+            }
+            let (Some(polyline), Some(material)) = (
+                polyline_assets.get(&polyline_handle.0),
+                materials.get(&material_handle.0),
+            ) else {
+                continue;
+            };
+
+            let clip_from_local = clip_from_world * transform.compute_matrix();
+            let world_from_local = transform.affine();
+            let has_widths = polyline.widths.len() == polyline.vertices.len();
+            let hit = polyline
+                .segments()
+                .filter_map(|(segment, start, end)| {
+                    let width_scales = if has_widths {
+                        [polyline.widths[segment], polyline.widths[segment + 1]]
+                    } else {
+                        [1.0; 2]
+                    };
+                    let (t, distance) = pick_segment(
+                        clip_from_local * start.extend(1.0),
+                        clip_from_local * end.extend(1.0),
+                        width_scales,
+                        pointer,
+                        viewport_size,
+                        material,
+                        settings.margin,
+                    )?;
+                    let point = world_from_local.transform_point3(start.lerp(end, t));
+                    let hit = PolylineHit {
+                        segment,
+                        t,
+                        point,
+                        depth: point.distance(ray.origin),
+                    };
+                    Some((hit, distance))
+                })
+                // Prefer the segment closest to the camera, then the one closest to the pointer
+                .min_by(|(a, a_distance), (b, b_distance)| {
+                    a.depth
+                        .total_cmp(&b.depth)
+                        .then(a_distance.total_cmp(b_distance))
+                });
+            if let Some((hit, _)) = hit {
+                picks.push((
+                    entity,
+                    HitData::new(
+                        ray_id.camera,
+                        hit.depth,
+                        Some(hit.point),
+                        Some(-*ray.direction),
+                    ),
+                ));
+                hits.hits.insert((ray_id.pointer, entity), hit);
+            }
+        }
+
+        if picks.is_empty() {
+            continue;
+        }
+        // Like the mesh picking backend, stop at the first hit blocking the ones below it
+        picks.sort_by(|(_, a), (_, b)| a.depth.total_cmp(&b.depth));
+        if let Some(blocking) = picks.iter().position(|(entity, _)| {
+            polylines
+                .get(*entity)
+                .is_ok_and(|(.., pickable)| pickable.is_none_or(|p| p.should_block_lower))
+        }) {
+            for (entity, _) in picks.drain(blocking + 1..) {
+                hits.hits.remove(&(ray_id.pointer, entity));
+            }
+        }
+        output.write(PointerHits::new(ray_id.pointer, picks, camera.order as f32));
+    }
+}
+
+/// Tests the pointer against the quad drawn for the segment from `clip0` to `clip1`, like
+/// `polyline.wgsl` does.
+///
+/// `width_scales` are the per-vertex widths at both ends of the segment. Returns the position
+/// of the hit along the segment and the distance in pixels from the pointer to the center line.
+#[allow(clippy::too_many_arguments)]
+fn pick_segment(
+    clip0: Vec4,
+    clip1: Vec4,
+    width_scales: [f32; 2],
+    pointer: Vec2,
+    viewport_size: Vec2,
+    material: &PolylineMaterial,
+    margin: f32,
+) -> Option<(f32, f32)> {
+    // Segments fully behind the near plane are not drawn
+    if clip0.z > clip0.w && clip1.z > clip1.w {
+        return None;
+    }
+    let (clipped0, t0) = clip_near_plane(clip0, clip1);
+    let (clipped1, t1) = clip_near_plane(clip1, clip0);
+    let t1 = 1.0 - t1;
+
+    let screen0 = viewport_size * (0.5 * clipped0.xy() / clipped0.w + 0.5);
+    let screen1 = viewport_size * (0.5 * clipped1.xy() / clipped1.w + 0.5);
+    let segment = screen1 - screen0;
+    let length_squared = segment.length_squared();
+    if length_squared == 0.0 {
+        return None;
+    }
+    // Quads have no caps, so the pointer must be between the two ends
+    let s = (pointer - screen0).dot(segment) / length_squared;
+    if !(0.0..=1.0).contains(&s) {
+        return None;
+    }
+    let distance = pointer.distance(screen0 + s * segment);
+
+    // Lines thinner than a pixel are drawn a pixel wide and faded, but lines with no width are
+    // not drawn at all, like in the shader
+    let width = |clip: Vec4, scale: f32| {
+        let mut line_width = material.width * scale;
+        let mut outline_width = material.outline_width;
+        if material.perspective {
+            line_width /= clip.w;
+            outline_width /= clip.w;
+            if line_width > 0.0 && line_width < 1.0 {
+                line_width = 1.0;
+            }
+        }
+        line_width.max(0.0) + 2.0 * outline_width.max(0.0)
+    };
+    let half_width =
+        0.5 * (width(clipped0, width_scales[0]) * (1.0 - s) + width(clipped1, width_scales[1]) * s);
+    if half_width <= 0.0 || distance > half_width + margin {
+        return None;
+    }
+
+    // Screen space interpolation is linear in 1 / w
+    let u = s * clipped0.w / ((1.0 - s) * clipped1.w + s * clipped0.w);
+    Some((t0 + (t1 - t0) * u, distance))
+}
+
+/// Moves `a` onto the near plane if it is behind it and `b` is in front, like `polyline.wgsl`.
+///
+/// Also returns how far `a` was moved towards `b`.
+fn clip_near_plane(a: Vec4, b: Vec4) -> (Vec4, f32) {
+    if a.z > a.w && b.z <= b.w {
+        let distance_a = a.z - a.w;
+        let distance_b = b.z - b.w;
+        let t = distance_a / (distance_a - distance_b);
+        return (a + (b - a) * t, t);
+    }
+    (a, 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Picks a horizontal segment across the middle of a 100 pixels viewport, at `w` from the
+    /// camera, with the pointer `offset` pixels above its center.
+    fn pick(material: &PolylineMaterial, width_scales: [f32; 2], w: f32, offset: f32) -> bool {
+        pick_segment(
+            Vec4::new(-0.5 * w, 0.0, 0.5 * w, w),
+            Vec4::new(0.5 * w, 0.0, 0.5 * w, w),
+            width_scales,
+            Vec2::new(50.0, 50.0 + offset),
+            Vec2::splat(100.0),
+            material,
+            2.0,
+        )
+        .is_some()
+    }
+
+    #[test]
+    fn hits_within_the_width_and_margin() {
+        let material = PolylineMaterial::default();
+        assert!(pick(&material, [1.0, 1.0], 1.0, 6.9));
+        assert!(!pick(&material, [1.0, 1.0], 1.0, 7.1));
+        // Per-vertex widths change the width along the segment
+        assert!(pick(&material, [0.0, 2.0], 1.0, 6.9));
+        assert!(!pick(&material, [0.0, 1.0], 1.0, 6.9));
+    }
+
+    #[test]
+    fn thin_lines_are_hit_but_invisible_lines_are_not() {
+        let perspective = PolylineMaterial {
+            width: 1.0,
+            perspective: true,
+            ..default()
+        };
+        // Thinner than a pixel, the line is faded but still drawn a pixel wide
+        assert!(pick(&perspective, [1.0, 1.0], 10.0, 2.4));
+        assert!(!pick(&perspective, [1.0, 1.0], 10.0, 2.6));
+
+        // With no width, the shader draws nothing
+        assert!(!pick(&perspective, [0.0, 0.0], 10.0, 0.0));
+        let flat = PolylineMaterial {
+            width: 0.0,
+            ..default()
+        };
+        assert!(!pick(&flat, [1.0, 1.0], 1.0, 0.0));
+
+        // Unless the line has an outline
+        let outlined = PolylineMaterial {
+            outline_width: 1.0,
+            ..flat
+        };
+        assert!(pick(&outlined, [1.0, 1.0], 1.0, 0.0));
+    }
+}
@@ -0,0 +1,429 @@
+use crate::polyline::{edge_strips, Polyline};
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use std::fmt::Write;
+use thiserror::Error;
+
+/// Loads the edges of `.ply` files as [`Polyline`] assets.
+///
+/// The `vertex` element gives the positions with its `x`, `y` and `z` properties, and the
+/// `edge` element the segments with its `vertex1` and `vertex2` properties. The edges are
+/// joined into strips like with [`Polyline::from_edges`]. When the vertices have `red`,
+/// `green` and `blue` properties, and optionally `alpha`, they are loaded as the
+/// [`colors`](Polyline::colors) of the polyline, from 0 to 255 for integers and from 0 to 1
+/// for floats. ASCII and binary files are supported, and faces and other elements are
+/// ignored.
+#[derive(Default)]
+pub struct PlyLoader;
+
+/// An error when loading a PLY file.
+#[derive(Debug, Error)]
+pub enum PlyLoaderError {
+    #[error("could not read the PLY file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid PLY header: {0}")]
+    Header(String),
+    #[error("invalid PLY data in element `{element}`: {message}")]
+    Data { element: String, message: String },
+}
+
+impl AssetLoader for PlyLoader {
+    type Asset = Polyline;
+    type Settings = ();
+    type Error = PlyLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _: &(),
+        _: &mut LoadContext<'_>,
+    ) -> Result<Polyline, PlyLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Polyline::from_ply(&bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ply"]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => PlyType::I8,
+            "uchar" | "uint8" => PlyType::U8,
+            "short" | "int16" => PlyType::I16,
+            "ushort" | "uint16" => PlyType::U16,
+            "int" | "int32" => PlyType::I32,
+            "uint" | "uint32" => PlyType::U32,
+            "float" | "float32" => PlyType::F32,
+            "double" | "float64" => PlyType::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+
+    /// The value of a color channel at full intensity.
+    fn color_max(self) -> f64 {
+        match self {
+            PlyType::F32 | PlyType::F64 => 1.0,
+            PlyType::U16 => 65535.0,
+            _ => 255.0,
+        }
+    }
+}
+
+struct PlyProperty {
+    name: String,
+    kind: PlyType,
+    /// Type of the length of list properties.
+    list: Option<PlyType>,
+}
+
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+/// Reads the values of the body of a PLY file in order.
+struct PlyValues<'a> {
+    format: PlyFormat,
+    body: &'a [u8],
+    /// Position of the next value in the body.
+    offset: usize,
+}
+
+impl PlyValues<'_> {
+    fn next(&mut self, kind: PlyType) -> Option<f64> {
+        if self.format == PlyFormat::Ascii {
+            let start = self.offset
+                + self.body[self.offset..]
+                    .iter()
+                    .position(|byte| !byte.is_ascii_whitespace())?;
+            let end = self.body[start..]
+                .iter()
+                .position(u8::is_ascii_whitespace)
+                .map_or(self.body.len(), |length| start + length);
+            self.offset = end;
+            return std::str::from_utf8(&self.body[start..end])
+                .ok()?
+                .parse()
+                .ok();
+        }
+
+        let bytes = self.body.get(self.offset..self.offset + kind.size())?;
+        self.offset += kind.size();
+        let mut array = [0; 8];
+        array[..bytes.len()].copy_from_slice(bytes);
+        if self.format == PlyFormat::BinaryBigEndian {
+            array[..bytes.len()].reverse();
+        }
+        Some(match kind {
+            PlyType::I8 => array[0] as i8 as f64,
+            PlyType::U8 => array[0] as f64,
+            PlyType::I16 => i16::from_le_bytes([array[0], array[1]]) as f64,
+            PlyType::U16 => u16::from_le_bytes([array[0], array[1]]) as f64,
+            PlyType::I32 => i32::from_le_bytes(array[..4].try_into().unwrap()) as f64,
+            PlyType::U32 => u32::from_le_bytes(array[..4].try_into().unwrap()) as f64,
+            PlyType::F32 => f32::from_le_bytes(array[..4].try_into().unwrap()) as f64,
+            PlyType::F64 => f64::from_le_bytes(array),
+        })
+    }
+}
+
+impl Polyline {
+    /// Reads the edges of the content of a PLY file, see [`PlyLoader`].
+    pub fn from_ply(bytes: &[u8]) -> Result<Polyline, PlyLoaderError> {
+        let header_error = |message: &str| PlyLoaderError::Header(message.into());
+        // The header ends with an `end_header` line, and the body starts after its line ending
+        let mut line_start = 0;
+        let (header_end, body_start) = loop {
+            let line_end = bytes[line_start..]
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .map(|position| line_start + position);
+            let line = &bytes[line_start..line_end.unwrap_or(bytes.len())];
+            if line.trim_ascii() == b"end_header" {
+                break (line_start, line_end.map_or(bytes.len(), |end| end + 1));
+            }
+            match line_end {
+                Some(end) => line_start = end + 1,
+                None => return Err(header_error("missing `end_header`")),
+            }
+        };
+        let header = std::str::from_utf8(&bytes[..header_end])
+            .map_err(|_| header_error("the header is not text"))?;
+
+        let mut lines = header.lines().map(str::trim);
+        if lines.next() != Some("ply") {
+            return Err(header_error("missing `ply` magic number"));
+        }
+        let mut format = None;
+        let mut elements: Vec<PlyElement> = Vec::new();
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                ["format", name, _] => {
+                    format = Some(match name {
+                        "ascii" => PlyFormat::Ascii,
+                        "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                        "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                        _ => return Err(header_error("unknown format")),
+                    });
+                }
+                ["element", name, count] => elements.push(PlyElement {
+                    name: name.into(),
+                    count: count
+                        .parse()
+                        .map_err(|_| header_error("invalid element count"))?,
+                    properties: Vec::new(),
+                }),
+                ["property", "list", list, kind, name] => {
+                    elements
+                        .last_mut()
+                        .ok_or_else(|| header_error("property before any element"))?
+                        .properties
+                        .push(PlyProperty {
+                            name: name.into(),
+                            kind: PlyType::parse(kind)
+                                .ok_or_else(|| header_error("unknown property type"))?,
+                            list: Some(
+                                PlyType::parse(list)
+                                    .ok_or_else(|| header_error("unknown property type"))?,
+                            ),
+                        });
+                }
+                ["property", kind, name] => {
+                    elements
+                        .last_mut()
+                        .ok_or_else(|| header_error("property before any element"))?
+                        .properties
+                        .push(PlyProperty {
+                            name: name.into(),
+                            kind: PlyType::parse(kind)
+                                .ok_or_else(|| header_error("unknown property type"))?,
+                            list: None,
+                        });
+                }
+                ["comment", ..] | ["obj_info", ..] | [] => {}
+                _ => return Err(header_error(&format!("unexpected line `{line}`"))),
+            }
+        }
+        let mut values = PlyValues {
+            format: format.ok_or_else(|| header_error("missing format"))?,
+            body: &bytes[body_start..],
+            offset: 0,
+        };
+
+        let mut vertices = Vec::new();
+        let mut colors = Vec::new();
+        let mut edges = Vec::new();
+        for element in &elements {
+            let data_error = |message: &str| PlyLoaderError::Data {
+                element: element.name.clone(),
+                message: message.into(),
+            };
+            let property = |name: &str| {
+                element
+                    .properties
+                    .iter()
+                    .position(|property| property.name == name && property.list.is_none())
+            };
+            let (x, y, z) = (property("x"), property("y"), property("z"));
+            let (red, green, blue) = (property("red"), property("green"), property("blue"));
+            let alpha = property("alpha");
+            let (vertex1, vertex2) = (property("vertex1"), property("vertex2"));
+
+            let mut row = vec![0.0; element.properties.len()];
+            for _ in 0..element.count {
+                for (value, property) in row.iter_mut().zip(&element.properties) {
+                    let Some(list) = property.list else {
+                        *value = values
+                            .next(property.kind)
+                            .ok_or_else(|| data_error("missing or invalid value"))?;
+                        continue;
+                    };
+                    // Lists such as the indices of faces are skipped
+                    let length = values
+                        .next(list)
+                        .ok_or_else(|| data_error("missing or invalid list length"))?;
+                    for _ in 0..length as usize {
+                        values
+                            .next(property.kind)
+                            .ok_or_else(|| data_error("missing or invalid value"))?;
+                    }
+                }
+
+                match element.name.as_str() {
+                    "vertex" => {
+                        let [Some(x), Some(y), Some(z)] = [x, y, z] else {
+                            return Err(data_error("missing `x`, `y` or `z` property"));
+                        };
+                        vertices.push(Vec3::new(row[x] as f32, row[y] as f32, row[z] as f32));
+                        if let [Some(red), Some(green), Some(blue)] = [red, green, blue] {
+                            let channel = |index: usize| {
+                                (row[index] / element.properties[index].kind.color_max()) as f32
+                            };
+                            let color = Srgba::new(
+                                channel(red),
+                                channel(green),
+                                channel(blue),
+                                alpha.map_or(1.0, channel),
+                            );
+                            colors.push(LinearRgba::from(color));
+                        }
+                    }
+                    "edge" => {
+                        let [Some(vertex1), Some(vertex2)] = [vertex1, vertex2] else {
+                            return Err(data_error("missing `vertex1` or `vertex2` property"));
+                        };
+                        // Negative indices are out of range, and ignored like them
+                        let index = |value: f64| {
+                            if value >= 0.0 {
+                                value as usize
+                            } else {
+                                usize::MAX
+                            }
+                        };
+                        edges.push([index(row[vertex1]), index(row[vertex2])]);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let strips = edge_strips(vertices.len(), edges);
+        let mut polyline = Polyline::from_strips(
+            strips
+                .iter()
+                .map(|strip| strip.iter().map(|&index| vertices[index])),
+        );
+        if !colors.is_empty() {
+            polyline.colors = strips
+                .iter()
+                .flatten()
+                .map(|&index| colors[index])
+                .collect();
+        }
+        Ok(polyline)
+    }
+
+    /// Writes the polyline as an ASCII PLY file, with a `vertex` element for its vertices and
+    /// an `edge` element for its segments.
+    ///
+    /// The [`colors`](Self::colors) are written in sRGB as the `red`, `green`, `blue` and
+    /// `alpha` properties of the vertices, from 0 to 255.
+    pub fn to_ply(&self) -> String {
+        let has_colors = self.colors.len() == self.vertices.len() && !self.colors.is_empty();
+        let segments: Vec<usize> = self
+            .strips()
+            .flat_map(|strip| strip.start..strip.end.saturating_sub(1))
+            .collect();
+
+        let mut ply = String::from("ply\nformat ascii 1.0\n");
+        let _ = writeln!(ply, "element vertex {}", self.vertices.len());
+        ply.push_str("property float x\nproperty float y\nproperty float z\n");
+        if has_colors {
+            for channel in ["red", "green", "blue", "alpha"] {
+                let _ = writeln!(ply, "property uchar {channel}");
+            }
+        }
+        let _ = writeln!(ply, "element edge {}", segments.len());
+        ply.push_str("property int vertex1\nproperty int vertex2\nend_header\n");
+
+        for (index, vertex) in self.vertices.iter().enumerate() {
+            let _ = write!(ply, "{} {} {}", vertex.x, vertex.y, vertex.z);
+            if has_colors {
+                let [red, green, blue, alpha] = Srgba::from(self.colors[index]).to_u8_array();
+                let _ = write!(ply, " {red} {green} {blue} {alpha}");
+            }
+            ply.push('\n');
+        }
+        for index in segments {
+            let _ = writeln!(ply, "{} {}", index, index + 1);
+        }
+        ply
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_keeps_strips_and_colors() {
+        let mut polyline = Polyline::from_strips([
+            vec![Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0)],
+            vec![Vec3::Z, Vec3::new(0.0, 1.0, 1.0)],
+        ]);
+        polyline.colors = vec![
+            LinearRgba::RED,
+            LinearRgba::GREEN,
+            LinearRgba::BLUE,
+            LinearRgba::WHITE,
+            LinearRgba::BLACK,
+        ];
+
+        let loaded = Polyline::from_ply(polyline.to_ply().as_bytes()).unwrap();
+        assert_eq!(loaded.vertices, polyline.vertices);
+        assert_eq!(loaded.breaks, polyline.breaks);
+        assert_eq!(loaded.colors, polyline.colors);
+    }
+
+    #[test]
+    fn invalid_edges_are_ignored() {
+        let ply = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\n\
+            property float y\nproperty float z\nelement edge 4\nproperty int vertex1\n\
+            property int vertex2\nend_header\n0 0 0\n1 0 0\n2 0 0\n0 1\n1 -1\n1 3\n2 2\n";
+        let polyline = Polyline::from_ply(ply.as_bytes()).unwrap();
+        assert_eq!(polyline.vertices, [Vec3::ZERO, Vec3::X]);
+        assert!(polyline.breaks.is_empty());
+    }
+
+    #[test]
+    fn end_header_must_be_a_whole_line() {
+        let ply = "ply\nformat ascii 1.0\ncomment written before end_header\n\
+            element vertex 2\nproperty float x\nproperty float y\nproperty float z\n\
+            element edge 1\nproperty int vertex1\nproperty int vertex2\nend_header\n\
+            0 0 0\n1 0 0\n0 1\n";
+        let polyline = Polyline::from_ply(ply.as_bytes()).unwrap();
+        assert_eq!(polyline.vertices, [Vec3::ZERO, Vec3::X]);
+
+        let ply = "ply\nformat ascii 1.0\ncomment no end_header here\n";
+        assert!(matches!(
+            Polyline::from_ply(ply.as_bytes()),
+            Err(PlyLoaderError::Header(_))
+        ));
+    }
+}
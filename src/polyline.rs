@@ -1,22 +1,37 @@
-use crate::material::PolylineMaterialHandle;
+use crate::{
+    gizmos::ExtractedPolylineGizmos,
+    material::{
+        GpuPolylineMaterial, PolylineMaterial, PolylineMaterialHandle, MARKER_MAX_VERTICES,
+    },
+    scene::{deserialize_handle, load_scene_handle, serialize_handle},
+    simplify::{rdp_kept_vertices, retain},
+};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
 use bevy::{
+    core_pipeline::{
+        core_3d::CORE_3D_DEPTH_FORMAT,
+        prepass::{prepass_target_descriptors, PreviousViewData, PreviousViewUniforms},
+    },
     ecs::{
+        entity::EntityHashMap,
         query::ROQueryItem,
         system::{
             lifetimeless::{Read, SRes},
             SystemParamItem,
         },
     },
+    math::bounding::{BoundingSphere, BoundingVolume},
     prelude::*,
-    reflect::TypePath,
     render::{
         extract_component::{ComponentUniforms, DynamicUniformIndex, UniformComponentPlugin},
         render_asset::{PrepareAssetError, RenderAsset, RenderAssetPlugin, RenderAssets},
         render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
         render_resource::{binding_types::uniform_buffer, *},
         renderer::RenderDevice,
-        sync_world::{RenderEntity, SyncToRenderWorld},
-        view::{self, ViewUniform, ViewUniforms, VisibilityClass},
+        sync_world::{MainEntity, RenderEntity, SyncToRenderWorld},
+        view::{self, ExtractedView, ViewUniform, ViewUniforms, VisibilityClass},
         Extract, Render, RenderApp, RenderSet,
     },
 };
@@ -26,6 +41,8 @@ pub struct PolylineBasePlugin;
 impl Plugin for PolylineBasePlugin {
     fn build(&self, app: &mut App) {
         app.init_asset::<Polyline>()
+            .register_asset_reflect::<Polyline>()
+            .register_type::<PolylineHandle>()
             .add_plugins(RenderAssetPlugin::<GpuPolyline>::default());
     }
 }
@@ -39,6 +56,7 @@ impl Plugin for PolylineRenderPlugin {
     fn finish(&self, app: &mut App) {
         app.sub_app_mut(RenderApp)
             .init_resource::<PolylinePipeline>()
+            .init_resource::<ExtractedPolylineShadowCasters>()
             .add_systems(ExtractSchedule, extract_polylines)
             .add_systems(
                 Render,
@@ -50,6 +68,10 @@ impl Plugin for PolylineRenderPlugin {
     }
 }
 
+/// Components to spawn a polyline.
+///
+/// Like meshes, polylines can be hidden from some cameras by adding a
+/// [`RenderLayers`](bevy::render::view::RenderLayers) component next to this bundle.
 #[derive(Bundle, Default)]
 pub struct PolylineBundle {
     pub polyline: PolylineHandle,
@@ -63,36 +85,324 @@ pub struct PolylineBundle {
     pub view_visibility: ViewVisibility,
 }
 
-#[derive(Debug, Default, Asset, Clone, TypePath)]
+#[derive(Debug, Default, Asset, Clone, PartialEq, Reflect)]
+#[reflect(Default, Debug)]
 pub struct Polyline {
     pub vertices: Vec<Vec3>,
+    /// Optional per-vertex scalar values, e.g. grade, depth or speed.
+    ///
+    /// When not empty, this must have the same length as `vertices`. The values are
+    /// mapped to a color through the [`PolylineMaterial::colormap`](crate::material::PolylineMaterial::colormap)
+    /// of the material, and are ignored when the material has no colormap.
+    pub scalars: Vec<f32>,
+    /// Optional per-vertex colors, multiplied with the color of the material.
+    ///
+    /// When not empty, this must have the same length as `vertices`. The colors are
+    /// interpolated along the segments.
+    pub colors: Vec<LinearRgba>,
+    /// Optional per-vertex widths, multiplied with the
+    /// [`PolylineMaterial::width`](crate::material::PolylineMaterial::width) of the material.
+    ///
+    /// When not empty, this must have the same length as `vertices`. The widths are
+    /// interpolated along the segments, which tapers the line between vertices.
+    pub widths: Vec<f32>,
+    /// Indices of the vertices starting a new strip, strictly increasing and between 1 and the
+    /// number of vertices excluded.
+    ///
+    /// Consecutive strips are not connected to each other, which lets a single polyline hold
+    /// disjoint lines such as the edges of a box. A polyline without breaks is a single strip.
+    /// [`push_strip`](Self::push_strip) and [`from_strips`](Self::from_strips) keep the breaks
+    /// valid, other breaks are ignored when iterating over the [`strips`](Self::strips) and
+    /// when drawing.
+    pub breaks: Vec<usize>,
+}
+
+impl Polyline {
+    /// Creates a polyline made of several disjoint strips.
+    pub fn from_strips<S: IntoIterator<Item = Vec3>>(strips: impl IntoIterator<Item = S>) -> Self {
+        let mut polyline = Polyline::default();
+        for strip in strips {
+            polyline.push_strip(strip);
+        }
+        polyline
+    }
+
+    /// Adds a strip after the existing ones, without connecting it to them.
+    ///
+    /// This doesn't add [`scalars`](Self::scalars), [`colors`](Self::colors) nor
+    /// [`widths`](Self::widths) for the new vertices.
+    pub fn push_strip(&mut self, vertices: impl IntoIterator<Item = Vec3>) {
+        let start = self.vertices.len();
+        self.vertices.extend(vertices);
+        if start > 0 && self.vertices.len() > start {
+            self.breaks.push(start);
+        }
+    }
+
+    /// Creates a polyline from a list of edges between `vertices`, such as the edges of a mesh.
+    ///
+    /// Edges sharing a vertex are joined into strips greedily, so the polyline has few strips
+    /// and joins are drawn where edges meet. Edges referring to vertices out of range are
+    /// ignored.
+    pub fn from_edges(vertices: &[Vec3], edges: impl IntoIterator<Item = [usize; 2]>) -> Self {
+        Polyline::from_strips(
+            edge_strips(vertices.len(), edges)
+                .into_iter()
+                .map(|strip| strip.into_iter().map(|index| vertices[index])),
+        )
+    }
+
+    /// Whether the [`breaks`](Self::breaks) are strictly increasing and inside the vertices.
+    pub fn breaks_are_valid(&self) -> bool {
+        self.breaks.windows(2).all(|pair| pair[0] < pair[1])
+            && self
+                .breaks
+                .iter()
+                .all(|&index| index > 0 && index < self.vertices.len())
+    }
+
+    /// Iterates over the ranges of vertices of each strip, without empty strips.
+    ///
+    /// Breaks that aren't after the previous one or inside the vertices are ignored, see
+    /// [`breaks_are_valid`](Self::breaks_are_valid).
+    pub fn strips(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        let len = self.vertices.len();
+        let mut starts = vec![0];
+        for &index in &self.breaks {
+            if index > starts[starts.len() - 1] && index < len {
+                starts.push(index);
+            }
+        }
+        let ends: Vec<usize> = starts[1..].iter().copied().chain([len]).collect();
+        starts
+            .into_iter()
+            .zip(ends)
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| start..end)
+    }
+
+    /// Iterates over the segments of all the strips, with the index of their first vertex.
+    pub fn segments(&self) -> impl Iterator<Item = (usize, Vec3, Vec3)> + '_ {
+        self.strips().flat_map(|strip| {
+            (strip.start..strip.end - 1)
+                .map(|index| (index, self.vertices[index], self.vertices[index + 1]))
+        })
+    }
 }
 
-#[derive(Debug, Clone, Default, Component)]
+/// Joins edges between `vertex_count` vertices into strips of vertex indices.
+///
+/// Strips start from the vertices with an odd number of edges first, as those must be the end
+/// of a strip, so that simple paths of edges become a single strip.
+pub(crate) fn edge_strips(
+    vertex_count: usize,
+    edges: impl IntoIterator<Item = [usize; 2]>,
+) -> Vec<Vec<usize>> {
+    let edges: Vec<[usize; 2]> = edges
+        .into_iter()
+        .filter(|&[a, b]| a != b && a < vertex_count && b < vertex_count)
+        .collect();
+    let mut adjacency = vec![Vec::new(); vertex_count];
+    for (edge, &[a, b]) in edges.iter().enumerate() {
+        adjacency[a].push(edge);
+        adjacency[b].push(edge);
+    }
+    let odd = (0..vertex_count).filter(|&vertex| adjacency[vertex].len() % 2 == 1);
+    let starts: Vec<usize> = odd.chain(0..vertex_count).collect();
+
+    let mut used = vec![false; edges.len()];
+    let mut strips = Vec::new();
+    for start in starts {
+        loop {
+            let mut strip = vec![start];
+            let mut vertex = start;
+            // Follow unused edges until reaching a vertex without any
+            while let Some(edge) = adjacency[vertex].pop() {
+                if used[edge] {
+                    continue;
+                }
+                used[edge] = true;
+                let [a, b] = edges[edge];
+                vertex = if a == vertex { b } else { a };
+                strip.push(vertex);
+            }
+            if strip.len() < 2 {
+                break;
+            }
+            strips.push(strip);
+        }
+    }
+    strips
+}
+
+/// The [`Polyline`] drawn by an entity.
+///
+/// In a `DynamicScene`, handles to loaded polylines are saved by asset path and loaded again when
+/// the scene is spawned, weak handles by their fixed `Uuid`. Polylines added at runtime have
+/// neither and are saved as the default handle.
+#[derive(Debug, Clone, Default, Component, Reflect, Serialize, Deserialize)]
+#[reflect(opaque)]
+#[reflect(Component, Default, Debug, Serialize, Deserialize)]
 #[require(SyncToRenderWorld, VisibilityClass)]
-#[component(on_add = view::add_visibility_class::<PolylineHandle>)]
-pub struct PolylineHandle(pub Handle<Polyline>);
+#[component(
+    on_add = view::add_visibility_class::<PolylineHandle>,
+    on_insert = load_scene_handle::<PolylineHandle>,
+)]
+#[serde(transparent)]
+pub struct PolylineHandle(
+    #[serde(
+        serialize_with = "serialize_handle",
+        deserialize_with = "deserialize_handle"
+    )]
+    pub Handle<Polyline>,
+);
 
 impl RenderAsset for GpuPolyline {
     type SourceAsset = Polyline;
 
-    type Param = SRes<RenderDevice>;
+    type Param = (SRes<RenderDevice>, Option<SRes<ExtractedPolylineGizmos>>);
 
     fn prepare_asset(
         polyline: Self::SourceAsset,
-        _: AssetId<Self::SourceAsset>,
-        render_device: &mut bevy::ecs::system::SystemParamItem<Self::Param>,
+        id: AssetId<Self::SourceAsset>,
+        (render_device, gizmos): &mut bevy::ecs::system::SystemParamItem<Self::Param>,
     ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
-        let vertex_buffer_data = bytemuck::cast_slice(polyline.vertices.as_slice());
+        let has_scalars =
+            polyline.scalars.len() == polyline.vertices.len() && !polyline.scalars.is_empty();
+        let mut vertices = polyline.vertices.clone();
+        let mut scalars = if has_scalars {
+            polyline.scalars.clone()
+        } else {
+            Vec::new()
+        };
+        let has_colors =
+            polyline.colors.len() == polyline.vertices.len() && !polyline.colors.is_empty();
+        let mut colors = if has_colors {
+            polyline.colors.clone()
+        } else {
+            Vec::new()
+        };
+        let has_widths =
+            polyline.widths.len() == polyline.vertices.len() && !polyline.widths.is_empty();
+        let mut widths = if has_widths {
+            polyline.widths.clone()
+        } else {
+            Vec::new()
+        };
+
+        // The levels of detail are stored after the full resolution vertices
+        let bounding_sphere = BoundingSphere::from_point_cloud(
+            Isometry3d::IDENTITY,
+            if polyline.vertices.is_empty() {
+                &[Vec3::ZERO]
+            } else {
+                &polyline.vertices
+            },
+        );
+        // Strips with a single vertex have no segment to draw
+        let strips: Vec<Range<u32>> = polyline
+            .strips()
+            .filter(|strip| strip.len() > 1)
+            .map(|strip| strip.start as u32..strip.end as u32)
+            .collect();
+        let mut lods = Vec::new();
+        // Gizmos change every frame, simplifying them would cost more than drawing them
+        let is_gizmo = gizmos.as_ref().is_some_and(|gizmos| gizmos.contains(&id));
+        if polyline.vertices.len() >= LOD_MIN_VERTEX_COUNT && !is_gizmo {
+            let mut level_vertices = polyline.vertices.clone();
+            let mut level_scalars = scalars.clone();
+            let mut level_colors = colors.clone();
+            let mut level_widths = widths.clone();
+            let mut level_strips = strips.clone();
+            let mut previous_count = level_vertices.len();
+            let mut tolerance = bounding_sphere.radius() / 2048.0;
+            let mut error = 0.0;
+            while level_vertices.len() > 2 * level_strips.len() && lods.len() < LOD_MAX_LEVELS {
+                // Each level is simplified from the previous one, so the errors add up
+                let (mut next_vertices, mut next_scalars, mut next_colors, mut next_widths) =
+                    (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+                for strip in &mut level_strips {
+                    let range = strip.start as usize..strip.end as usize;
+                    let kept = rdp_kept_vertices(&level_vertices[range.clone()], tolerance);
+                    let start = next_vertices.len() as u32;
+                    next_vertices.extend(retain(&level_vertices[range.clone()], &kept));
+                    if has_scalars {
+                        next_scalars.extend(retain(&level_scalars[range.clone()], &kept));
+                    }
+                    if has_colors {
+                        next_colors.extend(retain(&level_colors[range.clone()], &kept));
+                    }
+                    if has_widths {
+                        next_widths.extend(retain(&level_widths[range], &kept));
+                    }
+                    *strip = start..next_vertices.len() as u32;
+                }
+                level_vertices = next_vertices;
+                level_scalars = next_scalars;
+                level_colors = next_colors;
+                level_widths = next_widths;
+                error += tolerance;
+                tolerance *= 2.0;
+
+                // Only keep the levels that remove a significant number of segments
+                if level_vertices.len() * 4 > previous_count * 3 {
+                    continue;
+                }
+                previous_count = level_vertices.len();
+                let offset = vertices.len() as u32;
+                lods.push(PolylineLod {
+                    tolerance: error,
+                    strips: level_strips
+                        .iter()
+                        .map(|strip| strip.start + offset..strip.end + offset)
+                        .collect(),
+                });
+                vertices.extend_from_slice(&level_vertices);
+                scalars.extend_from_slice(&level_scalars);
+                colors.extend_from_slice(&level_colors);
+                widths.extend_from_slice(&level_widths);
+            }
+        }
+
         let vertex_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
             usage: BufferUsages::VERTEX,
             label: Some("Polyline Vertex Buffer"),
-            contents: vertex_buffer_data,
+            contents: bytemuck::cast_slice(vertices.as_slice()),
+        });
+
+        let scalar_buffer = has_scalars.then(|| {
+            render_device.create_buffer_with_data(&BufferInitDescriptor {
+                usage: BufferUsages::VERTEX,
+                label: Some("Polyline Scalar Buffer"),
+                contents: bytemuck::cast_slice(scalars.as_slice()),
+            })
+        });
+
+        let color_buffer = has_colors.then(|| {
+            render_device.create_buffer_with_data(&BufferInitDescriptor {
+                usage: BufferUsages::VERTEX,
+                label: Some("Polyline Color Buffer"),
+                contents: bytemuck::cast_slice(colors.as_slice()),
+            })
+        });
+
+        let width_buffer = has_widths.then(|| {
+            render_device.create_buffer_with_data(&BufferInitDescriptor {
+                usage: BufferUsages::VERTEX,
+                label: Some("Polyline Width Buffer"),
+                contents: bytemuck::cast_slice(widths.as_slice()),
+            })
         });
 
         Ok(GpuPolyline {
             vertex_buffer,
+            scalar_buffer,
+            color_buffer,
+            width_buffer,
             vertex_count: polyline.vertices.len() as u32,
+            strips,
+            lods,
+            bounding_sphere,
         })
     }
 }
@@ -100,49 +410,164 @@ impl RenderAsset for GpuPolyline {
 #[derive(Component, Clone, ShaderType)]
 pub struct PolylineUniform {
     pub transform: Mat4,
+    /// The transform of the previous frame, used to compute motion vectors.
+    pub previous_transform: Mat4,
 }
 
 /// The GPU-representation of a [`Polyline`]
 #[derive(Debug, Clone)]
 pub struct GpuPolyline {
     pub vertex_buffer: Buffer,
+    /// Per-vertex scalars, only present if [`Polyline::scalars`] matches the vertex count.
+    pub scalar_buffer: Option<Buffer>,
+    /// Per-vertex colors, only present if [`Polyline::colors`] matches the vertex count.
+    pub color_buffer: Option<Buffer>,
+    /// Per-vertex widths, only present if [`Polyline::widths`] matches the vertex count.
+    pub width_buffer: Option<Buffer>,
+    /// Number of vertices at full resolution.
     pub vertex_count: u32,
+    /// Ranges of vertices of the strips at full resolution, without the single vertex ones.
+    pub strips: Vec<Range<u32>>,
+    /// Simplified versions of long polylines, from the most to the least detailed.
+    ///
+    /// Their vertices and per-vertex values are stored after the full resolution ones in the
+    /// buffers.
+    pub lods: Vec<PolylineLod>,
+    /// Bounds of the vertices in local space, used to select the level of detail.
+    pub bounding_sphere: BoundingSphere,
 }
 
+/// Polylines with fewer vertices are always drawn at full resolution.
+const LOD_MIN_VERTEX_COUNT: usize = 1024;
+const LOD_MAX_LEVELS: usize = 16;
+
+/// A simplified version of a [`GpuPolyline`].
+#[derive(Debug, Clone)]
+pub struct PolylineLod {
+    /// Maximum distance from the full resolution polyline, in local space.
+    pub tolerance: f32,
+    /// Ranges of vertices of the simplified strips in the buffers.
+    pub strips: Vec<Range<u32>>,
+}
+
+impl GpuPolyline {
+    /// The pipeline key bits for the buffers of the polyline changing its shape, used by every
+    /// pass.
+    pub fn geometry_key(&self) -> PolylinePipelineKey {
+        let mut key = PolylinePipelineKey::NONE;
+        if self.width_buffer.is_some() {
+            key |= PolylinePipelineKey::WIDTHS
+        }
+        key
+    }
+
+    /// Returns the ranges of vertices of the strips of the least detailed version of the
+    /// polyline whose error is below `pixel_tolerance` pixels in `view`.
+    pub fn select_lod(
+        &self,
+        view: &ExtractedView,
+        world_from_local: &Mat4,
+        pixel_tolerance: f32,
+    ) -> &[Range<u32>] {
+        if self.lods.is_empty() || pixel_tolerance <= 0.0 || view.viewport.w == 0 {
+            return &self.strips;
+        }
+        let scale = world_from_local
+            .to_scale_rotation_translation()
+            .0
+            .max_element();
+        let center = world_from_local.transform_point3(self.bounding_sphere.center().into());
+        let radius = scale * self.bounding_sphere.radius();
+
+        // World size of a pixel at the closest point of the polyline. Orthographic projections
+        // have a w of 1 everywhere, perspective ones have a w of the distance along the view.
+        let w = if view.clip_from_view.w_axis.w == 1.0 {
+            1.0
+        } else {
+            (view.world_from_view.translation().distance(center) - radius).max(0.0)
+        };
+        let pixel_size = 2.0 * w / (view.clip_from_view.y_axis.y * view.viewport.w as f32);
+        let tolerance = pixel_tolerance * pixel_size / scale;
+
+        self.lods
+            .iter()
+            .rev()
+            .find(|lod| lod.tolerance <= tolerance)
+            .map_or(&self.strips, |lod| &lod.strips)
+    }
+}
+
+/// The extracted polylines whose material casts shadows, including the ones outside of the
+/// cameras' view, which can still cast shadows into it.
+#[derive(Resource, Default)]
+pub struct ExtractedPolylineShadowCasters(pub Vec<(Entity, MainEntity)>);
+
+#[allow(clippy::type_complexity)]
 pub fn extract_polylines(
     mut commands: Commands,
     mut previous_len: Local<usize>,
+    mut previous_transforms: Local<EntityHashMap<Mat4>>,
+    mut transforms: Local<EntityHashMap<Mat4>>,
+    mut shadow_casters: ResMut<ExtractedPolylineShadowCasters>,
     query: Extract<
         Query<(
+            Entity,
             RenderEntity,
             &InheritedVisibility,
             &ViewVisibility,
             &GlobalTransform,
             &PolylineHandle,
+            Option<&PolylineMaterialHandle>,
         )>,
     >,
+    materials: Extract<Res<Assets<PolylineMaterial>>>,
 ) {
     let mut values = Vec::with_capacity(*previous_len);
-    for (entity, inherited_visibility, view_visibility, transform, handle) in query.iter() {
-        if !inherited_visibility.get() || !view_visibility.get() {
+    transforms.clear();
+    shadow_casters.0.clear();
+    for (main_entity, entity, inherited_visibility, view_visibility, transform, handle, material) in
+        query.iter()
+    {
+        let cast_shadows = material
+            .and_then(|material| materials.get(&material.0))
+            .is_some_and(|material| material.cast_shadows);
+        if !inherited_visibility.get() || !(view_visibility.get() || cast_shadows) {
             continue;
         }
+        if cast_shadows {
+            shadow_casters.0.push((entity, main_entity.into()));
+        }
         let transform = transform.compute_matrix();
+        // Polylines that weren't rendered last frame have no motion
+        let previous_transform = previous_transforms
+            .get(&main_entity)
+            .copied()
+            .unwrap_or(transform);
+        transforms.insert(main_entity, transform);
         values.push((
             entity,
             (
                 PolylineHandle(handle.0.clone_weak()),
-                PolylineUniform { transform },
+                PolylineUniform {
+                    transform,
+                    previous_transform,
+                },
             ),
         ));
     }
     *previous_len = values.len();
+    std::mem::swap(&mut *previous_transforms, &mut *transforms);
     commands.try_insert_batch(values);
 }
 
 #[derive(Clone, Resource)]
 pub struct PolylinePipeline {
     pub view_layout: BindGroupLayout,
+    /// View layout with the previous frame's view, used by the motion vector prepass.
+    pub view_motion_vectors_layout: BindGroupLayout,
+    /// View layout with the view's distance fog, used by materials with `fog` enabled.
+    #[cfg(feature = "pbr")]
+    pub view_fog_layout: BindGroupLayout,
     pub polyline_layout: BindGroupLayout,
     pub shader: Handle<Shader>,
 }
@@ -158,6 +583,29 @@ impl FromWorld for PolylinePipeline {
             ),
         );
 
+        let view_motion_vectors_layout = render_device.create_bind_group_layout(
+            "polyline_view_motion_vectors_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::VERTEX,
+                (
+                    uniform_buffer::<ViewUniform>(true),
+                    uniform_buffer::<PreviousViewData>(true),
+                ),
+            ),
+        );
+
+        #[cfg(feature = "pbr")]
+        let view_fog_layout = render_device.create_bind_group_layout(
+            "polyline_view_fog_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::VERTEX_FRAGMENT,
+                (
+                    uniform_buffer::<ViewUniform>(true),
+                    uniform_buffer::<bevy::pbr::GpuFog>(true),
+                ),
+            ),
+        );
+
         let polyline_layout = render_device.create_bind_group_layout(
             "polyline_layout",
             &BindGroupLayoutEntries::single(
@@ -168,6 +616,9 @@ impl FromWorld for PolylinePipeline {
 
         PolylinePipeline {
             view_layout,
+            view_motion_vectors_layout,
+            #[cfg(feature = "pbr")]
+            view_fog_layout,
             polyline_layout,
             shader: crate::SHADER_HANDLE,
         }
@@ -178,35 +629,85 @@ impl SpecializedRenderPipeline for PolylinePipeline {
     type Key = PolylinePipelineKey;
 
     fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
-        let shader_defs = Vec::new();
-        let (label, blend, depth_write_enabled);
-
-        if key.contains(PolylinePipelineKey::TRANSPARENT_MAIN_PASS) {
-            label = "transparent_polyline_pipeline".into();
-            blend = Some(BlendState::ALPHA_BLENDING);
-            // For the transparent pass, fragments that are closer will be alpha blended
-            // but their depth is not written to the depth buffer
-            depth_write_enabled = false;
-        } else if key.contains(PolylinePipelineKey::PERSPECTIVE) {
-            // We need to use transparent pass with perspective to support thin line fading.
-            label = "transparent_polyline_pipeline".into();
-            blend = Some(BlendState::ALPHA_BLENDING);
-            // Because we are expecting an opaque matl we should enable depth writes, as we don't
-            // need to blend most lines.
-            depth_write_enabled = true;
+        let mut shader_defs = Vec::new();
+        let label;
+        let mut depth_write_enabled = true;
+        // Lines already written to the depth prepass must still pass the main pass depth test
+        let mut depth_compare = if key
+            .intersects(PolylinePipelineKey::PREPASS | PolylinePipelineKey::DEPTH_PREPASSED)
+        {
+            CompareFunction::GreaterEqual
         } else {
-            label = "opaque_polyline_pipeline".into();
-            blend = Some(BlendState::REPLACE);
-            // For the opaque and alpha mask passes, fragments that are closer will replace
-            // the current fragment value in the output and the depth is written to the
-            // depth buffer
-            depth_write_enabled = true;
-        }
-
-        let format = match key.contains(PolylinePipelineKey::HDR) {
-            true => bevy::render::view::ViewTarget::TEXTURE_FORMAT_HDR,
-            false => TextureFormat::bevy_default(),
+            CompareFunction::Greater
         };
+        let mut fragment_entry_point = "fragment";
+        let mut targets = Vec::new();
+
+        if key.contains(PolylinePipelineKey::SHADOW) {
+            label = "shadow_polyline_pipeline".into();
+            // Shadow maps only need depth, so no fragment shader is used.
+            shader_defs.push("POLYLINE_SHADOW".into());
+        } else if key.contains(PolylinePipelineKey::PREPASS) {
+            label = "prepass_polyline_pipeline".into();
+            shader_defs.push("POLYLINE_PREPASS".into());
+            let normal_prepass = key.contains(PolylinePipelineKey::NORMAL_PREPASS);
+            let motion_vector_prepass = key.contains(PolylinePipelineKey::MOTION_VECTOR_PREPASS);
+            if normal_prepass {
+                shader_defs.push("NORMAL_PREPASS".into());
+            }
+            if motion_vector_prepass {
+                shader_defs.push("MOTION_VECTOR_PREPASS".into());
+            }
+            targets = prepass_target_descriptors(normal_prepass, motion_vector_prepass, false);
+            // A depth only prepass doesn't need a fragment shader
+            if targets.iter().all(Option::is_none) {
+                targets.clear();
+            } else {
+                shader_defs.push("PREPASS_FRAGMENT".into());
+            }
+            fragment_entry_point = "fragment_prepass";
+        } else {
+            let blend;
+            if key.contains(PolylinePipelineKey::OCCLUDED) {
+                label = "occluded_polyline_pipeline".into();
+                blend = Some(BlendState::ALPHA_BLENDING);
+                // Only draw the fragments that are behind the geometry already in the depth buffer,
+                // without affecting it.
+                depth_write_enabled = false;
+                depth_compare = CompareFunction::Less;
+                shader_defs.push("POLYLINE_OCCLUDED".into());
+            } else if key.contains(PolylinePipelineKey::TRANSPARENT_MAIN_PASS) {
+                label = "transparent_polyline_pipeline".into();
+                blend = Some(BlendState::ALPHA_BLENDING);
+                // For the transparent pass, fragments that are closer will be alpha blended
+                // but their depth is not written to the depth buffer
+                depth_write_enabled = false;
+            } else if key.contains(PolylinePipelineKey::PERSPECTIVE) {
+                // We need to use transparent pass with perspective to support thin line fading.
+                label = "transparent_polyline_pipeline".into();
+                blend = Some(BlendState::ALPHA_BLENDING);
+                // Because we are expecting an opaque matl we should enable depth writes, as we don't
+                // need to blend most lines.
+                depth_write_enabled = true;
+            } else {
+                label = "opaque_polyline_pipeline".into();
+                blend = Some(BlendState::REPLACE);
+                // For the opaque and alpha mask passes, fragments that are closer will replace
+                // the current fragment value in the output and the depth is written to the
+                // depth buffer
+                depth_write_enabled = true;
+            }
+
+            let format = match key.contains(PolylinePipelineKey::HDR) {
+                true => bevy::render::view::ViewTarget::TEXTURE_FORMAT_HDR,
+                false => TextureFormat::bevy_default(),
+            };
+            targets.push(Some(ColorTargetState {
+                format,
+                blend,
+                write_mask: ColorWrites::ALL,
+            }));
+        }
 
         let mut vertex_layout = VertexBufferLayout {
             step_mode: VertexStepMode::Instance,
@@ -217,26 +718,87 @@ impl SpecializedRenderPipeline for PolylinePipeline {
                 shader_location: 0,
             }],
         };
+        let mut buffers = vec![vertex_layout.clone(), {
+            vertex_layout.attributes[0].shader_location = 1;
+            vertex_layout
+        }];
+
+        // The widths come first as every pass needs them
+        if key.contains(PolylinePipelineKey::WIDTHS) {
+            shader_defs.push("POLYLINE_WIDTHS".into());
+            let mut width_layout = VertexBufferLayout {
+                step_mode: VertexStepMode::Instance,
+                array_stride: VertexFormat::Float32.size(),
+                attributes: vec![VertexAttribute {
+                    format: VertexFormat::Float32,
+                    offset: 0,
+                    shader_location: 13,
+                }],
+            };
+            buffers.push(width_layout.clone());
+            width_layout.attributes[0].shader_location = 14;
+            buffers.push(width_layout);
+        }
+
+        if key.contains(PolylinePipelineKey::OUTLINE) {
+            shader_defs.push("POLYLINE_OUTLINE".into());
+        }
+        if key.contains(PolylinePipelineKey::TUBE) {
+            shader_defs.push("POLYLINE_TUBE".into());
+        }
+        // Both the outline and the tube shading need the position across the line
+        if key.intersects(PolylinePipelineKey::OUTLINE | PolylinePipelineKey::TUBE) {
+            shader_defs.push("POLYLINE_SIDE".into());
+        }
+
+        if key.contains(PolylinePipelineKey::FOG) {
+            shader_defs.push("POLYLINE_FOG".into());
+        }
+
+        if key.contains(PolylinePipelineKey::SCALARS) {
+            shader_defs.push("POLYLINE_SCALARS".into());
+            let mut scalar_layout = VertexBufferLayout {
+                step_mode: VertexStepMode::Instance,
+                array_stride: VertexFormat::Float32.size(),
+                attributes: vec![VertexAttribute {
+                    format: VertexFormat::Float32,
+                    offset: 0,
+                    shader_location: 2,
+                }],
+            };
+            buffers.push(scalar_layout.clone());
+            scalar_layout.attributes[0].shader_location = 3;
+            buffers.push(scalar_layout);
+        }
+
+        if key.contains(PolylinePipelineKey::COLORS) {
+            shader_defs.push("POLYLINE_COLORS".into());
+            let mut color_layout = VertexBufferLayout {
+                step_mode: VertexStepMode::Instance,
+                array_stride: VertexFormat::Float32x4.size(),
+                attributes: vec![VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: 0,
+                    shader_location: 11,
+                }],
+            };
+            buffers.push(color_layout.clone());
+            color_layout.attributes[0].shader_location = 12;
+            buffers.push(color_layout);
+        }
 
         RenderPipelineDescriptor {
             vertex: VertexState {
                 shader: self.shader.clone(),
                 entry_point: "vertex".into(),
                 shader_defs: shader_defs.clone(),
-                buffers: vec![vertex_layout.clone(), {
-                    vertex_layout.attributes[0].shader_location = 1;
-                    vertex_layout
-                }],
+                buffers,
             },
-            fragment: Some(FragmentState {
+            fragment: (!targets.is_empty()).then(|| FragmentState {
                 shader: self.shader.clone(),
                 shader_defs,
-                entry_point: "fragment".into(),
-                targets: vec![Some(ColorTargetState {
-                    format,
-                    blend,
-                    write_mask: ColorWrites::ALL,
-                })],
+                entry_point: fragment_entry_point.into(),
+                targets,
             }),
             layout: vec![], // This is set in `PolylineMaterialPipeline::specialize()`
             primitive: PrimitiveState {
@@ -249,9 +811,9 @@ impl SpecializedRenderPipeline for PolylinePipeline {
                 strip_index_format: None,
             },
             depth_stencil: Some(DepthStencilState {
-                format: TextureFormat::Depth32Float,
+                format: CORE_3D_DEPTH_FORMAT,
                 depth_write_enabled,
-                depth_compare: CompareFunction::Greater,
+                depth_compare,
                 stencil: StencilState {
                     front: StencilFaceState::IGNORE,
                     back: StencilFaceState::IGNORE,
@@ -286,6 +848,27 @@ bitflags::bitflags! {
         const PERSPECTIVE = (1 << 0);
         const TRANSPARENT_MAIN_PASS = (1 << 1);
         const HDR = (1 << 2);
+        /// The polyline has per-vertex scalars and the material maps them through a colormap.
+        const SCALARS = (1 << 3);
+        const OUTLINE = (1 << 4);
+        /// Second pass drawing only the occluded portions of the line.
+        const OCCLUDED = (1 << 5);
+        /// Depth prepass, optionally writing normals and motion vectors.
+        const PREPASS = (1 << 6);
+        const NORMAL_PREPASS = (1 << 7);
+        const MOTION_VECTOR_PREPASS = (1 << 8);
+        /// Depth only pass rendering into a light's shadow map.
+        const SHADOW = (1 << 9);
+        /// The view's distance fog is applied to the line.
+        const FOG = (1 << 10);
+        /// The line is shaded like a lit tube.
+        const TUBE = (1 << 11);
+        /// The polyline has per-vertex colors.
+        const COLORS = (1 << 12);
+        /// The line was drawn in the depth prepass, so the main pass also passes on equal depths.
+        const DEPTH_PREPASSED = (1 << 13);
+        /// The polyline has per-vertex widths.
+        const WIDTHS = (1 << 14);
         const MSAA_RESERVED_BITS = Self::MSAA_MASK_BITS << Self::MSAA_SHIFT_BITS;
     }
 }
@@ -338,6 +921,13 @@ pub fn prepare_polyline_bind_group(
 #[derive(Component)]
 pub struct PolylineViewBindGroup {
     pub value: BindGroup,
+    /// Only present when the previous view uniforms are prepared, which is done by bevy's
+    /// `PrepassPlugin`.
+    pub motion_vectors: Option<BindGroup>,
+    /// Only present when the fog uniforms are prepared, which is done by `bevy_pbr`'s
+    /// `FogPlugin`.
+    #[cfg(feature = "pbr")]
+    pub fog: Option<BindGroup>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -346,17 +936,44 @@ pub fn prepare_polyline_view_bind_groups(
     render_device: Res<RenderDevice>,
     polyline_pipeline: Res<PolylinePipeline>,
     view_uniforms: Res<ViewUniforms>,
+    previous_view_uniforms: Option<Res<PreviousViewUniforms>>,
+    #[cfg(feature = "pbr")] fog_meta: Option<Res<bevy::pbr::FogMeta>>,
     views: Query<Entity, With<bevy::render::view::ExtractedView>>,
 ) {
+    let previous_view_binding = previous_view_uniforms
+        .as_ref()
+        .and_then(|uniforms| uniforms.uniforms.binding());
+    #[cfg(feature = "pbr")]
+    let fog_binding = fog_meta
+        .as_ref()
+        .and_then(|fog_meta| fog_meta.gpu_fogs.binding());
     for entity in views.iter() {
         let view_bind_group = render_device.create_bind_group(
             Some("polyline_view_bind_group"),
             &polyline_pipeline.view_layout,
             &BindGroupEntries::single(&view_uniforms.uniforms),
         );
+        let motion_vectors = previous_view_binding.clone().map(|previous_view_binding| {
+            render_device.create_bind_group(
+                Some("polyline_view_motion_vectors_bind_group"),
+                &polyline_pipeline.view_motion_vectors_layout,
+                &BindGroupEntries::sequential((&view_uniforms.uniforms, previous_view_binding)),
+            )
+        });
+        #[cfg(feature = "pbr")]
+        let fog = fog_binding.clone().map(|fog_binding| {
+            render_device.create_bind_group(
+                Some("polyline_view_fog_bind_group"),
+                &polyline_pipeline.view_fog_layout,
+                &BindGroupEntries::sequential((&view_uniforms.uniforms, fog_binding)),
+            )
+        });
 
         commands.entity(entity).insert(PolylineViewBindGroup {
             value: view_bind_group,
+            motion_vectors,
+            #[cfg(feature = "pbr")]
+            fog,
         });
     }
 }
@@ -385,30 +1002,86 @@ impl<const I: usize, P: PhaseItem> RenderCommand<P> for SetPolylineBindGroup<I>
 
 pub struct DrawPolyline;
 impl<P: PhaseItem> RenderCommand<P> for DrawPolyline {
-    type ViewQuery = ();
-    type ItemQuery = Read<PolylineHandle>;
-    type Param = SRes<RenderAssets<GpuPolyline>>;
+    type ViewQuery = Read<ExtractedView>;
+    type ItemQuery = (
+        Read<PolylineHandle>,
+        Read<PolylineUniform>,
+        Read<PolylineMaterialHandle>,
+    );
+    type Param = (
+        SRes<RenderAssets<GpuPolyline>>,
+        SRes<RenderAssets<GpuPolylineMaterial>>,
+    );
 
     #[inline]
     fn render<'w>(
         _item: &P,
-        _view: ROQueryItem<'w, Self::ViewQuery>,
-        pl_handle: Option<ROQueryItem<'w, Self::ItemQuery>>,
-        polylines: SystemParamItem<'w, '_, Self::Param>,
+        view: ROQueryItem<'w, Self::ViewQuery>,
+        item: Option<ROQueryItem<'w, Self::ItemQuery>>,
+        (polylines, materials): SystemParamItem<'w, '_, Self::Param>,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
-        if let Some(gpu_polyline) = polylines.into_inner().get(&pl_handle.unwrap().0) {
+        let (pl_handle, polyline_uniform, material_handle) = item.unwrap();
+        if let Some(gpu_polyline) = polylines.into_inner().get(&pl_handle.0) {
             if gpu_polyline.vertex_count < 2 {
                 return RenderCommandResult::Success;
             }
 
+            let material = materials.into_inner().get(&material_handle.0);
+            let colormap = material.is_some_and(|material| material.colormap);
+            let lod_tolerance = material.map_or(0.0, |material| material.lod_tolerance);
+            let (start_marker_vertices, end_marker_vertices) = material
+                .map_or((0, 0), |material| {
+                    (material.start_marker_vertices, material.end_marker_vertices)
+                });
+            let strips = gpu_polyline.select_lod(view, &polyline_uniform.transform, lod_tolerance);
+
             let item_size = VertexFormat::Float32x3.size();
             let buffer_size = gpu_polyline.vertex_buffer.size() - item_size;
             pass.set_vertex_buffer(0, gpu_polyline.vertex_buffer.slice(..buffer_size));
             pass.set_vertex_buffer(1, gpu_polyline.vertex_buffer.slice(item_size..));
 
-            let num_instances = gpu_polyline.vertex_count.max(1) - 1;
-            pass.draw(0..6, 0..num_instances);
+            // The optional buffers follow each other in the slots, like in the pipeline
+            let mut slot = 2;
+            if let Some(width_buffer) = &gpu_polyline.width_buffer {
+                let item_size = VertexFormat::Float32.size();
+                let buffer_size = width_buffer.size() - item_size;
+                pass.set_vertex_buffer(slot, width_buffer.slice(..buffer_size));
+                pass.set_vertex_buffer(slot + 1, width_buffer.slice(item_size..));
+                slot += 2;
+            }
+            if let Some(scalar_buffer) = gpu_polyline.scalar_buffer.as_ref().filter(|_| colormap) {
+                let item_size = VertexFormat::Float32.size();
+                let buffer_size = scalar_buffer.size() - item_size;
+                pass.set_vertex_buffer(slot, scalar_buffer.slice(..buffer_size));
+                pass.set_vertex_buffer(slot + 1, scalar_buffer.slice(item_size..));
+                slot += 2;
+            }
+            if let Some(color_buffer) = &gpu_polyline.color_buffer {
+                let item_size = VertexFormat::Float32x4.size();
+                let buffer_size = color_buffer.size() - item_size;
+                pass.set_vertex_buffer(slot, color_buffer.slice(..buffer_size));
+                pass.set_vertex_buffer(slot + 1, color_buffer.slice(item_size..));
+            }
+
+            // Each instance draws the segment starting at its vertex, so the strips are drawn
+            // separately to skip the segments between them
+            for strip in strips {
+                pass.draw(0..6, strip.start..strip.end - 1);
+
+                // Markers are drawn by the vertices after the quad, with the first and last
+                // segments of the strip
+                if start_marker_vertices > 0 {
+                    pass.draw(6..6 + start_marker_vertices, strip.start..strip.start + 1);
+                }
+                if end_marker_vertices > 0 {
+                    let first_vertex = 6 + MARKER_MAX_VERTICES;
+                    pass.draw(
+                        first_vertex..first_vertex + end_marker_vertices,
+                        strip.end - 2..strip.end - 1,
+                    );
+                }
+            }
 
             RenderCommandResult::Success
         } else {
@@ -416,3 +1089,75 @@ impl<P: PhaseItem> RenderCommand<P> for DrawPolyline {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A polyline along X with a vertex at each integer.
+    fn polyline(vertex_count: usize, breaks: Vec<usize>) -> Polyline {
+        Polyline {
+            vertices: (0..vertex_count)
+                .map(|index| Vec3::new(index as f32, 0.0, 0.0))
+                .collect(),
+            breaks,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn cuboid_strips_and_segments() {
+        let cuboid = Polyline::from(Cuboid::default());
+        assert!(cuboid.breaks_are_valid());
+        assert_eq!(cuboid.strips().count(), 6);
+        assert_eq!(cuboid.segments().count(), 12);
+    }
+
+    #[test]
+    fn segments_skip_breaks() {
+        let polyline = polyline(5, vec![2]);
+        assert_eq!(polyline.strips().collect::<Vec<_>>(), vec![0..2, 2..5]);
+        let segments: Vec<_> = polyline.segments().collect();
+        assert_eq!(
+            segments,
+            vec![
+                (0, Vec3::ZERO, Vec3::X),
+                (2, 2.0 * Vec3::X, 3.0 * Vec3::X),
+                (3, 3.0 * Vec3::X, 4.0 * Vec3::X)
+            ]
+        );
+    }
+
+    #[test]
+    fn from_strips_skips_empty_strips() {
+        let polyline = Polyline::from_strips([vec![Vec3::ZERO], vec![], vec![Vec3::X, Vec3::Y]]);
+        assert_eq!(polyline.breaks, vec![1]);
+        assert_eq!(polyline.strips().collect::<Vec<_>>(), vec![0..1, 1..3]);
+        assert_eq!(polyline.segments().count(), 1);
+    }
+
+    #[test]
+    fn invalid_breaks_are_ignored() {
+        let polyline = polyline(5, vec![3, 1, 3, 0, 7]);
+        assert!(!polyline.breaks_are_valid());
+        assert_eq!(polyline.strips().collect::<Vec<_>>(), vec![0..3, 3..5]);
+        assert_eq!(polyline.segments().count(), 3);
+    }
+
+    #[test]
+    fn empty_polyline_has_no_strips() {
+        let polyline = Polyline::default();
+        assert!(polyline.breaks_are_valid());
+        assert_eq!(polyline.strips().count(), 0);
+        assert_eq!(polyline.segments().count(), 0);
+    }
+
+    #[test]
+    fn from_edges_joins_a_loop_into_one_strip() {
+        let vertices = [Vec3::ZERO, Vec3::X, Vec3::ONE, Vec3::Y];
+        let polyline = Polyline::from_edges(&vertices, [[0, 1], [1, 2], [2, 3], [3, 0], [3, 9]]);
+        assert_eq!(polyline.strips().count(), 1);
+        assert_eq!(polyline.segments().count(), 4);
+        assert_eq!(polyline.vertices.first(), polyline.vertices.last());
+    }
+}
@@ -0,0 +1,260 @@
+#[cfg(feature = "pbr")]
+use crate::polyline::{ExtractedPolylineShadowCasters, PolylineUniform};
+use crate::{
+    material::{
+        AlphaMode, DrawPolylineMaterial, DrawPolylineMotionVectors, GpuPolylineMaterial,
+        PolylineMaterialHandle, PolylineMaterialPipeline,
+    },
+    polyline::{GpuPolyline, PolylineHandle, PolylinePipelineKey},
+};
+
+use bevy::{
+    core_pipeline::prepass::{
+        DeferredPrepass, MotionVectorPrepass, NormalPrepass, Opaque3dPrepass,
+        OpaqueNoLightmap3dBatchSetKey, OpaqueNoLightmap3dBinKey,
+    },
+    ecs::component::Tick,
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_phase::*,
+        render_resource::{PipelineCache, SpecializedRenderPipelines},
+        view::{ExtractedView, RenderVisibleEntities},
+        Render, RenderApp, RenderSet,
+    },
+};
+#[cfg(feature = "pbr")]
+use bevy::{
+    ecs::entity::EntityHashSet,
+    render::primitives::{Frustum, Sphere},
+};
+
+/// Draws polylines whose [`PolylineMaterial::prepass`](crate::material::PolylineMaterial::prepass)
+/// or [`PolylineMaterial::cast_shadows`](crate::material::PolylineMaterial::cast_shadows) is
+/// enabled into the prepass and shadow phases.
+pub struct PolylinePrepassPlugin;
+
+impl Plugin for PolylinePrepassPlugin {
+    fn build(&self, _app: &mut App) {}
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .add_render_command::<Opaque3dPrepass, DrawPolylineMaterial>()
+            .add_render_command::<Opaque3dPrepass, DrawPolylineMotionVectors>()
+            .add_systems(Render, queue_prepass_polylines.in_set(RenderSet::Queue));
+
+        // Shadow phases only exist when bevy's `MaterialPlugin` is used.
+        #[cfg(feature = "pbr")]
+        if render_app
+            .world()
+            .contains_resource::<DrawFunctions<bevy::pbr::Shadow>>()
+        {
+            render_app
+                .add_render_command::<bevy::pbr::Shadow, DrawPolylineMaterial>()
+                .add_systems(Render, queue_shadow_polylines.in_set(RenderSet::Queue));
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn queue_prepass_polylines(
+    draw_functions: Res<DrawFunctions<Opaque3dPrepass>>,
+    material_pipeline: Res<PolylineMaterialPipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<PolylineMaterialPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    render_materials: Res<RenderAssets<GpuPolylineMaterial>>,
+    render_polylines: Res<RenderAssets<GpuPolyline>>,
+    polylines: Query<(&PolylineHandle, &PolylineMaterialHandle)>,
+    views: Query<(
+        &ExtractedView,
+        &RenderVisibleEntities,
+        &Msaa,
+        Has<NormalPrepass>,
+        Has<MotionVectorPrepass>,
+        Has<DeferredPrepass>,
+    )>,
+    mut prepass_phases: ResMut<ViewBinnedRenderPhases<Opaque3dPrepass>>,
+    mut next_tick: Local<Tick>,
+) {
+    let draw_prepass = draw_functions.read().id::<DrawPolylineMaterial>();
+    let draw_motion_vectors = draw_functions.read().id::<DrawPolylineMotionVectors>();
+
+    for (view, visible_entities, msaa, normal_prepass, motion_vector_prepass, deferred_prepass) in
+        &views
+    {
+        // The prepass node is skipped when rendering deferred
+        if deferred_prepass {
+            continue;
+        }
+        let Some(prepass_phase) = prepass_phases.get_mut(&view.retained_view_entity) else {
+            continue;
+        };
+
+        let mut view_key =
+            PolylinePipelineKey::from_msaa_samples(msaa.samples()) | PolylinePipelineKey::PREPASS;
+        let mut draw_function = draw_prepass;
+        if normal_prepass {
+            view_key |= PolylinePipelineKey::NORMAL_PREPASS;
+        }
+        if motion_vector_prepass {
+            view_key |= PolylinePipelineKey::MOTION_VECTOR_PREPASS;
+            draw_function = draw_motion_vectors;
+        }
+
+        for (visible_entity, visible_main_entity) in visible_entities.get::<PolylineHandle>() {
+            let Ok((polyline_handle, material_handle)) = polylines.get(*visible_entity) else {
+                continue;
+            };
+            let Some(material) = render_materials.get(&material_handle.0) else {
+                continue;
+            };
+            if !material.prepass || material.alpha_mode != AlphaMode::Opaque {
+                continue;
+            }
+            let Some(render_polyline) = render_polylines.get(&polyline_handle.0) else {
+                continue;
+            };
+
+            let pipeline_id = pipelines.specialize(
+                &pipeline_cache,
+                &material_pipeline,
+                view_key | material.geometry_key() | render_polyline.geometry_key(),
+            );
+
+            let this_tick = next_tick.get() + 1;
+            next_tick.set(this_tick);
+
+            prepass_phase.add(
+                OpaqueNoLightmap3dBatchSetKey {
+                    draw_function,
+                    pipeline: pipeline_id,
+                    material_bind_group_index: None,
+                    vertex_slab: default(),
+                    index_slab: None,
+                },
+                OpaqueNoLightmap3dBinKey {
+                    // The draw command doesn't use a mesh handle so we don't need an `asset_id`
+                    asset_id: AssetId::<Mesh>::invalid().untyped(),
+                },
+                (*visible_entity, *visible_main_entity),
+                InputUniformIndex::default(),
+                BinnedRenderPhaseType::NonMesh,
+                *next_tick,
+            );
+        }
+    }
+}
+
+/// Queues the shadow casting polylines into the shadow phase of each light view whose frustum
+/// they intersect.
+///
+/// Bevy only collects the meshes visible from the lights, so the polylines are culled here with
+/// their bounding sphere, including the ones outside of the cameras' view.
+#[cfg(feature = "pbr")]
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn queue_shadow_polylines(
+    draw_functions: Res<DrawFunctions<bevy::pbr::Shadow>>,
+    material_pipeline: Res<PolylineMaterialPipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<PolylineMaterialPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    render_materials: Res<RenderAssets<GpuPolylineMaterial>>,
+    render_polylines: Res<RenderAssets<GpuPolyline>>,
+    shadow_casters: Res<ExtractedPolylineShadowCasters>,
+    polylines: Query<(&PolylineHandle, &PolylineUniform, &PolylineMaterialHandle)>,
+    views: Query<&bevy::pbr::ViewLightEntities, With<ExtractedView>>,
+    light_views: Query<(&ExtractedView, &Frustum, &bevy::pbr::LightEntity)>,
+    mut shadow_phases: ResMut<ViewBinnedRenderPhases<bevy::pbr::Shadow>>,
+    mut queued_light_views: Local<EntityHashSet>,
+    mut next_tick: Local<Tick>,
+) {
+    use bevy::pbr::{LightEntity, ShadowBatchSetKey, ShadowBinKey};
+
+    let draw_shadow = draw_functions.read().id::<DrawPolylineMaterial>();
+    // Shadow maps are never multisampled
+    let view_key = PolylinePipelineKey::from_msaa_samples(1) | PolylinePipelineKey::SHADOW;
+
+    // The bounding spheres of the shadow casters in world space
+    let casters: Vec<_> = shadow_casters
+        .0
+        .iter()
+        .filter_map(|&(entity, main_entity)| {
+            let (polyline_handle, uniform, material_handle) = polylines.get(entity).ok()?;
+            let material = render_materials.get(&material_handle.0)?;
+            if !material.cast_shadows || material.alpha_mode != AlphaMode::Opaque {
+                return None;
+            }
+            let render_polyline = render_polylines.get(&polyline_handle.0)?;
+            let bounding_sphere = render_polyline.bounding_sphere;
+            let (scale, _, _) = uniform.transform.to_scale_rotation_translation();
+            let sphere = Sphere {
+                center: uniform.transform.transform_point3a(bounding_sphere.center),
+                radius: bounding_sphere.radius() * scale.abs().max_element(),
+            };
+            let key = view_key | material.geometry_key() | render_polyline.geometry_key();
+            Some((entity, main_entity, key, sphere))
+        })
+        .collect();
+
+    // Lights shared by several cameras are only queued once
+    queued_light_views.clear();
+    for view_lights in &views {
+        for &light_view_entity in &view_lights.lights {
+            if !queued_light_views.insert(light_view_entity) {
+                continue;
+            }
+            let Ok((light_view, frustum, light_entity)) = light_views.get(light_view_entity) else {
+                continue;
+            };
+            let Some(shadow_phase) = shadow_phases.get_mut(&light_view.retained_view_entity) else {
+                continue;
+            };
+            // Like Bevy, the casters between a directional light and its cascades are kept, the
+            // shader clamps them onto the near plane
+            let intersect_near = !matches!(light_entity, LightEntity::Directional { .. });
+
+            for (entity, main_entity, key, sphere) in &casters {
+                if !intersects_sphere(frustum, sphere, intersect_near) {
+                    continue;
+                }
+
+                let pipeline_id = pipelines.specialize(&pipeline_cache, &material_pipeline, *key);
+
+                let this_tick = next_tick.get() + 1;
+                next_tick.set(this_tick);
+
+                shadow_phase.add(
+                    ShadowBatchSetKey {
+                        pipeline: pipeline_id,
+                        draw_function: draw_shadow,
+                        material_bind_group_index: None,
+                        vertex_slab: default(),
+                        index_slab: None,
+                    },
+                    ShadowBinKey {
+                        asset_id: AssetId::<Mesh>::invalid().untyped(),
+                    },
+                    (*entity, *main_entity),
+                    InputUniformIndex::default(),
+                    BinnedRenderPhaseType::NonMesh,
+                    *next_tick,
+                );
+            }
+        }
+    }
+}
+
+/// Whether a sphere is inside or intersects a frustum, optionally ignoring its near plane.
+#[cfg(feature = "pbr")]
+fn intersects_sphere(frustum: &Frustum, sphere: &Sphere, intersect_near: bool) -> bool {
+    let center = sphere.center.extend(1.0);
+    frustum
+        .half_spaces
+        .iter()
+        .enumerate()
+        // The half spaces are the left, right, bottom, top, near and far planes
+        .filter(|(index, _)| intersect_near || *index != 4)
+        .all(|(_, half_space)| half_space.normal_d().dot(center) + sphere.radius > 0.0)
+}
@@ -0,0 +1,408 @@
+use crate::polyline::Polyline;
+use bevy::prelude::*;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+/// A builder creating a [`Polyline`], like [`MeshBuilder`] does for meshes.
+///
+/// Two dimensional primitives are drawn in the XY plane, like their meshes, and can be moved
+/// to another plane with the [`Transform`] of the polyline.
+pub trait PolylineBuilder {
+    /// Builds the polyline.
+    fn build(&self) -> Polyline;
+}
+
+impl<T: PolylineBuilder> From<T> for Polyline {
+    fn from(builder: T) -> Self {
+        builder.build()
+    }
+}
+
+/// A primitive that can be drawn as a [`Polyline`], like [`Meshable`] does for meshes.
+pub trait PolylineDrawable {
+    /// The builder used to create the polyline.
+    type Output: PolylineBuilder;
+
+    /// Creates a builder for the outline of the primitive.
+    fn polyline(&self) -> Self::Output;
+}
+
+/// Closed ring of `resolution` segments, going counter clockwise from the angle 0.
+fn ring(resolution: u32, point: impl Fn(f32) -> Vec3) -> Polyline {
+    arc(0.0, TAU, resolution, point)
+}
+
+/// Arc of `resolution` segments from `start_angle` to `end_angle`.
+fn arc(start_angle: f32, end_angle: f32, resolution: u32, point: impl Fn(f32) -> Vec3) -> Polyline {
+    let resolution = resolution.max(1);
+    Polyline {
+        vertices: (0..=resolution)
+            .map(|i| point(start_angle + (end_angle - start_angle) * i as f32 / resolution as f32))
+            .collect(),
+        ..default()
+    }
+}
+
+/// A builder used for creating a [`Polyline`] from a [`Circle`].
+#[derive(Clone, Copy, Debug)]
+pub struct CirclePolylineBuilder {
+    pub circle: Circle,
+    /// Number of segments of the circle. The default is 32.
+    pub resolution: u32,
+}
+
+impl CirclePolylineBuilder {
+    /// Sets the number of segments of the circle.
+    pub fn resolution(mut self, resolution: u32) -> Self {
+        self.resolution = resolution;
+        self
+    }
+}
+
+impl PolylineBuilder for CirclePolylineBuilder {
+    fn build(&self) -> Polyline {
+        ring(self.resolution.max(3), |angle| {
+            (self.circle.radius * Vec2::from_angle(angle)).extend(0.0)
+        })
+    }
+}
+
+impl PolylineDrawable for Circle {
+    type Output = CirclePolylineBuilder;
+
+    fn polyline(&self) -> Self::Output {
+        CirclePolylineBuilder {
+            circle: *self,
+            resolution: 32,
+        }
+    }
+}
+
+impl From<Circle> for Polyline {
+    fn from(circle: Circle) -> Self {
+        circle.polyline().build()
+    }
+}
+
+/// A builder used for creating a [`Polyline`] from an [`Arc2d`].
+///
+/// Like [`Arc2d`] itself, the arc is symmetric around the Y axis.
+#[derive(Clone, Copy, Debug)]
+pub struct Arc2dPolylineBuilder {
+    pub arc: Arc2d,
+    /// Number of segments of the arc. The default is 32.
+    pub resolution: u32,
+}
+
+impl Arc2dPolylineBuilder {
+    /// Sets the number of segments of the arc.
+    pub fn resolution(mut self, resolution: u32) -> Self {
+        self.resolution = resolution;
+        self
+    }
+}
+
+impl PolylineBuilder for Arc2dPolylineBuilder {
+    fn build(&self) -> Polyline {
+        arc(
+            FRAC_PI_2 - self.arc.half_angle,
+            FRAC_PI_2 + self.arc.half_angle,
+            self.resolution,
+            |angle| (self.arc.radius * Vec2::from_angle(angle)).extend(0.0),
+        )
+    }
+}
+
+impl PolylineDrawable for Arc2d {
+    type Output = Arc2dPolylineBuilder;
+
+    fn polyline(&self) -> Self::Output {
+        Arc2dPolylineBuilder {
+            arc: *self,
+            resolution: 32,
+        }
+    }
+}
+
+impl From<Arc2d> for Polyline {
+    fn from(arc: Arc2d) -> Self {
+        arc.polyline().build()
+    }
+}
+
+/// A builder used for creating a [`Polyline`] from an [`Ellipse`].
+#[derive(Clone, Copy, Debug)]
+pub struct EllipsePolylineBuilder {
+    pub ellipse: Ellipse,
+    /// Number of segments of the ellipse. The default is 32.
+    pub resolution: u32,
+}
+
+impl EllipsePolylineBuilder {
+    /// Sets the number of segments of the ellipse.
+    pub fn resolution(mut self, resolution: u32) -> Self {
+        self.resolution = resolution;
+        self
+    }
+}
+
+impl PolylineBuilder for EllipsePolylineBuilder {
+    fn build(&self) -> Polyline {
+        ring(self.resolution.max(3), |angle| {
+            (self.ellipse.half_size * Vec2::from_angle(angle)).extend(0.0)
+        })
+    }
+}
+
+impl PolylineDrawable for Ellipse {
+    type Output = EllipsePolylineBuilder;
+
+    fn polyline(&self) -> Self::Output {
+        EllipsePolylineBuilder {
+            ellipse: *self,
+            resolution: 32,
+        }
+    }
+}
+
+impl From<Ellipse> for Polyline {
+    fn from(ellipse: Ellipse) -> Self {
+        ellipse.polyline().build()
+    }
+}
+
+impl From<Rectangle> for Polyline {
+    fn from(rectangle: Rectangle) -> Self {
+        let Vec2 { x, y } = rectangle.half_size;
+        Polyline {
+            vertices: [(-x, -y), (x, -y), (x, y), (-x, y), (-x, -y)]
+                .map(|(x, y)| Vec3::new(x, y, 0.0))
+                .to_vec(),
+            ..default()
+        }
+    }
+}
+
+impl From<RegularPolygon> for Polyline {
+    /// The first vertex of the polygon is on the Y axis, like in its mesh.
+    fn from(polygon: RegularPolygon) -> Self {
+        let radius = polygon.circumcircle.radius;
+        ring(polygon.sides.max(3), |angle| {
+            (radius * Vec2::from_angle(FRAC_PI_2 + angle)).extend(0.0)
+        })
+    }
+}
+
+impl<const N: usize> From<Polyline3d<N>> for Polyline {
+    fn from(polyline: Polyline3d<N>) -> Self {
+        Polyline {
+            vertices: polyline.vertices.to_vec(),
+            ..default()
+        }
+    }
+}
+
+impl From<BoxedPolyline3d> for Polyline {
+    fn from(polyline: BoxedPolyline3d) -> Self {
+        Polyline {
+            vertices: polyline.vertices.into_vec(),
+            ..default()
+        }
+    }
+}
+
+impl From<Cuboid> for Polyline {
+    /// The 12 edges of the cuboid, as its bottom and top faces and the 4 edges between them.
+    fn from(cuboid: Cuboid) -> Self {
+        let Vec3 { x, y, z } = cuboid.half_size;
+        let face = |y| [(-x, -z), (x, -z), (x, z), (-x, z)].map(|(x, z)| Vec3::new(x, y, z));
+        let (bottom, top) = (face(-y), face(y));
+        let mut polyline = Polyline::from_strips([bottom, top].map(|face| {
+            let mut ring = face.to_vec();
+            ring.push(face[0]);
+            ring
+        }));
+        for (bottom, top) in bottom.into_iter().zip(top) {
+            polyline.push_strip([bottom, top]);
+        }
+        polyline
+    }
+}
+
+/// A builder used for creating a [`Polyline`] from a [`Sphere`], with rings along the
+/// meridians and parallels of the sphere.
+///
+/// The poles of the sphere are on the Y axis.
+#[derive(Clone, Copy, Debug)]
+pub struct SpherePolylineBuilder {
+    pub sphere: Sphere,
+    /// Number of half circles going from pole to pole. The default is 8.
+    pub meridians: u32,
+    /// Number of circles around the Y axis, evenly spaced between the poles. The default is 7.
+    pub parallels: u32,
+    /// Number of segments of a whole circle. The default is 32.
+    pub resolution: u32,
+}
+
+impl SpherePolylineBuilder {
+    /// Sets the number of half circles going from pole to pole.
+    pub fn meridians(mut self, meridians: u32) -> Self {
+        self.meridians = meridians;
+        self
+    }
+
+    /// Sets the number of circles around the Y axis.
+    pub fn parallels(mut self, parallels: u32) -> Self {
+        self.parallels = parallels;
+        self
+    }
+
+    /// Sets the number of segments of a whole circle.
+    pub fn resolution(mut self, resolution: u32) -> Self {
+        self.resolution = resolution;
+        self
+    }
+}
+
+impl PolylineBuilder for SpherePolylineBuilder {
+    fn build(&self) -> Polyline {
+        let radius = self.sphere.radius;
+        let resolution = self.resolution.max(3);
+        let mut polyline = Polyline::default();
+        for i in 0..self.meridians {
+            let longitude = i as f32 / self.meridians as f32 * TAU;
+            let meridian = arc(0.0, PI, resolution.div_ceil(2), |latitude| {
+                let (sin, cos) = latitude.sin_cos();
+                radius * Vec3::new(sin * longitude.cos(), cos, sin * longitude.sin())
+            });
+            polyline.push_strip(meridian.vertices);
+        }
+        for i in 1..=self.parallels {
+            let latitude = i as f32 / (self.parallels + 1) as f32 * PI;
+            let (sin, cos) = latitude.sin_cos();
+            let parallel = ring(resolution, |longitude| {
+                radius * Vec3::new(sin * longitude.cos(), cos, sin * longitude.sin())
+            });
+            polyline.push_strip(parallel.vertices);
+        }
+        polyline
+    }
+}
+
+impl PolylineDrawable for Sphere {
+    type Output = SpherePolylineBuilder;
+
+    fn polyline(&self) -> Self::Output {
+        SpherePolylineBuilder {
+            sphere: *self,
+            meridians: 8,
+            parallels: 7,
+            resolution: 32,
+        }
+    }
+}
+
+impl From<Sphere> for Polyline {
+    fn from(sphere: Sphere) -> Self {
+        sphere.polyline().build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_closed(vertices: &[Vec3]) -> bool {
+        vertices[0].abs_diff_eq(vertices[vertices.len() - 1], 1e-5)
+    }
+
+    #[test]
+    fn rings_are_closed() {
+        let circle = Circle::new(2.0).polyline().resolution(16).build();
+        assert_eq!(circle.vertices.len(), 16 + 1);
+        assert!(is_closed(&circle.vertices));
+        for vertex in &circle.vertices {
+            assert!((vertex.length() - 2.0).abs() < 1e-5);
+            assert_eq!(vertex.z, 0.0);
+        }
+
+        let ellipse = Polyline::from(Ellipse::new(2.0, 1.0));
+        assert_eq!(ellipse.vertices.len(), 32 + 1);
+        assert!(is_closed(&ellipse.vertices));
+        assert!(ellipse.vertices[0].abs_diff_eq(Vec3::new(2.0, 0.0, 0.0), 1e-5));
+        assert!(ellipse.vertices[8].abs_diff_eq(Vec3::new(0.0, 1.0, 0.0), 1e-5));
+
+        // Fewer than 3 segments can't enclose anything
+        let triangle = Circle::new(1.0).polyline().resolution(1).build();
+        assert_eq!(triangle.vertices.len(), 3 + 1);
+    }
+
+    #[test]
+    fn arcs_are_open_and_symmetric() {
+        let arc = Arc2d::from_radians(1.0, PI)
+            .polyline()
+            .resolution(4)
+            .build();
+        assert_eq!(arc.vertices.len(), 4 + 1);
+        assert!(!is_closed(&arc.vertices));
+        assert!(arc.vertices[0].abs_diff_eq(Vec3::X, 1e-5));
+        assert!(arc.vertices[2].abs_diff_eq(Vec3::Y, 1e-5));
+        assert!(arc.vertices[4].abs_diff_eq(Vec3::NEG_X, 1e-5));
+    }
+
+    #[test]
+    fn polygons_are_closed() {
+        let rectangle = Polyline::from(Rectangle::new(4.0, 2.0));
+        assert_eq!(rectangle.vertices.len(), 4 + 1);
+        assert!(is_closed(&rectangle.vertices));
+        assert_eq!(rectangle.vertices[2], Vec3::new(2.0, 1.0, 0.0));
+
+        let hexagon = Polyline::from(RegularPolygon::new(1.0, 6));
+        assert_eq!(hexagon.vertices.len(), 6 + 1);
+        assert!(is_closed(&hexagon.vertices));
+        assert!(hexagon.vertices[0].abs_diff_eq(Vec3::Y, 1e-5));
+    }
+
+    #[test]
+    fn polylines_keep_their_vertices() {
+        let vertices = [Vec3::ZERO, Vec3::X, Vec3::Y];
+        assert_eq!(
+            Polyline::from(Polyline3d::<3>::new(vertices)).vertices,
+            vertices
+        );
+        assert_eq!(
+            Polyline::from(BoxedPolyline3d::new(vertices)).vertices,
+            vertices
+        );
+    }
+
+    #[test]
+    fn sphere_rings() {
+        let sphere = Sphere::new(2.0)
+            .polyline()
+            .meridians(4)
+            .parallels(3)
+            .resolution(8)
+            .build();
+        let strips: Vec<_> = sphere.strips().collect();
+        assert_eq!(strips.len(), 4 + 3);
+
+        // Meridians go from pole to pole with half the segments of a whole circle
+        for strip in &strips[..4] {
+            let meridian = &sphere.vertices[strip.clone()];
+            assert_eq!(meridian.len(), 4 + 1);
+            assert!(meridian[0].abs_diff_eq(Vec3::Y * 2.0, 1e-5));
+            assert!(meridian[4].abs_diff_eq(Vec3::NEG_Y * 2.0, 1e-5));
+        }
+        // Parallels are closed circles, evenly spaced between the poles
+        for (i, strip) in strips[4..].iter().enumerate() {
+            let parallel = &sphere.vertices[strip.clone()];
+            assert_eq!(parallel.len(), 8 + 1);
+            assert!(is_closed(parallel));
+            let height = 2.0 * ((i + 1) as f32 / 4.0 * PI).cos();
+            for vertex in parallel {
+                assert!((vertex.length() - 2.0).abs() < 1e-5);
+                assert!((vertex.y - height).abs() < 1e-5);
+            }
+        }
+    }
+}
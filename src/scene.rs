@@ -0,0 +1,220 @@
+use crate::{
+    material::{PolylineMaterial, PolylineMaterialHandle},
+    polyline::{Polyline, PolylineHandle},
+};
+use std::sync::Mutex;
+
+use bevy::{
+    asset::{uuid::Uuid, AssetPath},
+    ecs::{
+        component::{HookContext, Mutable},
+        world::DeferredWorld,
+    },
+    prelude::*,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// How a handle component is saved in a scene.
+#[derive(Serialize, Deserialize)]
+enum SceneHandle {
+    /// The asset is loaded again from its path when the scene is spawned.
+    Path(AssetPath<'static>),
+    /// A weak handle to an asset with a fixed id.
+    Uuid(Uuid),
+    /// Assets added at runtime have neither, so the handle is saved as the default handle.
+    Added,
+}
+
+/// The paths of the handles read from scenes, by the placeholder id they have until the
+/// component is inserted and the asset is loaded.
+///
+/// Serde gives no access to the `AssetServer` while a scene is deserialized, so the paths wait
+/// here for the `on_insert` hook of the component. Placeholders are kept, as a scene can be
+/// spawned many times, but reused for the same path.
+static SCENE_PATHS: Mutex<Vec<(Uuid, AssetPath<'static>)>> = Mutex::new(Vec::new());
+
+pub(crate) fn serialize_handle<A: Asset, S: Serializer>(
+    handle: &Handle<A>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let saved = match (handle.path(), handle.id()) {
+        (Some(path), _) => SceneHandle::Path(path.clone()),
+        (None, AssetId::Uuid { uuid }) => SceneHandle::Uuid(uuid),
+        (None, AssetId::Index { .. }) => SceneHandle::Added,
+    };
+    saved.serialize(serializer)
+}
+
+pub(crate) fn deserialize_handle<'de, A: Asset, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Handle<A>, D::Error> {
+    Ok(match SceneHandle::deserialize(deserializer)? {
+        SceneHandle::Path(path) => {
+            let mut paths = SCENE_PATHS.lock().unwrap();
+            let uuid = match paths.iter().find(|(_, saved)| *saved == path) {
+                Some((uuid, _)) => *uuid,
+                None => {
+                    let uuid = Uuid::new_v4();
+                    paths.push((uuid, path));
+                    uuid
+                }
+            };
+            Handle::Weak(AssetId::Uuid { uuid })
+        }
+        SceneHandle::Uuid(uuid) => Handle::Weak(AssetId::Uuid { uuid }),
+        SceneHandle::Added => Handle::default(),
+    })
+}
+
+/// A component holding a handle that is saved in scenes by [`serialize_handle`].
+pub(crate) trait SceneHandleComponent: Component<Mutability = Mutable> {
+    type Asset: Asset;
+
+    fn handle(&self) -> &Handle<Self::Asset>;
+
+    fn handle_mut(&mut self) -> &mut Handle<Self::Asset>;
+}
+
+impl SceneHandleComponent for PolylineHandle {
+    type Asset = Polyline;
+
+    fn handle(&self) -> &Handle<Polyline> {
+        &self.0
+    }
+
+    fn handle_mut(&mut self) -> &mut Handle<Polyline> {
+        &mut self.0
+    }
+}
+
+impl SceneHandleComponent for PolylineMaterialHandle {
+    type Asset = PolylineMaterial;
+
+    fn handle(&self) -> &Handle<PolylineMaterial> {
+        &self.0
+    }
+
+    fn handle_mut(&mut self) -> &mut Handle<PolylineMaterial> {
+        &mut self.0
+    }
+}
+
+/// Replaces the placeholder of a handle read from a scene with the asset loaded from its path.
+pub(crate) fn load_scene_handle<C: SceneHandleComponent>(
+    mut world: DeferredWorld,
+    context: HookContext,
+) {
+    let Some(AssetId::Uuid { uuid }) = world.get::<C>(context.entity).map(|c| c.handle().id())
+    else {
+        return;
+    };
+    let Some(path) = SCENE_PATHS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(placeholder, _)| *placeholder == uuid)
+        .map(|(_, path)| path.clone())
+    else {
+        return;
+    };
+    let Some(asset_server) = world.get_resource::<AssetServer>() else {
+        return;
+    };
+    let handle = asset_server.load(path);
+    if let Some(mut component) = world.get_mut::<C>(context.entity) {
+        *component.handle_mut() = handle;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{
+        ecs::entity::EntityHashMap,
+        scene::{serde::SceneDeserializer, DynamicScene, DynamicSceneBuilder},
+    };
+    use serde::de::DeserializeSeed;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+            .init_asset::<Polyline>()
+            .init_asset::<PolylineMaterial>()
+            .register_type::<PolylineHandle>()
+            .register_type::<PolylineMaterialHandle>();
+        app
+    }
+
+    fn round_trip(app: &mut App, entity: Entity) -> (String, Entity) {
+        let scene = DynamicSceneBuilder::from_world(app.world())
+            .extract_entity(entity)
+            .build();
+        let registry = app.world().resource::<AppTypeRegistry>().clone();
+        let ron = scene.serialize(&registry.read()).unwrap();
+
+        let scene: DynamicScene = SceneDeserializer {
+            type_registry: &registry.read(),
+        }
+        .deserialize(&mut ron::Deserializer::from_str(&ron).unwrap())
+        .unwrap();
+        let mut entity_map = EntityHashMap::default();
+        scene
+            .write_to_world(app.world_mut(), &mut entity_map)
+            .unwrap();
+        (ron, entity_map[&entity])
+    }
+
+    #[test]
+    fn loaded_handles_are_saved_by_path() {
+        let mut app = app();
+        let asset_server = app.world().resource::<AssetServer>().clone();
+        let polyline = asset_server.load::<Polyline>("route.polyline.ron");
+        let material = asset_server.load::<PolylineMaterial>("route.polyline.ron#material");
+        let entity = app
+            .world_mut()
+            .spawn((
+                PolylineHandle(polyline.clone()),
+                PolylineMaterialHandle(material.clone()),
+            ))
+            .id();
+
+        let (ron, loaded) = round_trip(&mut app, entity);
+        assert!(ron.contains(r#"Path("route.polyline.ron")"#), "{ron}");
+        assert!(ron.contains(r#"Path("route.polyline.ron#material")"#));
+
+        let loaded = app.world().entity(loaded);
+        let loaded_polyline = &loaded.get::<PolylineHandle>().unwrap().0;
+        let loaded_material = &loaded.get::<PolylineMaterialHandle>().unwrap().0;
+        assert!(loaded_polyline.is_strong());
+        assert_eq!(loaded_polyline.id(), polyline.id());
+        assert_eq!(loaded_material.id(), material.id());
+    }
+
+    #[test]
+    fn weak_and_added_handles() {
+        let mut app = app();
+        let uuid = Uuid::from_u128(0x4a6f_d3c5_9e2b_4c17_8f3a_1b2c_3d4e_5f60);
+        let added = app
+            .world_mut()
+            .resource_mut::<Assets<PolylineMaterial>>()
+            .add(PolylineMaterial::default());
+        let entity = app
+            .world_mut()
+            .spawn((
+                PolylineHandle(Handle::Weak(AssetId::Uuid { uuid })),
+                PolylineMaterialHandle(added),
+            ))
+            .id();
+
+        let (_, loaded) = round_trip(&mut app, entity);
+        let loaded = app.world().entity(loaded);
+        assert_eq!(
+            loaded.get::<PolylineHandle>().unwrap().0.id(),
+            AssetId::Uuid { uuid }
+        );
+        assert_eq!(
+            loaded.get::<PolylineMaterialHandle>().unwrap().0,
+            Handle::default()
+        );
+    }
+}
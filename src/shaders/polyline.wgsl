@@ -3,8 +3,44 @@
 @group(0) @binding(0)
 var<uniform> view: View;
 
+#ifdef MOTION_VECTOR_PREPASS
+// Matches `PreviousViewData` from `bevy_core_pipeline`
+struct PreviousView {
+    view_from_world: mat4x4<f32>,
+    clip_from_world: mat4x4<f32>,
+    clip_from_view: mat4x4<f32>,
+};
+
+@group(0) @binding(1)
+var<uniform> previous_view: PreviousView;
+#endif
+
+#ifdef POLYLINE_FOG
+// Matches `GpuFog` from `bevy_pbr`
+struct Fog {
+    base_color: vec4<f32>,
+    directional_light_color: vec4<f32>,
+    // Linear: be.x = start, be.y = end
+    // Exponential and exponential squared: be.x = density
+    // Atmospheric: be = extinction, bi = inscattering
+    be: vec3<f32>,
+    directional_light_exponent: f32,
+    bi: vec3<f32>,
+    mode: u32,
+};
+
+const FOG_MODE_LINEAR: u32 = 1u;
+const FOG_MODE_EXPONENTIAL: u32 = 2u;
+const FOG_MODE_EXPONENTIAL_SQUARED: u32 = 3u;
+const FOG_MODE_ATMOSPHERIC: u32 = 4u;
+
+@group(0) @binding(1)
+var<uniform> fog: Fog;
+#endif
+
 struct Polyline {
     model: mat4x4<f32>,
+    previous_model: mat4x4<f32>,
 };
 
 @group(1) @binding(0)
@@ -14,38 +50,179 @@ struct PolylineMaterial {
     color: vec4<f32>,
     depth_bias: f32,
     width: f32,
+    scalar_min: f32,
+    scalar_max: f32,
+    colormap: u32,
+    outline_width: f32,
+    outline_color: vec4<f32>,
+    occluded_color: vec4<f32>,
+    occluded_dash_length: f32,
+    start_marker: u32,
+    end_marker: u32,
+    marker_size: f32,
+    // Segments of custom markers, with their start in xy and their end in zw
+    start_marker_segments: array<vec4<f32>, 16>,
+    end_marker_segments: array<vec4<f32>, 16>,
 };
 
 @group(2) @binding(0)
 var<uniform> material: PolylineMaterial;
+@group(2) @binding(1)
+var colormap_texture: texture_2d<f32>;
+@group(2) @binding(2)
+var colormap_sampler: sampler;
+
+const COLORMAP_VIRIDIS: u32 = 1u;
+const COLORMAP_TURBO: u32 = 2u;
+const COLORMAP_IMAGE: u32 = 3u;
+
+const MARKER_ARROW: u32 = 1u;
+const MARKER_CIRCLE: u32 = 2u;
+const MARKER_SQUARE: u32 = 3u;
+const MARKER_CUSTOM: u32 = 4u;
+// Matches `MARKER_MAX_VERTICES` and `MARKER_CIRCLE_SEGMENTS` in `material.rs`
+const MARKER_MAX_VERTICES: u32 = 96u;
+const MARKER_CIRCLE_SEGMENTS: u32 = 16u;
 
 struct Vertex {
     @location(0) point_a: vec3<f32>,
     @location(1) point_b: vec3<f32>,
+#ifdef POLYLINE_SCALARS
+    @location(2) scalar_a: f32,
+    @location(3) scalar_b: f32,
+#endif
+#ifdef POLYLINE_COLORS
+    @location(11) color_a: vec4<f32>,
+    @location(12) color_b: vec4<f32>,
+#endif
+#ifdef POLYLINE_WIDTHS
+    @location(13) width_a: f32,
+    @location(14) width_b: f32,
+#endif
     @builtin(vertex_index) index: u32,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec4<f32>,
+#ifdef POLYLINE_SCALARS
+    @location(1) scalar: f32,
+#endif
+#ifdef POLYLINE_SIDE
+    // Signed distance from the center line in pixels
+    @location(2) side: f32,
+#ifdef POLYLINE_WIDTHS
+    // The width changes along the segments
+    @location(3) half_width: f32,
+#else
+    @location(3) @interpolate(flat) half_width: f32,
+#endif
+#endif
+#ifdef POLYLINE_OUTLINE
+    @location(4) @interpolate(flat) outline_color: vec4<f32>,
+#endif
+#ifdef POLYLINE_TUBE
+    // Screen space direction across the line
+    @location(10) @interpolate(flat) across_direction: vec2<f32>,
+#endif
+#ifdef POLYLINE_OCCLUDED
+    // Position along the line in pixels, used for dashing
+    @location(5) along: f32,
+#endif
+#ifdef NORMAL_PREPASS
+    @location(6) world_normal: vec3<f32>,
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    // Unjittered clip positions of the center line in the current and previous frame
+    @location(7) clip_position_t: vec4<f32>,
+    @location(8) previous_clip_position_t: vec4<f32>,
+#endif
+#ifdef POLYLINE_FOG
+    // Offset from the camera to the center line, in world space
+    @location(9) view_offset: vec3<f32>,
+#endif
 };
 
+const QUAD_POSITIONS = array<vec3<f32>, 6u>(
+    vec3(0.0, -0.5, 0.0),
+    vec3(0.0, -0.5, 1.0),
+    vec3(0.0, 0.5, 1.0),
+    vec3(0.0, -0.5, 0.0),
+    vec3(0.0, 0.5, 1.0),
+    vec3(0.0, 0.5, 0.0)
+);
+
+// Position of a vertex of a marker in pixels, with x pointing away from the line and y across it
+fn marker_position(marker: u32, index: u32, is_end: bool, size: f32, line_width: f32) -> vec2<f32> {
+    switch marker {
+        case MARKER_ARROW: {
+            let arrow = array<vec2<f32>, 3u>(vec2(0.0, -0.5), vec2(1.0, 0.0), vec2(0.0, 0.5));
+            return size * arrow[index];
+        }
+        case MARKER_CIRCLE: {
+            // Triangle fan around the center
+            let corner = index % 3u;
+            if (corner == 0u) {
+                return vec2(0.0);
+            }
+            let step = f32(index / 3u + corner - 1u) / f32(MARKER_CIRCLE_SEGMENTS);
+            let angle = 6.2831855 * step;
+            return 0.5 * size * vec2(cos(angle), sin(angle));
+        }
+        case MARKER_SQUARE: {
+            let position = QUAD_POSITIONS[index];
+            return size * vec2(position.z - 0.5, position.y);
+        }
+        case MARKER_CUSTOM: {
+            var segment: vec4<f32>;
+            if (is_end) {
+                segment = material.end_marker_segments[index / 6u];
+            } else {
+                segment = material.start_marker_segments[index / 6u];
+            }
+            let start = size * segment.xy;
+            let end = size * segment.zw;
+            let along = end - start;
+            if (dot(along, along) == 0.0) {
+                return start;
+            }
+            let across = normalize(vec2(-along.y, along.x));
+            let position = QUAD_POSITIONS[index % 6u];
+            return start + position.z * along + position.y * line_width * across;
+        }
+        default: {
+            return vec2(0.0);
+        }
+    }
+}
+
 @vertex
 fn vertex(vertex: Vertex) -> VertexOutput {
-    var positions = array<vec3<f32>, 6u>(
-        vec3(0.0, -0.5, 0.0),
-        vec3(0.0, -0.5, 1.0),
-        vec3(0.0, 0.5, 1.0),
-        vec3(0.0, -0.5, 0.0),
-        vec3(0.0, 0.5, 1.0),
-        vec3(0.0, 0.5, 0.0)
-    );
-    let position = positions[vertex.index];
+    // The vertices after the quad of the segment draw the markers at its ends
+    let is_marker = vertex.index >= 6u;
+    let marker_index = vertex.index - 6u;
+    let is_end = marker_index >= MARKER_MAX_VERTICES;
+    var position: vec3<f32>;
+    if (is_marker) {
+        // Markers are centered on the end of the segment, with no width of their own
+        position = vec3(0.0, 0.0, select(0.0, 1.0, is_end));
+    } else {
+        position = QUAD_POSITIONS[vertex.index];
+    }
 
     // algorithm based on https://wwwtyro.net/2019/11/18/instanced-lines.html
     var clip0 = view.clip_from_world * polyline.model * vec4(vertex.point_a, 1.0);
     var clip1 = view.clip_from_world * polyline.model * vec4(vertex.point_b, 1.0);
 
+#ifdef POLYLINE_SHADOW
+    // Directional light shadow maps use an orthographic projection. Clamp the lines behind the
+    // near plane onto it so they still cast shadows.
+    if (view.clip_from_view[3].w == 1.0) {
+        clip0.z = min(clip0.z, clip0.w);
+        clip1.z = min(clip1.z, clip1.w);
+    }
+#endif
+
     // Manual near plane clipping to avoid errors when doing the perspective divide inside this shader.
     clip0 = clip_near_plane(clip0, clip1);
     clip1 = clip_near_plane(clip1, clip0);
@@ -60,21 +237,61 @@ fn vertex(vertex: Vertex) -> VertexOutput {
     let y_basis = vec2(-x_basis.y, x_basis.x);
 
     var line_width = material.width;
+    #ifdef POLYLINE_WIDTHS
+        line_width *= mix(vertex.width_a, vertex.width_b, position.z);
+    #endif
     var color = material.color;
+    #ifdef POLYLINE_OCCLUDED
+        color = material.occluded_color;
+    #endif
+    #ifdef POLYLINE_COLORS
+        color *= mix(vertex.color_a, vertex.color_b, position.z);
+    #endif
+    var outline_width = 0.0;
+    #ifdef POLYLINE_OUTLINE
+        outline_width = material.outline_width;
+        var outline_color = material.outline_color;
+    #endif
 
     #ifdef POLYLINE_PERSPECTIVE
         line_width /= clip.w;
+        outline_width /= clip.w;
         // Line thinness fade from https://acegikmo.com/shapes/docs/#anti-aliasing
         if (line_width > 0.0 && line_width < 1.0) {
             color.a *= line_width;
+            #ifdef POLYLINE_OUTLINE
+                outline_color.a *= line_width;
+            #endif
             line_width = 1.0;
         }
     #endif
 
-    let pt_offset = line_width * (position.x * x_basis + position.y * y_basis);
+    // The quad is widened by the outline on both sides, the fragment shader picks the color
+    let total_width = line_width + 2.0 * outline_width;
+    let pt_offset = total_width * (position.x * x_basis + position.y * y_basis);
     let pt0 = screen0 + pt_offset;
     let pt1 = screen1 + pt_offset;
-    let pt = mix(pt0, pt1, position.z);
+    var pt = mix(pt0, pt1, position.z);
+    if (is_marker) {
+        var marker_size = material.marker_size;
+        #ifdef POLYLINE_PERSPECTIVE
+            marker_size /= clip.w;
+        #endif
+        var marker = material.start_marker;
+        var forward = -x_basis;
+        if (is_end) {
+            marker = material.end_marker;
+            forward = x_basis;
+        }
+        let marker_offset = marker_position(
+            marker,
+            marker_index % MARKER_MAX_VERTICES,
+            is_end,
+            marker_size,
+            line_width
+        );
+        pt += marker_offset.x * forward + marker_offset.y * vec2(-forward.y, forward.x);
+    }
 
     var depth: f32 = clip.z;
     if (material.depth_bias >= 0.0) {
@@ -91,7 +308,43 @@ fn vertex(vertex: Vertex) -> VertexOutput {
         depth = depth * exp2(-material.depth_bias * log2(clip.w / depth - epsilon));
     }
 
-    return VertexOutput(vec4(clip.w * ((2.0 * pt) / resolution - 1.0), depth, clip.w), color);
+    var out: VertexOutput;
+    out.clip_position = vec4(clip.w * ((2.0 * pt) / resolution - 1.0), depth, clip.w);
+    out.color = color;
+#ifdef POLYLINE_SCALARS
+    out.scalar = mix(vertex.scalar_a, vertex.scalar_b, position.z);
+#endif
+#ifdef POLYLINE_SIDE
+    out.side = position.y * total_width;
+    out.half_width = 0.5 * line_width;
+#endif
+#ifdef POLYLINE_OUTLINE
+    out.outline_color = outline_color;
+#endif
+#ifdef POLYLINE_TUBE
+    out.across_direction = y_basis;
+#endif
+#ifdef POLYLINE_OCCLUDED
+    out.along = dot(pt, x_basis);
+#endif
+#ifdef NORMAL_PREPASS
+    // Lines are billboards, so their normal always faces the camera
+    out.world_normal = normalize(view.world_from_view[2].xyz);
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    // The width of the line is applied in screen space and is the same in both frames,
+    // so only the motion of the center line is taken into account.
+    let center = vec4(mix(vertex.point_a, vertex.point_b, position.z), 1.0);
+    out.clip_position_t = view.unjittered_clip_from_world * polyline.model * center;
+    out.previous_clip_position_t =
+        previous_view.clip_from_world * polyline.previous_model * center;
+#endif
+#ifdef POLYLINE_FOG
+    // Computed from the clip position so it stays correct for segments clipped by the near plane
+    let world_position = view.world_from_clip * clip;
+    out.view_offset = world_position.xyz / world_position.w - view.world_position;
+#endif
+    return out;
 }
 
 fn clip_near_plane(a: vec4<f32>, b: vec4<f32>) -> vec4<f32> {
@@ -106,11 +359,197 @@ fn clip_near_plane(a: vec4<f32>, b: vec4<f32>) -> vec4<f32> {
     return a;
 }
 
+// Polynomial fit of viridis, from https://www.shadertoy.com/view/WlfXRN
+fn colormap_viridis(t: f32) -> vec3<f32> {
+    let c0 = vec3(0.2777273272234177, 0.005407344544966578, 0.3340998053353061);
+    let c1 = vec3(0.1050930431085774, 1.404613529898575, 1.384590162594685);
+    let c2 = vec3(-0.3308618287255563, 0.214847559468213, 0.09509516302823659);
+    let c3 = vec3(-4.634230498983486, -5.799100973351585, -19.33244095627987);
+    let c4 = vec3(6.228269936347081, 14.17993336680509, 56.69055260068105);
+    let c5 = vec3(4.776384997670288, -13.74514537774601, -65.35303263337234);
+    let c6 = vec3(-5.435455855934631, 4.645852612178535, 26.3124352495832);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+// Polynomial fit of turbo, from https://www.shadertoy.com/view/WlfXRN
+fn colormap_turbo(t: f32) -> vec3<f32> {
+    let c0 = vec3(0.1140890109226559, 0.06288340699912215, 0.2248337216805064);
+    let c1 = vec3(6.716419496985708, 3.182286745507602, 7.571581586103393);
+    let c2 = vec3(-66.09402360453038, -4.9279827041226, -10.09439367561635);
+    let c3 = vec3(228.7660791526501, 25.04986699771073, -91.54105330182436);
+    let c4 = vec3(-334.8351565777451, -69.31749712757485, 288.5858850615712);
+    let c5 = vec3(218.7637218434795, 67.52150567819112, -305.2045772184957);
+    let c6 = vec3(-52.88903478218835, -21.54527364654712, 110.5174647748972);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let c = clamp(color, vec3(0.0), vec3(1.0));
+    return select(pow((c + 0.055) / 1.055, vec3(2.4)), c / 12.92, c <= vec3(0.04045));
+}
+
+fn colormap(scalar: f32) -> vec4<f32> {
+    let range = material.scalar_max - material.scalar_min;
+    var t = 0.0;
+    if (range != 0.0) {
+        t = clamp((scalar - material.scalar_min) / range, 0.0, 1.0);
+    }
+    switch material.colormap {
+        case COLORMAP_VIRIDIS: {
+            return vec4(srgb_to_linear(colormap_viridis(t)), 1.0);
+        }
+        case COLORMAP_TURBO: {
+            return vec4(srgb_to_linear(colormap_turbo(t)), 1.0);
+        }
+        case COLORMAP_IMAGE: {
+            return textureSampleLevel(colormap_texture, colormap_sampler, vec2(t, 0.5), 0.0);
+        }
+        default: {
+            return vec4(1.0);
+        }
+    }
+}
+
+#ifdef POLYLINE_FOG
+// Same formulas as `bevy_pbr::fog`, without the directional light scattering as lines are not lit
+fn apply_fog(color: vec4<f32>, distance: f32) -> vec4<f32> {
+    var fog_color = fog.base_color;
+    switch fog.mode {
+        case FOG_MODE_LINEAR: {
+            let start = fog.be.x;
+            let end = fog.be.y;
+            fog_color.a *= 1.0 - clamp((end - distance) / (end - start), 0.0, 1.0);
+        }
+        case FOG_MODE_EXPONENTIAL: {
+            fog_color.a *= 1.0 - 1.0 / exp(distance * fog.be.x);
+        }
+        case FOG_MODE_EXPONENTIAL_SQUARED: {
+            let distance_times_density = distance * fog.be.x;
+            fog_color.a *= 1.0 - 1.0 / exp(distance_times_density * distance_times_density);
+        }
+        case FOG_MODE_ATMOSPHERIC: {
+            let extinction_factor = 1.0 - 1.0 / exp(distance * fog.be);
+            let inscattering_factor = 1.0 - 1.0 / exp(distance * fog.bi);
+            return vec4(
+                color.rgb * (1.0 - extinction_factor * fog_color.a)
+                    + fog_color.rgb * inscattering_factor * fog_color.a,
+                color.a
+            );
+        }
+        default: {
+            return color;
+        }
+    }
+    return vec4(mix(color.rgb, fog_color.rgb, fog_color.a), color.a);
+}
+#endif
+
+#ifdef POLYLINE_TUBE
+// Headlight slightly above the camera, in view space
+const TUBE_LIGHT_DIRECTION: vec3<f32> = vec3(0.0, 0.4472136, 0.8944272);
+// Half vector between the light and the view direction, which is the view space z axis
+const TUBE_HALF_VECTOR: vec3<f32> = vec3(0.0, 0.2297529, 0.9732489);
+const TUBE_AMBIENT: f32 = 0.25;
+const TUBE_SPECULAR: f32 = 0.3;
+const TUBE_SHININESS: f32 = 32.0;
+
+// Shades the line like a cylinder, `across` going from -1 on one edge of the line to 1 on the other
+fn shade_tube(color: vec3<f32>, across: f32, across_direction: vec2<f32>) -> vec3<f32> {
+    let normal = vec3(across * across_direction, sqrt(1.0 - across * across));
+    let diffuse = max(dot(normal, TUBE_LIGHT_DIRECTION), 0.0);
+    let specular = TUBE_SPECULAR * pow(max(dot(normal, TUBE_HALF_VECTOR), 0.0), TUBE_SHININESS);
+    return color * (TUBE_AMBIENT + (1.0 - TUBE_AMBIENT) * diffuse) + vec3(specular);
+}
+#endif
+
 struct FragmentInput {
     @location(0) color: vec4<f32>,
+#ifdef POLYLINE_SCALARS
+    @location(1) scalar: f32,
+#endif
+#ifdef POLYLINE_SIDE
+    @location(2) side: f32,
+#ifdef POLYLINE_WIDTHS
+    @location(3) half_width: f32,
+#else
+    @location(3) @interpolate(flat) half_width: f32,
+#endif
+#endif
+#ifdef POLYLINE_OUTLINE
+    @location(4) @interpolate(flat) outline_color: vec4<f32>,
+#endif
+#ifdef POLYLINE_TUBE
+    @location(10) @interpolate(flat) across_direction: vec2<f32>,
+#endif
+#ifdef POLYLINE_OCCLUDED
+    @location(5) along: f32,
+#endif
+#ifdef POLYLINE_FOG
+    @location(9) view_offset: vec3<f32>,
+#endif
 };
 
 @fragment
 fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
-    return in.color;
+#ifdef POLYLINE_OCCLUDED
+    let dash_length = material.occluded_dash_length;
+    if (dash_length > 0.0 && fract(in.along / (2.0 * dash_length)) >= 0.5) {
+        discard;
+    }
+#endif
+    var color = in.color;
+#ifdef POLYLINE_SCALARS
+    color *= colormap(in.scalar);
+#endif
+#ifdef POLYLINE_TUBE
+    let across = clamp(in.side / in.half_width, -1.0, 1.0);
+    color = vec4(shade_tube(color.rgb, across, in.across_direction), color.a);
+#endif
+#ifdef POLYLINE_OUTLINE
+    if (abs(in.side) > in.half_width) {
+        color = in.outline_color;
+    }
+#endif
+#ifdef POLYLINE_FOG
+    color = apply_fog(color, length(in.view_offset));
+#endif
+    return color;
+}
+
+#ifdef PREPASS_FRAGMENT
+struct PrepassFragmentInput {
+    @builtin(position) position: vec4<f32>,
+#ifdef NORMAL_PREPASS
+    @location(6) world_normal: vec3<f32>,
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    @location(7) clip_position_t: vec4<f32>,
+    @location(8) previous_clip_position_t: vec4<f32>,
+#endif
+};
+
+struct PrepassFragmentOutput {
+#ifdef NORMAL_PREPASS
+    @location(0) normal: vec4<f32>,
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    @location(1) motion_vector: vec2<f32>,
+#endif
+};
+
+@fragment
+fn fragment_prepass(in: PrepassFragmentInput) -> PrepassFragmentOutput {
+    var out: PrepassFragmentOutput;
+#ifdef NORMAL_PREPASS
+    out.normal = vec4(in.world_normal * 0.5 + vec3(0.5), 1.0);
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    let clip_position = in.clip_position_t.xy / in.clip_position_t.w;
+    let previous_clip_position = in.previous_clip_position_t.xy / in.previous_clip_position_t.w;
+    // Same convention as bevy's `calculate_motion_vector`: scaled to UV offsets, with y
+    // flipped as V goes down where clip space y goes up.
+    out.motion_vector = (clip_position - previous_clip_position) * vec2(0.5, -0.5);
+#endif
+    return out;
 }
+#endif
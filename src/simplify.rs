@@ -0,0 +1,433 @@
+use crate::polyline::{Polyline, PolylineHandle};
+use bevy::{math::bounding::BoundingVolume, prelude::*, transform::TransformSystem};
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+/// Simplification of polylines, which removes the vertices that barely change their shape.
+///
+/// Fewer, longer segments are cheaper to draw and shimmer less when lines are far away.
+impl Polyline {
+    /// Simplifies the polyline with the Ramer–Douglas–Peucker algorithm.
+    ///
+    /// The simplified polyline stays within `tolerance` of the original one. The first and
+    /// last vertices of each strip are always kept, as are the [`scalars`](Self::scalars),
+    /// [`colors`](Self::colors) and [`widths`](Self::widths) of the kept vertices.
+    pub fn simplify_rdp(&self, tolerance: f32) -> Polyline {
+        self.retain_vertices(
+            &self.kept_per_strip(|vertices| rdp_kept_vertices(vertices, tolerance)),
+        )
+    }
+
+    /// Simplifies the polyline with the Visvalingam–Whyatt algorithm.
+    ///
+    /// Vertices are removed while the triangle they form with their neighbors has an area
+    /// smaller than `tolerance` squared, which tends to keep the overall shape of noisy lines
+    /// better than [`simplify_rdp`](Self::simplify_rdp). The first and last vertices of each
+    /// strip are always kept, as are the [`scalars`](Self::scalars),
+    /// [`colors`](Self::colors) and [`widths`](Self::widths) of the kept vertices.
+    pub fn simplify_visvalingam(&self, tolerance: f32) -> Polyline {
+        self.retain_vertices(
+            &self.kept_per_strip(|vertices| {
+                visvalingam_kept_vertices(vertices, tolerance * tolerance)
+            }),
+        )
+    }
+
+    /// Simplifies the polyline with `method`.
+    pub fn simplify(&self, method: PolylineSimplificationMethod, tolerance: f32) -> Polyline {
+        match method {
+            PolylineSimplificationMethod::Rdp => self.simplify_rdp(tolerance),
+            PolylineSimplificationMethod::Visvalingam => self.simplify_visvalingam(tolerance),
+        }
+    }
+
+    /// Simplifies each strip separately, so strips never merge.
+    fn kept_per_strip(&self, strip_kept: impl Fn(&[Vec3]) -> Vec<bool>) -> Vec<bool> {
+        let mut kept = vec![true; self.vertices.len()];
+        for strip in self.strips() {
+            kept[strip.clone()].copy_from_slice(&strip_kept(&self.vertices[strip]));
+        }
+        kept
+    }
+
+    fn retain_vertices(&self, kept: &[bool]) -> Polyline {
+        // Number of kept vertices before each vertex, to move the breaks between strips
+        let kept_before: Vec<usize> = kept
+            .iter()
+            .scan(0, |count, &kept| {
+                let before = *count;
+                *count += kept as usize;
+                Some(before)
+            })
+            .collect();
+        let kept_count = kept.iter().filter(|kept| **kept).count();
+        Polyline {
+            vertices: retain(&self.vertices, kept),
+            scalars: if self.scalars.len() == self.vertices.len() {
+                retain(&self.scalars, kept)
+            } else {
+                self.scalars.clone()
+            },
+            colors: if self.colors.len() == self.vertices.len() {
+                retain(&self.colors, kept)
+            } else {
+                self.colors.clone()
+            },
+            widths: if self.widths.len() == self.vertices.len() {
+                retain(&self.widths, kept)
+            } else {
+                self.widths.clone()
+            },
+            breaks: self
+                .breaks
+                .iter()
+                .map(|&index| kept_before.get(index).copied().unwrap_or(kept_count))
+                .collect(),
+        }
+    }
+}
+
+pub(crate) fn retain<T: Copy>(values: &[T], kept: &[bool]) -> Vec<T> {
+    values
+        .iter()
+        .zip(kept)
+        .filter_map(|(value, kept)| kept.then_some(*value))
+        .collect()
+}
+
+/// Algorithm used to simplify a polyline.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PolylineSimplificationMethod {
+    /// Ramer–Douglas–Peucker, see [`Polyline::simplify_rdp`].
+    #[default]
+    Rdp,
+    /// Visvalingam–Whyatt, see [`Polyline::simplify_visvalingam`].
+    Visvalingam,
+}
+
+pub(crate) fn rdp_kept_vertices(vertices: &[Vec3], tolerance: f32) -> Vec<bool> {
+    let mut kept = vec![false; vertices.len()];
+    if vertices.len() < 3 {
+        kept.fill(true);
+        return kept;
+    }
+    kept[0] = true;
+    kept[vertices.len() - 1] = true;
+
+    let tolerance_squared = tolerance * tolerance;
+    let mut ranges = vec![(0, vertices.len() - 1)];
+    while let Some((start, end)) = ranges.pop() {
+        let (a, b) = (vertices[start], vertices[end]);
+        let farthest = (start + 1..end)
+            .map(|i| (i, distance_squared_to_segment(vertices[i], a, b)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((i, distance_squared)) = farthest {
+            if distance_squared > tolerance_squared {
+                kept[i] = true;
+                ranges.push((start, i));
+                ranges.push((i, end));
+            }
+        }
+    }
+    kept
+}
+
+fn distance_squared_to_segment(point: Vec3, a: Vec3, b: Vec3) -> f32 {
+    let direction = b - a;
+    let length_squared = direction.length_squared();
+    let t = if length_squared > 0.0 {
+        ((point - a).dot(direction) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    point.distance_squared(a + t * direction)
+}
+
+/// A vertex in the priority queue of the Visvalingam–Whyatt algorithm, smallest area first.
+struct VisvalingamVertex {
+    area: f32,
+    index: usize,
+}
+
+impl PartialEq for VisvalingamVertex {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for VisvalingamVertex {}
+
+impl PartialOrd for VisvalingamVertex {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for VisvalingamVertex {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .area
+            .total_cmp(&self.area)
+            .then(other.index.cmp(&self.index))
+    }
+}
+
+fn visvalingam_kept_vertices(vertices: &[Vec3], min_area: f32) -> Vec<bool> {
+    let len = vertices.len();
+    let mut kept = vec![true; len];
+    if len < 3 {
+        return kept;
+    }
+
+    let triangle_area = |a: usize, b: usize, c: usize| {
+        0.5 * (vertices[b] - vertices[a])
+            .cross(vertices[c] - vertices[a])
+            .length()
+    };
+    let mut previous: Vec<usize> = (0..len).map(|i| i.saturating_sub(1)).collect();
+    let mut next: Vec<usize> = (0..len).map(|i| (i + 1).min(len - 1)).collect();
+    let mut areas: Vec<f32> = (0..len)
+        .map(|i| {
+            if i == 0 || i == len - 1 {
+                f32::INFINITY
+            } else {
+                triangle_area(i - 1, i, i + 1)
+            }
+        })
+        .collect();
+    let mut queue: BinaryHeap<_> = (1..len - 1)
+        .map(|index| VisvalingamVertex {
+            area: areas[index],
+            index,
+        })
+        .collect();
+
+    while let Some(VisvalingamVertex { area, index }) = queue.pop() {
+        // Skip the outdated entries of vertices whose area changed or that were removed
+        if !kept[index] || area != areas[index] {
+            continue;
+        }
+        if area >= min_area {
+            break;
+        }
+        kept[index] = false;
+        let (before, after) = (previous[index], next[index]);
+        next[before] = after;
+        previous[after] = before;
+        for neighbor in [before, after] {
+            if neighbor == 0 || neighbor == len - 1 {
+                continue;
+            }
+            // The area of a vertex never decreases, so that removing a vertex can't make its
+            // neighbors be removed before the vertices that were less significant than it.
+            areas[neighbor] = triangle_area(previous[neighbor], neighbor, next[neighbor]).max(area);
+            queue.push(VisvalingamVertex {
+                area: areas[neighbor],
+                index: neighbor,
+            });
+        }
+    }
+    kept
+}
+
+/// Keeps the [`PolylineHandle`] of the entity pointing to a simplified version of `source`,
+/// simplified with a tolerance in screen pixels for the cameras looking at it.
+///
+/// The simplified polyline is a new asset owned by the entity, `source` itself is never
+/// modified.
+///
+/// The polyline is simplified again when its distance to the cameras changes enough, which
+/// keeps lines detailed up close while far away lines use fewer segments and shimmer less.
+#[derive(Component, Debug, Clone)]
+#[require(PolylineHandle)]
+pub struct PolylineSimplification {
+    /// The polyline at full resolution.
+    pub source: Handle<Polyline>,
+    /// Maximum error of the simplified polyline, in screen pixels.
+    pub tolerance: f32,
+    pub method: PolylineSimplificationMethod,
+    /// Local space tolerance the current simplified polyline was generated with.
+    applied_tolerance: Option<f32>,
+}
+
+impl PolylineSimplification {
+    pub fn new(source: Handle<Polyline>, tolerance: f32) -> Self {
+        Self {
+            source,
+            tolerance,
+            method: PolylineSimplificationMethod::default(),
+            applied_tolerance: None,
+        }
+    }
+
+    pub fn with_method(mut self, method: PolylineSimplificationMethod) -> Self {
+        self.method = method;
+        self
+    }
+}
+
+/// Updates the polylines with a [`PolylineSimplification`].
+pub struct PolylineSimplificationPlugin;
+
+impl Plugin for PolylineSimplificationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            simplify_polylines.after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+pub fn simplify_polylines(
+    cameras: Query<(&Camera, &Projection, &GlobalTransform)>,
+    mut simplified: Query<(
+        &mut PolylineSimplification,
+        &mut PolylineHandle,
+        &GlobalTransform,
+    )>,
+    mut polylines: ResMut<Assets<Polyline>>,
+    mut asset_events: EventReader<AssetEvent<Polyline>>,
+) {
+    let modified: Vec<_> = asset_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (mut simplification, mut handle, transform) in &mut simplified {
+        let Some(source) = polylines.get(&simplification.source) else {
+            continue;
+        };
+        let Some(bounding_box) = source.bounding_box() else {
+            continue;
+        };
+        let scale = transform.scale().max_element();
+        let center = transform.transform_point(bounding_box.center().into());
+        let radius = scale * Vec3::from(bounding_box.half_size()).length();
+
+        // World size of a pixel at the closest point of the polyline, for the camera
+        // needing the most detail
+        let Some(pixel_size) = cameras
+            .iter()
+            .filter(|(camera, ..)| camera.is_active)
+            .filter_map(|(camera, projection, camera_transform)| {
+                let viewport_height = camera.physical_viewport_size()?.y as f32;
+                let distance = (camera_transform.translation().distance(center) - radius).max(0.0);
+                match projection {
+                    Projection::Perspective(perspective) => {
+                        Some(2.0 * distance * (0.5 * perspective.fov).tan() / viewport_height)
+                    }
+                    Projection::Orthographic(orthographic) => {
+                        Some(orthographic.area.height() / viewport_height)
+                    }
+                    Projection::Custom(_) => None,
+                }
+            })
+            .min_by(f32::total_cmp)
+        else {
+            continue;
+        };
+
+        // The tolerance is rounded down to a power of two, so the polyline is only simplified
+        // again when the camera moves significantly. It is converted to the local space of the
+        // polyline so the simplification follows its scale.
+        let tolerance = simplification.tolerance * pixel_size / scale;
+        let tolerance = if tolerance > 0.0 {
+            tolerance.log2().floor().exp2()
+        } else {
+            0.0
+        };
+        let previous_tolerance = simplification.applied_tolerance;
+        if previous_tolerance == Some(tolerance) && !modified.contains(&simplification.source.id())
+        {
+            continue;
+        }
+        simplification.applied_tolerance = Some(tolerance);
+
+        let simplified_polyline = source.simplify(simplification.method, tolerance);
+        match polylines.get_mut(&handle.0) {
+            Some(polyline) if previous_tolerance.is_some() => *polyline = simplified_polyline,
+            _ => handle.0 = polylines.add(simplified_polyline),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METHODS: [PolylineSimplificationMethod; 2] = [
+        PolylineSimplificationMethod::Rdp,
+        PolylineSimplificationMethod::Visvalingam,
+    ];
+
+    #[test]
+    fn strips_are_simplified_separately() {
+        // Two zigzags whose ends would be joined if the strips were simplified together
+        let mut polyline = Polyline::from_strips([
+            vec![
+                Vec3::ZERO,
+                Vec3::new(1.0, 0.1, 0.0),
+                Vec3::new(2.0, 0.0, 0.0),
+            ],
+            vec![
+                Vec3::new(2.0, 5.0, 0.0),
+                Vec3::new(1.0, 5.1, 0.0),
+                Vec3::new(0.0, 5.0, 0.0),
+            ],
+        ]);
+        polyline.scalars = vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0];
+        polyline.colors = vec![LinearRgba::RED; 6];
+        polyline.widths = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+
+        for method in METHODS {
+            let simplified = polyline.simplify(method, 0.5);
+            assert_eq!(
+                simplified.vertices,
+                [
+                    Vec3::ZERO,
+                    Vec3::new(2.0, 0.0, 0.0),
+                    Vec3::new(2.0, 5.0, 0.0),
+                    Vec3::new(0.0, 5.0, 0.0)
+                ]
+            );
+            assert_eq!(simplified.breaks, [2]);
+            assert_eq!(simplified.scalars, [0.0, 2.0, 3.0, 5.0]);
+            assert_eq!(simplified.colors, [LinearRgba::RED; 4]);
+            assert_eq!(simplified.widths, [1.0, 3.0, 4.0, 6.0]);
+        }
+    }
+
+    #[test]
+    fn cuboid_edges_become_single_segments() {
+        let cuboid = Polyline::from(Cuboid::default());
+        for method in METHODS {
+            let simplified = cuboid.simplify(method, 10.0);
+            let strips: Vec<_> = simplified.strips().collect();
+            assert_eq!(strips.len(), 6);
+            assert!(strips.iter().all(|strip| strip.len() == 2));
+        }
+    }
+
+    #[test]
+    fn collinear_vertices_are_removed() {
+        let polyline = Polyline {
+            vertices: (0..5).map(|x| Vec3::new(x as f32, 0.0, 0.0)).collect(),
+            ..default()
+        };
+        for method in METHODS {
+            assert_eq!(
+                polyline.simplify(method, 0.001).vertices,
+                [Vec3::ZERO, Vec3::new(4.0, 0.0, 0.0)]
+            );
+        }
+
+        // A vertex farther than the tolerance is kept
+        let mut bent = polyline.clone();
+        bent.vertices[2].y = 1.0;
+        for method in METHODS {
+            assert_eq!(bent.simplify(method, 0.8).vertices.len(), 3);
+        }
+    }
+}
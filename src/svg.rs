@@ -0,0 +1,404 @@
+use crate::{material::PolylineMaterial, polyline::Polyline};
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    platform::collections::HashMap,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use usvg::tiny_skia_path::{PathSegment, Point, Transform};
+
+/// The plane in which the [`SvgLoader`] places the SVG images.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SvgPlane {
+    /// Facing +Z, with the top of the image along +Y.
+    #[default]
+    Xy,
+    /// On the ground facing +Y, with the top of the image along -Z. This suits floor plans
+    /// and maps.
+    Xz,
+    /// Facing +X, with the top of the image along +Y.
+    Zy,
+}
+
+impl SvgPlane {
+    /// Places a point of the image, with Y pointing down like in SVG.
+    fn point(&self, point: Vec2) -> Vec3 {
+        match self {
+            SvgPlane::Xy => Vec3::new(point.x, -point.y, 0.0),
+            SvgPlane::Xz => Vec3::new(point.x, 0.0, point.y),
+            SvgPlane::Zy => Vec3::new(0.0, -point.y, -point.x),
+        }
+    }
+}
+
+/// Settings of the [`SvgLoader`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SvgLoaderSettings {
+    /// Largest distance between a curve and its segments, in the units of the SVG image. The
+    /// default is 0.1.
+    pub tolerance: f32,
+    pub plane: SvgPlane,
+    /// Size of a unit of the SVG image in the plane. The default is 1.
+    pub scale: f32,
+    /// Width of the lines outlining the paths that are filled but have no stroke. The
+    /// default is 1.
+    pub fill_width: f32,
+}
+
+impl Default for SvgLoaderSettings {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.1,
+            plane: SvgPlane::Xy,
+            scale: 1.0,
+            fill_width: 1.0,
+        }
+    }
+}
+
+/// The paths of an SVG image, loaded by the [`SvgLoader`].
+#[derive(Asset, TypePath, Debug)]
+pub struct SvgPolylines {
+    /// The lines of all the paths in a single polyline, labeled `Polyline`.
+    pub polyline: Handle<Polyline>,
+    /// The visible paths, in the order they are drawn.
+    pub paths: Vec<SvgPath>,
+}
+
+/// A path of an SVG image.
+#[derive(Debug, Clone)]
+pub struct SvgPath {
+    /// The `id` attribute of the element, empty when it has none.
+    pub id: String,
+    /// The lines of the path, labeled `Path{index}` with the index of the path.
+    pub polyline: Handle<Polyline>,
+    /// A material with the stroke color and width of the path, labeled `Material{index}`.
+    /// Paths with the same stroke share their material.
+    pub material: Handle<PolylineMaterial>,
+}
+
+/// Loads the `path`, `polyline`, `polygon`, `line`, `rect`, `circle` and `ellipse` elements
+/// of `.svg` files as [`SvgPolylines`].
+///
+/// Transforms are applied, and curves are flattened into segments. The stroke of each path
+/// is mapped to a [`PolylineMaterial`], with the stroke width in pixels. Paths without a stroke
+/// are outlined with their fill color. Gradients use the color of their first stop.
+#[derive(Default)]
+pub struct SvgLoader;
+
+/// An error when loading an SVG file.
+#[derive(Debug, Error)]
+pub enum SvgLoaderError {
+    #[error("could not read the SVG file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse the SVG file: {0}")]
+    Svg(#[from] usvg::Error),
+}
+
+impl AssetLoader for SvgLoader {
+    type Asset = SvgPolylines;
+    type Settings = SvgLoaderSettings;
+    type Error = SvgLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &SvgLoaderSettings,
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<SvgPolylines, SvgLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let tree = usvg::Tree::from_data(&bytes, &usvg::Options::default())?;
+
+        let mut paths = Vec::new();
+        collect_paths(tree.root(), &mut paths);
+
+        let mut all = Polyline::default();
+        let mut materials = HashMap::<[u32; 5], Handle<PolylineMaterial>>::default();
+        let mut loaded_paths = Vec::new();
+        for path in paths {
+            let transform = path.abs_transform();
+            let polyline = flatten(path.data(), transform, settings);
+            if polyline.vertices.is_empty() {
+                continue;
+            }
+            let (paint, opacity, width) = match (path.stroke(), path.fill()) {
+                (Some(stroke), _) => {
+                    // Scale the width like the path, by the average scale of the transform
+                    let (scale_x, scale_y) = transform.get_scale();
+                    let width = stroke.width().get() * (scale_x * scale_y).sqrt();
+                    (stroke.paint(), stroke.opacity().get(), width)
+                }
+                (None, Some(fill)) => (fill.paint(), fill.opacity().get(), settings.fill_width),
+                (None, None) => continue,
+            };
+            let color = paint_color(paint);
+            let color = LinearRgba::from(color.with_alpha(color.alpha * opacity));
+
+            for strip in polyline.strips() {
+                all.push_strip(polyline.vertices[strip].iter().copied());
+            }
+            let key = [
+                color.red.to_bits(),
+                color.green.to_bits(),
+                color.blue.to_bits(),
+                color.alpha.to_bits(),
+                width.to_bits(),
+            ];
+            let material_count = materials.len();
+            let material = materials
+                .entry(key)
+                .or_insert_with(|| {
+                    load_context.add_labeled_asset(
+                        format!("Material{material_count}"),
+                        PolylineMaterial {
+                            width,
+                            color,
+                            ..default()
+                        },
+                    )
+                })
+                .clone();
+            loaded_paths.push(SvgPath {
+                id: path.id().to_owned(),
+                polyline: load_context
+                    .add_labeled_asset(format!("Path{}", loaded_paths.len()), polyline),
+                material,
+            });
+        }
+
+        Ok(SvgPolylines {
+            polyline: load_context.add_labeled_asset("Polyline".into(), all),
+            paths: loaded_paths,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["svg", "svgz"]
+    }
+}
+
+/// Adds the visible paths of a group and its children to `paths`, in drawing order.
+fn collect_paths<'a>(group: &'a usvg::Group, paths: &mut Vec<&'a usvg::Path>) {
+    for node in group.children() {
+        match node {
+            usvg::Node::Group(group) => collect_paths(group, paths),
+            usvg::Node::Path(path) if path.is_visible() => paths.push(path),
+            _ => {}
+        }
+    }
+}
+
+/// The sRGB color of a paint, the color of the first stop for gradients.
+fn paint_color(paint: &usvg::Paint) -> Srgba {
+    let (color, opacity) = match paint {
+        usvg::Paint::Color(color) => (*color, 1.0),
+        usvg::Paint::LinearGradient(gradient) => gradient
+            .stops()
+            .first()
+            .map_or((usvg::Color::black(), 1.0), |stop| {
+                (stop.color(), stop.opacity().get())
+            }),
+        usvg::Paint::RadialGradient(gradient) => gradient
+            .stops()
+            .first()
+            .map_or((usvg::Color::black(), 1.0), |stop| {
+                (stop.color(), stop.opacity().get())
+            }),
+        usvg::Paint::Pattern(_) => (usvg::Color::black(), 1.0),
+    };
+    Srgba::rgb_u8(color.red, color.green, color.blue).with_alpha(opacity)
+}
+
+/// Flattens a path into a polyline with a strip per subpath.
+fn flatten(
+    path: &usvg::tiny_skia_path::Path,
+    transform: Transform,
+    settings: &SvgLoaderSettings,
+) -> Polyline {
+    let map = |point: Point| {
+        Vec2::new(
+            transform.sx * point.x + transform.kx * point.y + transform.tx,
+            transform.ky * point.x + transform.sy * point.y + transform.ty,
+        )
+    };
+    let mut polyline = Polyline::default();
+    let mut strip: Vec<Vec2> = Vec::new();
+    let mut start = Vec2::ZERO;
+    let mut finish = |strip: &mut Vec<Vec2>| {
+        if strip.len() > 1 {
+            polyline.push_strip(
+                strip
+                    .iter()
+                    .map(|&point| settings.plane.point(point * settings.scale)),
+            );
+        }
+        strip.clear();
+    };
+
+    for segment in path.segments() {
+        let last = strip.last().copied().unwrap_or(start);
+        match segment {
+            PathSegment::MoveTo(point) => {
+                finish(&mut strip);
+                start = map(point);
+                strip.push(start);
+            }
+            PathSegment::LineTo(point) => strip.push(map(point)),
+            PathSegment::QuadTo(control, point) => {
+                let (control, end) = (map(control), map(point));
+                let segments = bezier_segments(&[last, control, end], settings.tolerance);
+                strip.extend((1..=segments).map(|i| {
+                    let t = i as f32 / segments as f32;
+                    last.lerp(control, t).lerp(control.lerp(end, t), t)
+                }));
+            }
+            PathSegment::CubicTo(control_a, control_b, point) => {
+                let (control_a, control_b, end) = (map(control_a), map(control_b), map(point));
+                let segments =
+                    bezier_segments(&[last, control_a, control_b, end], settings.tolerance);
+                strip.extend((1..=segments).map(|i| {
+                    let t = i as f32 / segments as f32;
+                    let (a, b, c) = (
+                        last.lerp(control_a, t),
+                        control_a.lerp(control_b, t),
+                        control_b.lerp(end, t),
+                    );
+                    a.lerp(b, t).lerp(b.lerp(c, t), t)
+                }));
+            }
+            PathSegment::Close => {
+                if strip.last() != Some(&start) {
+                    strip.push(start);
+                }
+                // Segments after a close without a move start from the same point
+                finish(&mut strip);
+                strip.push(start);
+            }
+        }
+    }
+    finish(&mut strip);
+    polyline
+}
+
+/// Number of segments flattening a Bézier curve within `tolerance`, with Wang's formula.
+fn bezier_segments(points: &[Vec2], tolerance: f32) -> u32 {
+    let degree = (points.len() - 1) as f32;
+    let second_difference = points
+        .windows(3)
+        .map(|p| (p[0] - 2.0 * p[1] + p[2]).length())
+        .fold(0.0, f32::max);
+    let segments = (degree * (degree - 1.0) / 8.0 * second_difference / tolerance.max(1e-6)).sqrt();
+    (segments.ceil() as u32).clamp(1, 1024)
+}
+
+/// Loads SVG files with the [`SvgLoader`].
+pub struct PolylineSvgPlugin;
+
+impl Plugin for PolylineSvgPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<SvgPolylines>()
+            .init_asset_loader::<SvgLoader>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flattens the paths of an SVG image.
+    fn flatten_svg(svg: &str, settings: &SvgLoaderSettings) -> Vec<Polyline> {
+        let tree = usvg::Tree::from_str(svg, &usvg::Options::default()).unwrap();
+        let mut paths = Vec::new();
+        collect_paths(tree.root(), &mut paths);
+        paths
+            .iter()
+            .map(|path| flatten(path.data(), path.abs_transform(), settings))
+            .collect()
+    }
+
+    fn svg(content: &str) -> String {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">{content}</svg>"#
+        )
+    }
+
+    #[test]
+    fn subpaths_are_strips() {
+        let svg = svg(r#"<path d="M 0 0 L 10 0 L 10 10 Z M 20 0 L 30 0" stroke="black"/>"#);
+        let [polyline] = &flatten_svg(&svg, &default())[..] else {
+            panic!("expected a single path");
+        };
+        assert_eq!(
+            polyline.vertices,
+            [
+                Vec3::ZERO,
+                Vec3::new(10.0, 0.0, 0.0),
+                Vec3::new(10.0, -10.0, 0.0),
+                Vec3::ZERO,
+                Vec3::new(20.0, 0.0, 0.0),
+                Vec3::new(30.0, 0.0, 0.0),
+            ]
+        );
+        assert_eq!(polyline.breaks, [4]);
+    }
+
+    #[test]
+    fn transforms_and_planes() {
+        let svg = svg(
+            r#"<g transform="translate(5 0)"><line x1="0" y1="2" x2="4" y2="2" stroke="red"/></g>"#,
+        );
+        let points = |plane: SvgPlane| {
+            let settings = SvgLoaderSettings {
+                plane,
+                scale: 0.5,
+                ..default()
+            };
+            flatten_svg(&svg, &settings)[0].vertices.clone()
+        };
+        assert_eq!(
+            points(SvgPlane::Xy),
+            [Vec3::new(2.5, -1.0, 0.0), Vec3::new(4.5, -1.0, 0.0)]
+        );
+        assert_eq!(
+            points(SvgPlane::Xz),
+            [Vec3::new(2.5, 0.0, 1.0), Vec3::new(4.5, 0.0, 1.0)]
+        );
+        assert_eq!(
+            points(SvgPlane::Zy),
+            [Vec3::new(0.0, -1.0, -2.5), Vec3::new(0.0, -1.0, -4.5)]
+        );
+    }
+
+    #[test]
+    fn curves_are_flattened_within_the_tolerance() {
+        let svg = svg(r#"<circle cx="50" cy="50" r="40" stroke="black" fill="none"/>"#);
+        for tolerance in [1.0, 0.1] {
+            let settings = SvgLoaderSettings {
+                tolerance,
+                ..default()
+            };
+            let [circle] = &flatten_svg(&svg, &settings)[..] else {
+                panic!("expected a single path");
+            };
+            assert!(circle.breaks.is_empty());
+            assert_eq!(circle.vertices.first(), circle.vertices.last());
+            let center = Vec3::new(50.0, -50.0, 0.0);
+            // The circle is made of cubic curves, which are a few hundredths away from it
+            let curve_error = 0.05;
+            for (_, start, end) in circle.segments() {
+                assert!((start.distance(center) - 40.0).abs() <= curve_error);
+                // The middle of each segment is the farthest from the curve
+                let distance = start.midpoint(end).distance(center);
+                assert!((distance - 40.0).abs() <= tolerance + curve_error);
+            }
+        }
+
+        // Straight curves need a single segment
+        assert_eq!(
+            bezier_segments(&[Vec2::ZERO, Vec2::X, Vec2::X * 2.0], 0.1),
+            1
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use crate::{
+    gizmos::PolylineGizmo,
+    material::{PolylineMaterial, PolylineMaterialHandle},
+    polyline::{Polyline, PolylineHandle},
+};
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    render::{camera::CameraProjection, view::RenderLayers},
+};
+use std::fmt::Write;
+
+/// Writes polylines seen by a camera to an SVG image, projecting them on the CPU.
+///
+/// The lines have the width they are drawn with on screen, with
+/// [`perspective`](PolylineMaterial::perspective) lines getting thinner with the distance and
+/// [`widths`](Polyline::widths) tapering them, and their outline. Colormaps, per-vertex colors,
+/// markers, occluded styles and shading are not exported, and the lines are drawn from the farthest to the closest without depth
+/// testing.
+pub struct PolylineSvgWriter {
+    size: Vec2,
+    clip_from_world: Mat4,
+    view_from_clip: Mat4,
+    /// The elements of each polyline, with their distance to the camera
+    elements: Vec<(f32, String)>,
+}
+
+/// A vertex of a line projected on the image.
+#[derive(Clone, Copy)]
+struct ScreenVertex {
+    position: Vec2,
+    width: f32,
+    outline_width: f32,
+    /// Opacity of the lines thinner than a pixel, drawn a pixel wide instead.
+    opacity: f32,
+    /// Distance from the camera along its view direction.
+    depth: f32,
+}
+
+impl PolylineSvgWriter {
+    /// Creates an image of `size` pixels seen through a camera.
+    ///
+    /// Widths are in the pixels of the image, so `size` should be the physical size of the
+    /// camera's viewport for the lines to look like they do on screen.
+    pub fn new(camera_transform: &GlobalTransform, projection: &Projection, size: Vec2) -> Self {
+        let mut projection = projection.clone();
+        projection.update(size.x, size.y);
+        let view_from_world = camera_transform.compute_matrix().inverse();
+        let clip_from_view = projection.get_clip_from_view();
+        Self {
+            size,
+            clip_from_world: clip_from_view * view_from_world,
+            view_from_clip: clip_from_view.inverse(),
+            elements: Vec::new(),
+        }
+    }
+
+    /// Adds a polyline with its material and transform to the image.
+    pub fn add(
+        &mut self,
+        polyline: &Polyline,
+        material: &PolylineMaterial,
+        transform: &GlobalTransform,
+    ) {
+        let world_from_local = transform.compute_matrix();
+        let clip_from_local = self.clip_from_world * world_from_local;
+        let clip: Vec<Vec4> = polyline
+            .vertices
+            .iter()
+            .map(|vertex| clip_from_local * vertex.extend(1.0))
+            .collect();
+
+        let has_widths = polyline.widths.len() == polyline.vertices.len();
+        let width_scale = |index: usize| {
+            if has_widths {
+                polyline.widths[index]
+            } else {
+                1.0
+            }
+        };
+
+        // Runs of vertices in front of the camera, split where the lines cross the near plane.
+        // Like in the shader, a clipped end keeps the width of its vertex.
+        let mut runs: Vec<Vec<ScreenVertex>> = Vec::new();
+        for strip in polyline.strips() {
+            let mut run = Vec::new();
+            for index in strip.start..strip.end.saturating_sub(1) {
+                let (a, b) = (clip[index], clip[index + 1]);
+                if a.z > a.w && b.z > b.w {
+                    runs.push(std::mem::take(&mut run));
+                    continue;
+                }
+                let (clipped_a, clipped_b) = (clip_near_plane(a, b), clip_near_plane(b, a));
+                if clipped_a != a {
+                    runs.push(std::mem::take(&mut run));
+                }
+                if run.is_empty() {
+                    run.push(self.screen_vertex(clipped_a, material, width_scale(index)));
+                }
+                run.push(self.screen_vertex(clipped_b, material, width_scale(index + 1)));
+                if clipped_b != b {
+                    runs.push(std::mem::take(&mut run));
+                }
+            }
+            runs.push(run);
+        }
+        runs.retain(|run| run.len() > 1);
+        if runs.is_empty() {
+            return;
+        }
+
+        let mut element = String::new();
+        let (color, alpha) = svg_color(material.color);
+        let (outline_color, outline_alpha) = svg_color(material.outline_color);
+        for run in &runs {
+            if material.perspective || has_widths {
+                // The width changes along the segments, draw each of them as a trapezoid
+                for pair in run.windows(2) {
+                    let opacity = (pair[0].opacity + pair[1].opacity) / 2.0;
+                    if material.outline_width > 0.0 {
+                        let corners =
+                            trapezoid(pair, |vertex| vertex.width + 2.0 * vertex.outline_width);
+                        write_polygon(
+                            &mut element,
+                            &corners,
+                            &outline_color,
+                            outline_alpha * opacity,
+                        );
+                    }
+                    let corners = trapezoid(pair, |vertex| vertex.width);
+                    write_polygon(&mut element, &corners, &color, alpha * opacity);
+                }
+            } else {
+                let points = svg_points(run.iter().map(|vertex| vertex.position));
+                let width = run[0].width;
+                if material.outline_width > 0.0 {
+                    let outline_width = width + 2.0 * run[0].outline_width;
+                    write_polyline(
+                        &mut element,
+                        &points,
+                        &outline_color,
+                        outline_alpha,
+                        outline_width,
+                    );
+                }
+                write_polyline(&mut element, &points, &color, alpha, width);
+            }
+        }
+
+        // Only the drawn parts count, the vertices behind the camera would bring lines forward
+        let vertices = runs.iter().flatten();
+        let distance =
+            vertices.clone().map(|vertex| vertex.depth).sum::<f32>() / vertices.count() as f32;
+        self.elements.push((distance, element));
+    }
+
+    /// Projects a vertex in clip space to the image, with the widths of its material scaled by
+    /// the per-vertex `width_scale`.
+    fn screen_vertex(
+        &self,
+        clip: Vec4,
+        material: &PolylineMaterial,
+        width_scale: f32,
+    ) -> ScreenVertex {
+        // Like the shader, with the Y axis flipped to go down the image
+        let screen = self.size * (0.5 * clip.xy() / clip.w + 0.5);
+        let view = self.view_from_clip * clip;
+        let mut vertex = ScreenVertex {
+            position: Vec2::new(screen.x, self.size.y - screen.y),
+            width: material.width * width_scale,
+            outline_width: material.outline_width,
+            opacity: 1.0,
+            depth: -view.z / view.w,
+        };
+        if material.perspective {
+            vertex.width /= clip.w;
+            vertex.outline_width /= clip.w;
+            if vertex.width > 0.0 && vertex.width < 1.0 {
+                vertex.opacity = vertex.width;
+                vertex.width = 1.0;
+            }
+        }
+        vertex
+    }
+
+    /// Writes the SVG image, with the farthest polylines first.
+    pub fn finish(mut self) -> String {
+        self.elements.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+            self.size.x, self.size.y
+        );
+        for (_, element) in &self.elements {
+            svg.push_str(element);
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Moves `a` onto the near plane toward `b` when only `a` is behind it, like the shader.
+fn clip_near_plane(a: Vec4, b: Vec4) -> Vec4 {
+    if a.z > a.w && b.z <= b.w {
+        let distance_a = a.z - a.w;
+        let distance_b = b.z - b.w;
+        let t = distance_a / (distance_a - distance_b);
+        return a + (b - a) * t;
+    }
+    a
+}
+
+/// The corners of the quad of a segment, with the width of each of its ends.
+fn trapezoid(pair: &[ScreenVertex], width: impl Fn(&ScreenVertex) -> f32) -> [Vec2; 4] {
+    let (a, b) = (pair[0], pair[1]);
+    let along = (b.position - a.position).normalize_or_zero();
+    let across = Vec2::new(-along.y, along.x) / 2.0;
+    [
+        a.position - across * width(&a),
+        b.position - across * width(&b),
+        b.position + across * width(&b),
+        a.position + across * width(&a),
+    ]
+}
+
+/// The hexadecimal sRGB color and the alpha of a color.
+fn svg_color(color: LinearRgba) -> (String, f32) {
+    let color = Srgba::from(color);
+    let [red, green, blue, _] = color.to_u8_array();
+    (format!("#{red:02x}{green:02x}{blue:02x}"), color.alpha)
+}
+
+fn svg_points(points: impl Iterator<Item = Vec2>) -> String {
+    let mut svg = String::new();
+    for point in points {
+        let _ = write!(svg, "{:.2},{:.2} ", point.x, point.y);
+    }
+    svg.pop();
+    svg
+}
+
+fn write_polyline(svg: &mut String, points: &str, color: &str, alpha: f32, width: f32) {
+    let _ = writeln!(
+        svg,
+        "  <polyline points=\"{points}\" fill=\"none\" stroke=\"{color}\" stroke-opacity=\"{alpha:.3}\" stroke-width=\"{width:.2}\" stroke-linejoin=\"bevel\"/>"
+    );
+}
+
+fn write_polygon(svg: &mut String, corners: &[Vec2], color: &str, alpha: f32) {
+    let _ = writeln!(
+        svg,
+        "  <polygon points=\"{}\" fill=\"{color}\" fill-opacity=\"{alpha:.3}\"/>",
+        svg_points(corners.iter().copied())
+    );
+}
+
+/// A system parameter exporting the visible polylines seen by a camera to an SVG image.
+///
+/// This only needs the polylines and their assets in the main world, so it also works in
+/// headless apps.
+#[derive(SystemParam)]
+pub struct PolylineSvgExport<'w, 's> {
+    cameras: Query<
+        'w,
+        's,
+        (
+            &'static GlobalTransform,
+            &'static Projection,
+            Option<&'static RenderLayers>,
+        ),
+    >,
+    polylines: Query<
+        'w,
+        's,
+        (
+            &'static PolylineHandle,
+            &'static PolylineMaterialHandle,
+            &'static GlobalTransform,
+            &'static InheritedVisibility,
+            Option<&'static RenderLayers>,
+        ),
+        Without<PolylineGizmo>,
+    >,
+    polyline_assets: Res<'w, Assets<Polyline>>,
+    materials: Res<'w, Assets<PolylineMaterial>>,
+}
+
+impl PolylineSvgExport<'_, '_> {
+    /// Exports the polylines seen by `camera` to an image of `size` pixels, see
+    /// [`PolylineSvgWriter::new`].
+    ///
+    /// Returns `None` if the camera has no [`GlobalTransform`] or [`Projection`].
+    pub fn export(&self, camera: Entity, size: Vec2) -> Option<String> {
+        let (camera_transform, projection, camera_layers) = self.cameras.get(camera).ok()?;
+        let camera_layers = camera_layers.cloned().unwrap_or_default();
+        let mut writer = PolylineSvgWriter::new(camera_transform, projection, size);
+        for (polyline, material, transform, visibility, layers) in &self.polylines {
+            let layers = layers.cloned().unwrap_or_default();
+            if !visibility.get() || !camera_layers.intersects(&layers) {
+                continue;
+            }
+            let (Some(polyline), Some(material)) = (
+                self.polyline_assets.get(&polyline.0),
+                self.materials.get(&material.0),
+            ) else {
+                continue;
+            };
+            writer.add(polyline, material, transform);
+        }
+        Some(writer.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A writer for a camera at the origin looking along -Z.
+    fn writer() -> PolylineSvgWriter {
+        PolylineSvgWriter::new(
+            &GlobalTransform::IDENTITY,
+            &Projection::Perspective(default()),
+            Vec2::splat(100.0),
+        )
+    }
+
+    fn material(color: Color) -> PolylineMaterial {
+        PolylineMaterial {
+            color: color.into(),
+            ..default()
+        }
+    }
+
+    fn strip(vertices: &[[f32; 3]]) -> Polyline {
+        Polyline {
+            vertices: vertices.iter().copied().map(Vec3::from).collect(),
+            ..default()
+        }
+    }
+
+    #[test]
+    fn segment_crossing_the_near_plane_is_clipped() {
+        let mut writer = writer();
+        let polyline = strip(&[[0.0, 0.0, -5.0], [0.0, 1.0, 5.0]]);
+        writer.add(&polyline, &default(), &default());
+        let svg = writer.finish();
+        assert_eq!(svg.matches("<polyline").count(), 1);
+        // The clipped end is on the near plane, still inside of the image
+        assert!(!svg.contains("NaN") && !svg.contains("inf"));
+    }
+
+    #[test]
+    fn near_plane_splits_the_runs() {
+        let mut writer = writer();
+        let polyline = strip(&[[-1.0, 0.0, -5.0], [0.0, 0.0, 5.0], [1.0, 0.0, -5.0]]);
+        writer.add(&polyline, &default(), &default());
+        assert_eq!(writer.elements.len(), 1);
+        assert_eq!(writer.elements[0].1.matches("<polyline").count(), 2);
+
+        // Polylines fully behind the camera are left out
+        writer.add(&strip(&[[0.0; 3], [1.0, 0.0, 1.0]]), &default(), &default());
+        assert_eq!(writer.elements.len(), 1);
+    }
+
+    #[test]
+    fn farthest_polylines_are_drawn_first() {
+        let mut writer = writer();
+        let near = material(Color::srgb(1.0, 0.0, 0.0));
+        let far = material(Color::srgb(0.0, 0.0, 1.0));
+        writer.add(
+            &strip(&[[-1.0, 0.0, -4.0], [1.0, 0.0, -4.0]]),
+            &near,
+            &default(),
+        );
+        // Most of this line is behind the camera, only the drawn part sets its depth
+        writer.add(
+            &strip(&[[-1.0, 0.0, -8.0], [1.0, 0.0, -8.0], [1.0, 0.0, 50.0]]),
+            &far,
+            &default(),
+        );
+        let svg = writer.finish();
+        let (near_index, far_index) = (svg.find("#ff0000"), svg.find("#0000ff"));
+        assert!(far_index.unwrap() < near_index.unwrap());
+    }
+
+    #[test]
+    fn widths_taper_the_lines() {
+        let mut writer = writer();
+        let polyline = Polyline {
+            widths: vec![1.0, 3.0],
+            ..strip(&[[-1.0, 0.0, -5.0], [1.0, 0.0, -5.0]])
+        };
+        writer.add(&polyline, &default(), &default());
+        let svg = writer.finish();
+        assert!(!svg.contains("<polyline"));
+
+        // The quad is as wide as the material at the start and three times as wide at the end
+        let points = svg.split("points=\"").nth(1).unwrap();
+        let corners: Vec<Vec2> = points[..points.find('"').unwrap()]
+            .split(' ')
+            .map(|point| {
+                let (x, y) = point.split_once(',').unwrap();
+                Vec2::new(x.parse().unwrap(), y.parse().unwrap())
+            })
+            .collect();
+        let width = PolylineMaterial::default().width;
+        assert!((corners[3].y - corners[0].y - width).abs() < 0.1);
+        assert!((corners[2].y - corners[1].y - 3.0 * width).abs() < 0.1);
+    }
+}
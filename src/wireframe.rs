@@ -0,0 +1,333 @@
+use crate::{
+    polyline::{Polyline, PolylineHandle},
+    primitives::PolylineBuilder,
+};
+use bevy::{
+    asset::AssetEvents,
+    platform::collections::HashMap,
+    prelude::*,
+    render::mesh::{PrimitiveTopology, VertexAttributeValues},
+};
+
+/// A builder used for creating a [`Polyline`] from the edges of a [`Mesh`].
+///
+/// Vertices at the same position are merged, so each edge is only drawn once even when the
+/// faces of the mesh don't share their vertices, like with flat shaded meshes. Meshes without
+/// `f32` positions produce an empty polyline.
+///
+/// Silhouette edges, between the faces turned toward and away from the camera, aren't
+/// extracted: they change as the camera moves, which would rebuild the polyline every frame.
+/// [`feature_angle`](Self::feature_angle) gives an outline that doesn't depend on the view.
+#[derive(Clone, Copy, Debug)]
+pub struct PolylineWireframeBuilder<'a> {
+    pub mesh: &'a Mesh,
+    /// When set, only the edges where the faces meet at an angle in radians larger than this
+    /// are kept, along with the boundary edges. This outlines the shape of the mesh without
+    /// the edges inside flat or smooth areas.
+    ///
+    /// The edges of a triangle list are only known from its triangles, so the diagonals of
+    /// flat quads are also drawn unless this is set.
+    pub feature_angle: Option<f32>,
+}
+
+impl<'a> PolylineWireframeBuilder<'a> {
+    pub fn new(mesh: &'a Mesh) -> Self {
+        Self {
+            mesh,
+            feature_angle: None,
+        }
+    }
+
+    /// Only keeps the edges where the faces meet at an angle in radians larger than
+    /// `feature_angle`, along with the boundary edges.
+    pub fn feature_angle(mut self, feature_angle: f32) -> Self {
+        self.feature_angle = Some(feature_angle);
+        self
+    }
+}
+
+impl PolylineBuilder for PolylineWireframeBuilder<'_> {
+    fn build(&self) -> Polyline {
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            self.mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return Polyline::default();
+        };
+
+        // Merge the vertices at the same position
+        let mut vertices = Vec::new();
+        let mut vertex_indices = HashMap::<[u32; 3], usize>::default();
+        let welded: Vec<usize> = positions
+            .iter()
+            .map(|position| {
+                *vertex_indices
+                    .entry(position.map(f32::to_bits))
+                    .or_insert_with(|| {
+                        vertices.push(Vec3::from(*position));
+                        vertices.len() - 1
+                    })
+            })
+            .collect();
+        let indices: Vec<usize> = match self.mesh.indices() {
+            Some(indices) => indices.iter().collect(),
+            None => (0..positions.len()).collect(),
+        };
+        let index = |i: usize| indices.get(i).and_then(|&index| welded.get(index).copied());
+
+        let mut triangles = Vec::new();
+        let mut lines = Vec::new();
+        match self.mesh.primitive_topology() {
+            PrimitiveTopology::TriangleList => {
+                for i in (0..indices.len() / 3).map(|i| 3 * i) {
+                    triangles.push([index(i), index(i + 1), index(i + 2)]);
+                }
+            }
+            PrimitiveTopology::TriangleStrip => {
+                // Every other triangle of a strip is flipped to keep the same winding
+                for i in 0..indices.len().saturating_sub(2) {
+                    if i % 2 == 0 {
+                        triangles.push([index(i), index(i + 1), index(i + 2)]);
+                    } else {
+                        triangles.push([index(i + 1), index(i), index(i + 2)]);
+                    }
+                }
+            }
+            PrimitiveTopology::LineList => {
+                for i in (0..indices.len() / 2).map(|i| 2 * i) {
+                    lines.push([index(i), index(i + 1)]);
+                }
+            }
+            PrimitiveTopology::LineStrip => {
+                for i in 0..indices.len().saturating_sub(1) {
+                    lines.push([index(i), index(i + 1)]);
+                }
+            }
+            PrimitiveTopology::PointList => {}
+        }
+
+        // The normals of the faces around each edge, with the edge going from its lowest vertex
+        let mut edges = HashMap::<[usize; 2], Vec<Vec3>>::default();
+        for triangle in triangles {
+            let [Some(a), Some(b), Some(c)] = triangle else {
+                continue;
+            };
+            let normal = (vertices[b] - vertices[a]).cross(vertices[c] - vertices[a]);
+            let Some(normal) = normal.try_normalize() else {
+                continue;
+            };
+            for (start, end) in [(a, b), (b, c), (c, a)] {
+                edges
+                    .entry([start.min(end), start.max(end)])
+                    .or_default()
+                    .push(normal);
+            }
+        }
+        let mut kept_edges: Vec<[usize; 2]> = edges
+            .into_iter()
+            .filter(
+                |(_, normals)| match (self.feature_angle, normals.as_slice()) {
+                    (Some(feature_angle), [a, b]) => a.angle_between(*b) > feature_angle,
+                    _ => true,
+                },
+            )
+            .map(|(edge, _)| edge)
+            .collect();
+        for line in lines {
+            if let [Some(a), Some(b)] = line {
+                kept_edges.push([a.min(b), a.max(b)]);
+            }
+        }
+        // Hash maps iterate in any order, sort the edges to always build the same polyline
+        kept_edges.sort_unstable();
+        kept_edges.dedup();
+
+        Polyline::from_edges(&vertices, kept_edges)
+    }
+}
+
+/// Keeps the [`PolylineHandle`] of the entity pointing to the wireframe of `mesh`, updated
+/// when the mesh changes.
+///
+/// The wireframe is a new asset owned by the entity. Spawning it on the entity of the mesh,
+/// with a negative [`depth_bias`](crate::material::PolylineMaterial::depth_bias) on its
+/// material, draws thick lines over the faces of the mesh.
+///
+/// The mesh must be kept in the main world, with
+/// [`RenderAssetUsages::MAIN_WORLD`](bevy::asset::RenderAssetUsages::MAIN_WORLD).
+#[derive(Component, Debug, Clone)]
+#[require(PolylineHandle)]
+pub struct PolylineWireframe {
+    pub mesh: Handle<Mesh>,
+    /// See [`PolylineWireframeBuilder::feature_angle`].
+    pub feature_angle: Option<f32>,
+    /// Whether the current [`PolylineHandle`] was created for the wireframe.
+    generated: bool,
+}
+
+impl PolylineWireframe {
+    pub fn new(mesh: Handle<Mesh>) -> Self {
+        Self {
+            mesh,
+            feature_angle: None,
+            generated: false,
+        }
+    }
+
+    pub fn with_feature_angle(mut self, feature_angle: f32) -> Self {
+        self.feature_angle = Some(feature_angle);
+        self
+    }
+}
+
+/// Updates the polylines with a [`PolylineWireframe`].
+pub struct PolylineWireframePlugin;
+
+impl Plugin for PolylineWireframePlugin {
+    fn build(&self, app: &mut App) {
+        // Before the asset events are sent, so the rebuilt polylines are drawn in the same frame.
+        // Mesh changes are only seen in the frame after, once their events are sent.
+        app.add_systems(PostUpdate, update_wireframes.before(AssetEvents));
+    }
+}
+
+pub fn update_wireframes(
+    mut wireframes: Query<(&mut PolylineWireframe, &mut PolylineHandle)>,
+    meshes: Res<Assets<Mesh>>,
+    mut polylines: ResMut<Assets<Polyline>>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+) {
+    let modified: Vec<_> = mesh_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Added { id }
+            | AssetEvent::Modified { id }
+            | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (mut wireframe, mut handle) in &mut wireframes {
+        if !wireframe.is_changed() && !modified.contains(&wireframe.mesh.id()) {
+            continue;
+        }
+        let Some(mesh) = meshes.get(&wireframe.mesh) else {
+            continue;
+        };
+        let polyline = PolylineWireframeBuilder {
+            mesh,
+            feature_angle: wireframe.feature_angle,
+        }
+        .build();
+        match polylines.get_mut(&handle.0) {
+            Some(existing) if wireframe.generated => *existing = polyline,
+            _ => handle.0 = polylines.add(polyline),
+        }
+        // Bypass change detection so this doesn't update the wireframe again next frame
+        wireframe.bypass_change_detection().generated = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::mesh::{Indices, MeshBuilder, Meshable};
+
+    fn edge_count(mesh: &Mesh, feature_angle: Option<f32>) -> usize {
+        PolylineWireframeBuilder {
+            mesh,
+            feature_angle,
+        }
+        .build()
+        .segments()
+        .count()
+    }
+
+    #[test]
+    fn vertices_at_the_same_position_are_welded() {
+        // Each face of the cuboid has its own vertices
+        let mesh = Cuboid::default().mesh().build();
+        assert_eq!(mesh.count_vertices(), 24);
+        let polyline = PolylineWireframeBuilder::new(&mesh).build();
+        let mut vertices = polyline.vertices.clone();
+        vertices.sort_by(|a, b| a.to_array().partial_cmp(&b.to_array()).unwrap());
+        vertices.dedup();
+        assert_eq!(vertices.len(), 8);
+        // The 12 edges of the cuboid and a diagonal per face
+        assert_eq!(edge_count(&mesh, None), 18);
+
+        // Lines drawn twice are only kept once
+        let mut lines = Mesh::new(PrimitiveTopology::LineList, default());
+        lines.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![[0.0; 3], [1.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0; 3]],
+        );
+        assert_eq!(edge_count(&lines, None), 1);
+    }
+
+    #[test]
+    fn feature_angle_keeps_the_edges_between_bent_faces() {
+        let mesh = Cuboid::default().mesh().build();
+        for angle in [0.0, 30f32.to_radians()] {
+            assert_eq!(edge_count(&mesh, Some(angle)), 12);
+        }
+        assert_eq!(edge_count(&mesh, Some(90f32.to_radians())), 0);
+
+        // Boundary edges are always kept
+        let mut quad = Mesh::new(PrimitiveTopology::TriangleList, default());
+        quad.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![[0.0; 3], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+        );
+        quad.insert_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]));
+        assert_eq!(edge_count(&quad, None), 5);
+        assert_eq!(edge_count(&quad, Some(0.0)), 4);
+    }
+
+    #[test]
+    fn wireframes_are_rebuilt_when_the_mesh_changes() {
+        let mut app = App::new();
+        app.add_plugins((
+            TaskPoolPlugin::default(),
+            AssetPlugin::default(),
+            PolylineWireframePlugin,
+        ))
+        .init_asset::<Mesh>()
+        .init_asset::<Polyline>();
+
+        let mesh = app
+            .world_mut()
+            .resource_mut::<Assets<Mesh>>()
+            .add(Cuboid::default());
+        let entity = app
+            .world_mut()
+            .spawn(PolylineWireframe::new(mesh.clone()).with_feature_angle(0.0))
+            .id();
+        let wireframe_edges = |app: &mut App| {
+            app.update();
+            let world = app.world();
+            let handle = &world.get::<PolylineHandle>(entity).unwrap().0;
+            let polyline = world.resource::<Assets<Polyline>>().get(handle).unwrap();
+            (handle.id(), polyline.segments().count())
+        };
+        let (id, edges) = wireframe_edges(&mut app);
+        assert_eq!(edges, 12);
+
+        // The polyline is updated in place, the frame after the mesh
+        app.world_mut()
+            .resource_mut::<Assets<Mesh>>()
+            .insert(&mesh, Tetrahedron::default().mesh().build());
+        app.update();
+        assert_eq!(wireframe_edges(&mut app), (id, 6));
+
+        app.world_mut()
+            .get_mut::<PolylineWireframe>(entity)
+            .unwrap()
+            .feature_angle = None;
+        assert_eq!(wireframe_edges(&mut app), (id, 6));
+        app.world_mut()
+            .resource_mut::<Assets<Mesh>>()
+            .insert(&mesh, Cuboid::default().mesh().build());
+        app.update();
+        assert_eq!(wireframe_edges(&mut app), (id, 18));
+    }
+}